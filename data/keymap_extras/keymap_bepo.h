@@ -0,0 +1,122 @@
+// Snapshot of quantum/keymap_extras/keymap_bepo.h from qmk_firmware, trimmed down to the
+// keycode definitions of the base, Shift and AltGr levels.
+
+#pragma once
+#include "keycodes.h"
+// clang-format off
+
+// Aliases
+#define BP_DLR  KC_GRV  // $
+#define BP_DQUO KC_1    // "
+#define BP_LDAQ KC_2    // «
+#define BP_RDAQ KC_3    // »
+#define BP_LPRN KC_4    // (
+#define BP_RPRN KC_5    // )
+#define BP_AT   KC_6    // @
+#define BP_PLUS KC_7    // +
+#define BP_MINS KC_8    // -
+#define BP_SLSH KC_9    // /
+#define BP_ASTR KC_0    // *
+#define BP_EQL  KC_MINS // =
+#define BP_PERC KC_EQL  // %
+#define BP_B    KC_Q    // B
+#define BP_EACU KC_W    // É
+#define BP_P    KC_E    // P
+#define BP_O    KC_R    // O
+#define BP_EGRV KC_T    // È
+#define BP_DCIR KC_Y    // ^ (dead)
+#define BP_V    KC_U    // V
+#define BP_D    KC_I    // D
+#define BP_L    KC_O    // L
+#define BP_J    KC_P    // J
+#define BP_Z    KC_LBRC // Z
+#define BP_W    KC_RBRC // W
+#define BP_A    KC_A    // A
+#define BP_U    KC_S    // U
+#define BP_I    KC_D    // I
+#define BP_E    KC_F    // E
+#define BP_COMM KC_G    // ,
+#define BP_C    KC_H    // C
+#define BP_T    KC_J    // T
+#define BP_S    KC_K    // S
+#define BP_R    KC_L    // R
+#define BP_N    KC_SCLN // N
+#define BP_M    KC_QUOT // M
+#define BP_CCED KC_NUHS // Ç
+#define BP_ECIR KC_NUBS // Ê
+#define BP_AGRV KC_Z    // À
+#define BP_Y    KC_X    // Y
+#define BP_X    KC_C    // X
+#define BP_DOT  KC_V    // .
+#define BP_K    KC_B    // K
+#define BP_QUOT KC_N    // '
+#define BP_Q    KC_M    // Q
+#define BP_G    KC_COMM // G
+#define BP_H    KC_DOT  // H
+#define BP_F    KC_SLSH // F
+
+#define BP_HASH S(BP_DLR)  // #
+#define BP_1    S(BP_DQUO) // 1
+#define BP_2    S(BP_LDAQ) // 2
+#define BP_3    S(BP_RDAQ) // 3
+#define BP_4    S(BP_LPRN) // 4
+#define BP_5    S(BP_RPRN) // 5
+#define BP_6    S(BP_AT)   // 6
+#define BP_7    S(BP_PLUS) // 7
+#define BP_8    S(BP_MINS) // 8
+#define BP_9    S(BP_SLSH) // 9
+#define BP_0    S(BP_ASTR) // 0
+#define BP_DEG  S(BP_EQL)  // °
+#define BP_GRV  S(BP_PERC) // `
+#define BP_EXLM S(BP_DCIR) // !
+#define BP_SCLN S(BP_COMM) // ;
+#define BP_COLN S(BP_DOT)  // :
+#define BP_QUES S(BP_QUOT) // ?
+
+#define BP_NDSH ALGR(BP_DLR)  // –
+#define BP_MDSH ALGR(BP_DQUO) // —
+#define BP_LABK ALGR(BP_LDAQ) // <
+#define BP_RABK ALGR(BP_RDAQ) // >
+#define BP_LBRC ALGR(BP_LPRN) // [
+#define BP_RBRC ALGR(BP_RPRN) // ]
+#define BP_CIRC ALGR(BP_AT)   // ^
+#define BP_PLMN ALGR(BP_PLUS) // ±
+#define BP_MMNS ALGR(BP_MINS) // −
+#define BP_DIV  ALGR(BP_SLSH) // ÷
+#define BP_MUL  ALGR(BP_ASTR) // ×
+#define BP_NEQL ALGR(BP_EQL)  // ≠
+#define BP_PERM ALGR(BP_PERC) // ‰
+#define BP_PIPE ALGR(BP_B)    // |
+#define BP_DACU ALGR(BP_EACU) // ´ (dead)
+#define BP_AMPR ALGR(BP_P)    // &
+#define BP_OE   ALGR(BP_O)    // Œ
+#define BP_DGRV ALGR(BP_EGRV) // ` (dead)
+#define BP_IEXL ALGR(BP_DCIR) // ¡
+#define BP_DCAR ALGR(BP_V)    // ˇ (dead)
+#define BP_ETH  ALGR(BP_D)    // Ð
+#define BP_IJ   ALGR(BP_J)    // Ĳ
+#define BP_SCHW ALGR(BP_Z)    // Ə
+#define BP_DBRV ALGR(BP_W)    // ˘ (dead)
+#define BP_AE   ALGR(BP_A)    // Æ
+#define BP_UGRV ALGR(BP_U)    // Ù
+#define BP_DIAE ALGR(BP_I)    // ¨ (dead)
+#define BP_EURO ALGR(BP_E)    // €
+#define BP_RSQU ALGR(BP_COMM) // ’
+#define BP_COPY ALGR(BP_C)    // ©
+#define BP_THRN ALGR(BP_T)    // Þ
+#define BP_SS   ALGR(BP_S)    // ß
+#define BP_REGD ALGR(BP_R)    // ®
+#define BP_DTIL ALGR(BP_N)    // ~ (dead)
+#define BP_MACR ALGR(BP_M)    // ¯ (dead)
+#define BP_DCED ALGR(BP_CCED) // ¸ (dead)
+#define BP_BSLS ALGR(BP_AGRV) // (backslash)
+#define BP_LCBR ALGR(BP_Y)    // {
+#define BP_RCBR ALGR(BP_X)    // }
+#define BP_ELLP ALGR(BP_DOT)  // …
+#define BP_TILD ALGR(BP_K)    // ~
+#define BP_IQUE ALGR(BP_QUOT) // ¿
+#define BP_RNGA ALGR(BP_Q)    // ° (dead)
+#define BP_MICR ALGR(BP_G)    // µ
+#define BP_DAGG ALGR(BP_H)    // †
+#define BP_OGON ALGR(BP_F)    // ˛ (dead)
+#define BP_UNDS ALGR(KC_SPC)  // _
@@ -0,0 +1,77 @@
+// Snapshot of quantum/keymap_extras/keymap_ergol.h from qmk_firmware, trimmed down to the
+// keycode definitions of the base and Shift levels.
+
+#pragma once
+#include "keycodes.h"
+// clang-format off
+
+// Aliases
+#define ER_GRV  KC_GRV  // `
+#define ER_1    KC_1    // 1
+#define ER_2    KC_2    // 2
+#define ER_3    KC_3    // 3
+#define ER_4    KC_4    // 4
+#define ER_5    KC_5    // 5
+#define ER_6    KC_6    // 6
+#define ER_7    KC_7    // 7
+#define ER_8    KC_8    // 8
+#define ER_9    KC_9    // 9
+#define ER_0    KC_0    // 0
+#define ER_SLSH KC_MINS // /
+#define ER_EQL  KC_EQL  // =
+#define ER_Q    KC_Q    // Q
+#define ER_C    KC_W    // C
+#define ER_O    KC_E    // O
+#define ER_P    KC_R    // P
+#define ER_W    KC_T    // W
+#define ER_J    KC_Y    // J
+#define ER_M    KC_U    // M
+#define ER_D    KC_I    // D
+#define ER_ODK  KC_O    // ★ (dead)
+#define ER_Y    KC_P    // Y
+#define ER_LBRC KC_LBRC // [
+#define ER_RBRC KC_RBRC // ]
+#define ER_A    KC_A    // A
+#define ER_S    KC_S    // S
+#define ER_E    KC_D    // E
+#define ER_N    KC_F    // N
+#define ER_F    KC_G    // F
+#define ER_L    KC_H    // L
+#define ER_R    KC_J    // R
+#define ER_T    KC_K    // T
+#define ER_I    KC_L    // I
+#define ER_U    KC_SCLN // U
+#define ER_QUOT KC_QUOT // '
+#define ER_BSLS KC_BSLS // (backslash)
+#define ER_Z    KC_Z    // Z
+#define ER_X    KC_X    // X
+#define ER_MINS KC_C    // -
+#define ER_V    KC_V    // V
+#define ER_B    KC_B    // B
+#define ER_DOT  KC_N    // .
+#define ER_H    KC_M    // H
+#define ER_G    KC_COMM // G
+#define ER_COMM KC_DOT  // ,
+#define ER_K    KC_SLSH // K
+
+#define ER_TILD S(ER_GRV)  // ~
+#define ER_EURO S(ER_1)    // €
+#define ER_LDAQ S(ER_2)    // «
+#define ER_RDAQ S(ER_3)    // »
+#define ER_DLR  S(ER_4)    // $
+#define ER_PERC S(ER_5)    // %
+#define ER_CIRC S(ER_6)    // ^
+#define ER_AMPR S(ER_7)    // &
+#define ER_ASTR S(ER_8)    // *
+#define ER_HASH S(ER_9)    // #
+#define ER_AT   S(ER_0)    // @
+#define ER_UNDS S(ER_SLSH) // _
+#define ER_PLUS S(ER_EQL)  // +
+#define ER_EXLM S(ER_ODK)  // !
+#define ER_LCBR S(ER_LBRC) // {
+#define ER_RCBR S(ER_RBRC) // }
+#define ER_DQUO S(ER_QUOT) // "
+#define ER_PIPE S(ER_BSLS) // |
+#define ER_QUES S(ER_MINS) // ?
+#define ER_COLN S(ER_DOT)  // :
+#define ER_SCLN S(ER_COMM) // ;
@@ -0,0 +1,73 @@
+use std::path::Path;
+use std::process::Command;
+
+pub struct Commit {
+    pub hash: String,
+    pub subject: String,
+}
+
+fn git_dir(path: &Path) -> &Path {
+    path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))
+}
+
+// Revisions and ranges come from the command line, the ones starting with `-` would be read by git
+// as options
+fn check_revision(revision: &str) -> Result<&str, &'static str> {
+    match revision.starts_with('-') {
+        true => Err("invalid git revision"),
+        false => Ok(revision),
+    }
+}
+
+// Returns the content of `path` at `revision` in the local git repository containing `path`.
+// Only the local repository is used, nothing is fetched.
+pub fn show(revision: &str, path: &Path) -> Result<String, &'static str> {
+    let revision = check_revision(revision)?;
+    let dir = git_dir(path);
+    let file = path.file_name().ok_or("invalid file name")?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("{revision}:./{}", file.to_string_lossy()))
+        .output()
+        .map_err(|_| "Unable to run git")?;
+    if !output.status.success() {
+        return Err("file not found at this git revision");
+    }
+    String::from_utf8(output.stdout).map_err(|_| "file is not valid UTF-8")
+}
+
+// Returns the commits of `range` modifying `path`, from the oldest to the newest one
+pub fn log(range: &str, path: &Path) -> Result<Vec<Commit>, &'static str> {
+    let range = check_revision(range)?;
+    let file = path.file_name().ok_or("invalid file name")?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(git_dir(path))
+        .args(["log", "--reverse", "--format=%H %s", range, "--"])
+        .arg(file)
+        .output()
+        .map_err(|_| "Unable to run git")?;
+    if !output.status.success() {
+        return Err("invalid git revision range");
+    }
+    let log = String::from_utf8(output.stdout).map_err(|_| "git log is not valid UTF-8")?;
+    Ok(log
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(hash, subject)| Commit { hash: hash.to_string(), subject: subject.to_string() })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_are_not_revisions() {
+        let path = Path::new("keymap.json");
+        assert_eq!(show("--output=/tmp/x", path).err(), Some("invalid git revision"));
+        assert_eq!(log("-p", path).err(), Some("invalid git revision"));
+    }
+}
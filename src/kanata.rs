@@ -0,0 +1,3 @@
+pub mod keycodes;
+pub mod parser;
+pub mod writer;
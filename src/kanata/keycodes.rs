@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use crate::qmk::keycodes;
+use crate::qmk::keymap_extras::US_SHIFTED;
+
+// (QMK keycode, Kanata names), the first Kanata name is the one written in Kanata configs.
+// Letters, digits, function keys and keypad digits are generated by `keycodes`.
+const KEYCODES: &[(&str, &[&str])] = &[
+    ("KC_GRV", &["grv", "`"]),
+    ("KC_MINS", &["min", "-"]),
+    ("KC_EQL", &["eql", "="]),
+    ("KC_BSPC", &["bspc", "bks"]),
+    ("KC_TAB", &["tab"]),
+    ("KC_LBRC", &["lbrc", "["]),
+    ("KC_RBRC", &["rbrc", "]"]),
+    ("KC_BSLS", &["bksl", "\\"]),
+    ("KC_CAPS", &["caps"]),
+    ("KC_SCLN", &["scln", ";"]),
+    ("KC_QUOT", &["apo", "'"]),
+    ("KC_ENT", &["ret", "ent", "enter", "return"]),
+    ("KC_COMM", &["comm", ","]),
+    ("KC_DOT", &["dot", "."]),
+    ("KC_SLSH", &["slsh", "/"]),
+    ("KC_SPC", &["spc"]),
+    ("KC_ESC", &["esc"]),
+    ("KC_INS", &["ins"]),
+    ("KC_DEL", &["del"]),
+    ("KC_HOME", &["home"]),
+    ("KC_END", &["end"]),
+    ("KC_PGUP", &["pgup"]),
+    ("KC_PGDN", &["pgdn"]),
+    ("KC_LEFT", &["left"]),
+    ("KC_RGHT", &["rght", "right"]),
+    ("KC_UP", &["up"]),
+    ("KC_DOWN", &["down"]),
+    ("KC_PSCR", &["prnt"]),
+    ("KC_SCRL", &["slck"]),
+    ("KC_PAUS", &["pause"]),
+    ("KC_NUM", &["nlck"]),
+    ("KC_PSLS", &["kp/"]),
+    ("KC_PAST", &["kp*"]),
+    ("KC_PMNS", &["kp-"]),
+    ("KC_PPLS", &["kp+"]),
+    ("KC_PENT", &["kprt"]),
+    ("KC_PDOT", &["kp."]),
+    ("KC_APP", &["menu", "comp"]),
+    ("KC_MUTE", &["mute"]),
+    ("KC_VOLU", &["volu"]),
+    ("KC_VOLD", &["vold"]),
+    ("KC_MPLY", &["pp"]),
+    ("KC_MNXT", &["next"]),
+    ("KC_MPRV", &["prev"]),
+    ("KC_MSTP", &["stop"]),
+    ("KC_BRIU", &["brup"]),
+    ("KC_BRID", &["brdown", "brdwn"]),
+    ("KC_LCTL", &["lctl"]),
+    ("KC_LSFT", &["lsft"]),
+    ("KC_LALT", &["lalt"]),
+    ("KC_LGUI", &["lmet", "lgui", "lwin"]),
+    ("KC_RCTL", &["rctl"]),
+    ("KC_RSFT", &["rsft"]),
+    ("KC_RALT", &["ralt"]),
+    ("KC_RGUI", &["rmet", "rgui", "rwin"]),
+];
+
+// (QMK modifier, Kanata chord prefix), `LCTL(KC_C)` is `C-c` in Kanata
+pub const MOD_PREFIXES: &[(&str, &str)] = &[
+    ("LCTL", "C-"),
+    ("LSFT", "S-"),
+    ("LALT", "A-"),
+    ("LGUI", "M-"),
+    ("RCTL", "RC-"),
+    ("RSFT", "RS-"),
+    ("RALT", "RA-"),
+    ("RGUI", "RM-"),
+];
+
+// QMK modifier aliases, `SFT_T()` is `LSFT_T()` and `S()` is `LSFT()`
+const MOD_ALIASES: &[(&str, &str)] = &[
+    ("C", "LCTL"),
+    ("CTL", "LCTL"),
+    ("S", "LSFT"),
+    ("SFT", "LSFT"),
+    ("A", "LALT"),
+    ("ALT", "LALT"),
+    ("LOPT", "LALT"),
+    ("G", "LGUI"),
+    ("GUI", "LGUI"),
+    ("LCMD", "LGUI"),
+    ("LWIN", "LGUI"),
+    ("ROPT", "RALT"),
+    ("ALGR", "RALT"),
+    ("RCMD", "RGUI"),
+    ("RWIN", "RGUI"),
+];
+
+// (QMK keycode, Kanata names) for all the known keycodes
+fn keycodes() -> Vec<(String, Vec<String>)> {
+    let mut keycodes: Vec<(String, Vec<String>)> = Vec::new();
+    for c in 'a'..='z' {
+        keycodes.push((format!("KC_{}", c.to_ascii_uppercase()), vec![c.to_string()]));
+    }
+    for n in 0..10 {
+        keycodes.push((format!("KC_{n}"), vec![n.to_string()]));
+        keycodes.push((format!("KC_P{n}"), vec![format!("kp{n}")]));
+    }
+    for n in 1..=24 {
+        keycodes.push((format!("KC_F{n}"), vec![format!("f{n}")]));
+    }
+    for (qmk, kanata) in KEYCODES {
+        keycodes.push((qmk.to_string(), kanata.iter().map(|name| name.to_string()).collect()));
+    }
+    keycodes
+}
+
+lazy_static! {
+    // Kanata name -> QMK keycode
+    static ref TO_QMK: HashMap<String, String> = keycodes()
+        .into_iter()
+        .flat_map(|(qmk, kanata)| kanata.into_iter().map(move |name| (name, qmk.to_string())))
+        .collect();
+    // QMK basic keycode alias -> keycode of the table, `KC_ENTER` -> `KC_ENT`
+    static ref QMK_ALIASES: HashMap<String, String> = {
+        let values: HashMap<String, u16> = keycodes::basic_keycodes()
+            .into_iter()
+            .flat_map(|(value, name, aliases)| aliases.into_iter().chain([name]).map(move |alias| (alias, value)))
+            .collect();
+        let mut aliases = HashMap::new();
+        for (qmk, _) in keycodes() {
+            let Some(value) = values.get(&qmk) else {
+                continue;
+            };
+            for (alias, _) in values.iter().filter(|(_, v)| *v == value) {
+                aliases.insert(alias.to_string(), qmk.to_string());
+            }
+        }
+        aliases
+    };
+    // QMK keycode -> Kanata name
+    static ref TO_KANATA: HashMap<String, String> = keycodes()
+        .into_iter()
+        .filter_map(|(qmk, kanata)| kanata.into_iter().next().map(|name| (qmk, name)))
+        .collect();
+}
+
+fn canonical_modifier(qmk: &str) -> &str {
+    MOD_ALIASES.iter().find(|(alias, _)| *alias == qmk).map_or(qmk, |(_, modifier)| modifier)
+}
+
+// QMK keycode of a Kanata key name, `S-1` -> `KC_EXLM`, `C-c` -> `LCTL(KC_C)`
+pub fn to_qmk(kanata: &str) -> Option<String> {
+    for (modifier, prefix) in MOD_PREFIXES {
+        let Some(key) = kanata.strip_prefix(prefix).filter(|key| !key.is_empty()) else {
+            continue;
+        };
+        let keycode = to_qmk(key)?;
+        // shifted symbols have their own keycode
+        if *modifier == "LSFT" {
+            if let Some((shifted, _, _)) = US_SHIFTED.iter().find(|(_, unshifted, _)| *unshifted == keycode) {
+                return Some(shifted.to_string());
+            }
+        }
+        return Some(format!("{modifier}({keycode})"));
+    }
+    TO_QMK.get(kanata).map(|qmk| qmk.to_string())
+}
+
+// Kanata key name of a QMK keycode, modifiers included, `LCTL(KC_C)` -> `C-c`
+pub fn to_kanata(qmk: &str) -> Option<String> {
+    if let Some((modifier, key)) = qmk.strip_suffix(')').and_then(|k| k.split_once('(')) {
+        let modifier = canonical_modifier(modifier);
+        let (_, prefix) = MOD_PREFIXES.iter().find(|(m, _)| *m == modifier)?;
+        return Some(format!("{prefix}{}", to_kanata(key.trim())?));
+    }
+    if let Some((_, unshifted, _)) = US_SHIFTED.iter().find(|(shifted, _, _)| *shifted == qmk) {
+        return Some(format!("S-{}", to_kanata(unshifted)?));
+    }
+    let keycode = QMK_ALIASES.get(qmk).map_or(qmk, |k| k.as_str());
+    TO_KANATA.get(keycode).map(|name| name.to_string())
+}
+
+// QMK modifier of a Kanata modifier key name, `lmet` -> `LGUI`
+pub fn modifier_to_qmk(kanata: &str) -> Option<String> {
+    let modifier = to_qmk(kanata)?.strip_prefix("KC_")?.to_string();
+    MOD_PREFIXES.iter().any(|(m, _)| *m == modifier).then_some(modifier)
+}
+
+// Kanata modifier key name of a QMK modifier, `SFT` -> `lsft`
+pub fn modifier_to_kanata(qmk: &str) -> Option<String> {
+    let modifier = canonical_modifier(qmk);
+    MOD_PREFIXES.iter().any(|(m, _)| *m == modifier).then(|| to_kanata(&format!("KC_{modifier}")))?
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{Key, Keymap};
+use crate::kanata::keycodes;
+use crate::qmk::parser;
+
+// Kanata configuration expression, atoms have the line they are on
+#[derive(Clone, Debug)]
+enum Expr {
+    Atom(String, usize),
+    List(Vec<Expr>),
+}
+
+impl Expr {
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Expr::Atom(atom, _) => Some(atom),
+            Expr::List(_) => None,
+        }
+    }
+
+    // Kanata syntax of the expression, for the actions which are kept as is
+    fn to_text(&self) -> String {
+        match self {
+            Expr::Atom(atom, _) => atom.to_string(),
+            Expr::List(items) => format!("({})", items.iter().map(|i| i.to_text()).collect::<Vec<_>>().join(" ")),
+        }
+    }
+}
+
+// Parses the top-level lists of a configuration, `;;` line comments and `#| |#` block comments
+// are skipped
+fn parse_exprs(data: &str) -> Result<Vec<Expr>, &'static str> {
+    let chars: Vec<char> = data.chars().collect();
+    let mut stack: Vec<Vec<Expr>> = vec![Vec::new()];
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => line += 1,
+            ';' if chars.get(i + 1) == Some(&';') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '#' if chars.get(i + 1) == Some(&'|') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '|' && chars.get(i + 1) == Some(&'#')) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                i += 2;
+                continue;
+            }
+            '(' => stack.push(Vec::new()),
+            ')' => {
+                let list = stack.pop().ok_or("unbalanced parentheses")?;
+                stack.last_mut().ok_or("unbalanced parentheses")?.push(Expr::List(list));
+            }
+            '"' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += 1;
+                }
+                let atom: String = chars[start..(i + 1).min(chars.len())].iter().collect();
+                stack.last_mut().ok_or("unbalanced parentheses")?.push(Expr::Atom(atom, line));
+            }
+            _ if c.is_whitespace() => (),
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '(' && chars[i] != ')' {
+                    i += 1;
+                }
+                let atom: String = chars[start..i].iter().collect();
+                stack.last_mut().ok_or("unbalanced parentheses")?.push(Expr::Atom(atom, line));
+                continue;
+            }
+        }
+        i += 1;
+    }
+    if stack.len() != 1 {
+        return Err("unbalanced parentheses");
+    }
+    Ok(stack.pop().unwrap())
+}
+
+struct Context<'a> {
+    layer_names: &'a LayerNames,
+    aliases: HashMap<String, Expr>,
+}
+
+impl Context<'_> {
+    fn layer(&self, name: &str) -> Option<String> {
+        self.layer_names.index(name).map(|_| parser::layer_id(name))
+    }
+
+    // QMK keycode of a Kanata action, for the actions which have a QMK equivalent
+    fn keycode(&self, action: &Expr, depth: usize) -> Option<String> {
+        let items = match action {
+            Expr::Atom(atom, _) => {
+                return match atom.as_str() {
+                    "_" => Some(String::from("KC_TRNS")),
+                    "XX" | "✗" | "∅" | "•" => Some(String::from("KC_NO")),
+                    _ => match atom.strip_prefix('@') {
+                        // aliases may refer to other aliases
+                        Some(alias) if depth < 8 => self.keycode(self.aliases.get(alias)?, depth + 1),
+                        Some(_) => None,
+                        None => keycodes::to_qmk(atom),
+                    },
+                };
+            }
+            Expr::List(items) => items,
+        };
+        let args: Vec<&str> = items.iter().skip(1).filter_map(|i| i.atom()).collect();
+        let keycode = match (items.first()?.atom()?, args.as_slice()) {
+            ("layer-while-held" | "layer-toggle", [layer]) => format!("MO({})", self.layer(layer)?),
+            ("layer-switch", [layer]) => format!("DF({})", self.layer(layer)?),
+            ("one-shot" | "one-shot-press" | "one-shot-release", [_, modifier]) => {
+                format!("OSM(MOD_{})", keycodes::modifier_to_qmk(modifier)?)
+            }
+            ("tap-hold" | "tap-hold-press" | "tap-hold-release", _) if items.len() == 5 => {
+                let tap = self.keycode(&items[3], depth + 1)?;
+                let hold = match &items[4] {
+                    Expr::Atom(alias, _) if alias.starts_with('@') && depth < 8 => self.aliases.get(&alias[1..])?,
+                    hold => hold,
+                };
+                match hold {
+                    Expr::Atom(modifier, _) => format!("{}_T({tap})", keycodes::modifier_to_qmk(modifier)?),
+                    Expr::List(hold) => match (hold.first()?.atom()?, hold.get(1).and_then(|l| l.atom())) {
+                        ("layer-while-held" | "layer-toggle", Some(layer)) => format!("LT({}, {tap})", self.layer(layer)?),
+                        _ => return None,
+                    },
+                }
+            }
+            _ => return None,
+        };
+        Some(keycode)
+    }
+
+    // Actions without a QMK equivalent are kept as custom keys named after the action
+    fn key(&self, action: &Expr) -> Key {
+        match self.keycode(action, 0) {
+            Some(keycode) => parser::parse_keycode(&keycode, self.layer_names),
+            None => Key::Custom(action.to_text()),
+        }
+    }
+}
+
+// Reads a Kanata configuration. The layer names come from the `deflayer`s, the layers are split
+// in rows following the lines of `defsrc`, shorter lines being padded with `KC_NO`.
+pub fn keymap_from_str(data: &str) -> Result<(Keymap, LayerNames), &'static str> {
+    let exprs = parse_exprs(data)?;
+    // line of each defsrc key
+    let mut source: Vec<(String, usize)> = Vec::new();
+    let mut layers: Vec<(String, Vec<Expr>)> = Vec::new();
+    let mut aliases = HashMap::new();
+    for expr in &exprs {
+        let Expr::List(items) = expr else {
+            continue;
+        };
+        match items.first().and_then(|i| i.atom()) {
+            Some("defsrc") => {
+                source = items[1..]
+                    .iter()
+                    .map(|i| match i {
+                        Expr::Atom(name, line) => (name.to_string(), *line),
+                        Expr::List(_) => (i.to_text(), 0),
+                    })
+                    .collect()
+            }
+            Some("deflayer") => {
+                let name = items.get(1).and_then(|i| i.atom()).ok_or("deflayer without a name")?;
+                layers.push((name.to_string(), items[2..].to_vec()));
+            }
+            Some("defalias") => {
+                for pair in items[1..].chunks(2) {
+                    if let [Expr::Atom(name, _), action] = pair {
+                        aliases.insert(name.to_string(), action.clone());
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    if source.is_empty() || layers.is_empty() {
+        return Err("no defsrc or deflayer");
+    }
+    if layers.iter().any(|(_, keys)| keys.len() != source.len()) {
+        return Err("deflayer with a different number of keys than defsrc");
+    }
+
+    // (row, column) of each defsrc key
+    let mut positions = Vec::new();
+    let mut row = 0;
+    let mut column = 0;
+    for (i, (_, line)) in source.iter().enumerate() {
+        if i > 0 && *line != source[i - 1].1 {
+            row += 1;
+            column = 0;
+        }
+        positions.push((row, column));
+        column += 1;
+    }
+    let num_rows = row + 1;
+    let num_columns = positions.iter().map(|(_, c)| c + 1).max().unwrap_or(1);
+
+    let mut source_keys = vec![None; num_rows * num_columns];
+    for ((name, _), (row, column)) in source.iter().zip(&positions) {
+        source_keys[row * num_columns + column] = Some(name.to_string());
+    }
+
+    let layer_names = LayerNames::new(layers.iter().map(|(name, _)| name.to_string()).collect());
+    let context = Context {
+        layer_names: &layer_names,
+        aliases,
+    };
+    let layers = layers
+        .iter()
+        .map(|(_, actions)| {
+            let mut keys = vec![Key::No; num_rows * num_columns];
+            for (action, (row, column)) in actions.iter().zip(&positions) {
+                keys[row * num_columns + column] = context.key(action);
+            }
+            keys
+        })
+        .collect();
+    let keymap = Keymap {
+        keymap: String::from("kanata"),
+        // no QMK keyboard, the keymap is drawn as an ortho layout
+        keyboard: String::new(),
+        layout: String::new(),
+        num_rows,
+        num_columns,
+        layers,
+        tap_dances: HashMap::new(),
+        combos: Vec::new(),
+        macros: Vec::new(),
+        custom_keycodes: HashMap::new(),
+        key_overrides: Vec::new(),
+        encoders: Vec::new(),
+        extra_fields: serde_json::Map::new(),
+        info_json: None,
+        source_keys,
+    };
+    Ok((keymap, layer_names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+;; comment
+(defcfg process-unmapped-keys yes)
+
+(defsrc
+  q w e
+  a s
+)
+
+#| block
+   comment |#
+(defalias
+  hma (tap-hold 200 200 a lctl)
+  nav (layer-while-held nav)
+  lts (tap-hold 200 200 spc @nav)
+)
+
+(deflayer base
+  q @hma @lts
+  @nav (unicode é)
+)
+
+(deflayer nav
+  _ XX (layer-switch base)
+  (one-shot 500 lsft) left
+)
+"#;
+
+    #[test]
+    fn configs_are_parsed() {
+        let (keymap, layer_names) = keymap_from_str(CONFIG).unwrap();
+        assert_eq!(layer_names.names(), &[String::from("base"), String::from("nav")]);
+        assert_eq!((keymap.num_rows, keymap.num_columns), (2, 3));
+        let keycodes: Vec<Vec<String>> = keymap.layers.iter().map(|layer| layer.iter().map(parser::keycode).collect()).collect();
+        // the second row is shorter than defsrc's first one
+        assert_eq!(keycodes[0], ["KC_Q", "LCTL_T(KC_A)", "LT(_NAV, KC_SPC)", "MO(_NAV)", "(unicode é)", "KC_NO"]);
+        assert_eq!(keycodes[1], ["KC_TRNS", "KC_NO", "DF(_BASE)", "OSM(MOD_LSFT)", "KC_LEFT", "KC_NO"]);
+        assert_eq!(keymap.source_keys, [Some("q"), Some("w"), Some("e"), Some("a"), Some("s"), None].map(|k| k.map(String::from)));
+    }
+
+    #[test]
+    fn unbalanced_parentheses_are_an_error() {
+        assert_eq!(keymap_from_str("(defsrc a b").err(), Some("unbalanced parentheses"));
+        assert_eq!(keymap_from_str("(defsrc a))").err(), Some("unbalanced parentheses"));
+    }
+}
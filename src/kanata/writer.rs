@@ -0,0 +1,258 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{CustomAction, Key, Keymap, Unsupported};
+use crate::kanata::keycodes;
+use crate::qmk::parser;
+
+// Laptop keys, with their Kanata names, at the positions of an ortho keymap: the number row and
+// the four rows below it. The bottom row keys are under the ortho columns they are the closest to,
+// the thumb positions left are given the right Meta, menu and right Ctrl keys, which not all
+// laptops have.
+const LAPTOP_ROWS: &[&[&str]] = &[
+    &["grv", "1", "2", "3", "4", "5", "6", "7", "8", "9", "0", "min", "eql", "bspc"],
+    &["tab", "q", "w", "e", "r", "t", "y", "u", "i", "o", "p", "lbrc", "rbrc", "bksl"],
+    &["caps", "a", "s", "d", "f", "g", "h", "j", "k", "l", "scln", "apo", "ret"],
+    &["lsft", "z", "x", "c", "v", "b", "n", "m", "comm", "dot", "slsh", "rsft"],
+    &["lctl", "lmet", "lalt", "rmet", "comp", "spc", "rctl", "ralt", "left", "down", "up", "rght"],
+];
+
+// Tapping term of the tap-hold aliases, and timeout of the one-shot ones
+const TAP_TIMEOUT: u32 = 200;
+const ONE_SHOT_TIMEOUT: u32 = 500;
+
+// Source key at the position of the keymap: the `defsrc` key it was read from for the keymaps
+// imported from Kanata, else the laptop key at its ortho position. Keymaps with less than five
+// rows don't use the number row.
+fn source_key(keymap: &Keymap, index: usize) -> Option<String> {
+    if !keymap.source_keys.is_empty() {
+        return keymap.source_keys.get(index).cloned().flatten();
+    }
+    let offset = if keymap.num_rows >= LAPTOP_ROWS.len() { 0 } else { 1 };
+    let row = LAPTOP_ROWS.get(index / keymap.num_columns + offset)?;
+    row.get(index % keymap.num_columns).map(|key| key.to_string())
+}
+
+// Name of a layer in the config, `NaVim` -> `navim`
+fn layer_name(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect()
+}
+
+// Part of an alias name for a QMK keycode, `KC_SPC` -> `spc`
+fn alias_part(keycode: &str) -> String {
+    layer_name(keycode.strip_prefix("KC_").unwrap_or(keycode))
+}
+
+struct Writer<'a> {
+    keymap: &'a Keymap,
+    // (name, action), in the order they are first used
+    aliases: Vec<(String, String)>,
+    unsupported: Vec<Unsupported>,
+}
+
+impl Writer<'_> {
+    // Defines the alias if it's not defined yet, and returns the reference to it
+    fn alias(&mut self, name: String, action: String) -> String {
+        if !self.aliases.iter().any(|(n, _)| *n == name) {
+            self.aliases.push((name.to_string(), action));
+        }
+        format!("@{name}")
+    }
+
+    // Kanata action of the key, or None if it has no equivalent
+    fn action(&mut self, key: &Key) -> Option<String> {
+        lazy_static! {
+            static ref OSM: Regex = Regex::new(r"^OSM\(MOD_(\w+)\)$").unwrap();
+        }
+        let action = match key {
+            Key::No => String::from("XX"),
+            Key::Trans => String::from("_"),
+            Key::BasicKey(key) => keycodes::to_kanata(&format!("KC_{key}"))?,
+            Key::ModKey { modifier, key } => keycodes::to_kanata(&format!("{modifier}(KC_{key})"))?,
+            Key::ModTap { modifier, key } => {
+                let tap = keycodes::to_kanata(&format!("KC_{key}"))?;
+                let hold = keycodes::modifier_to_kanata(modifier)?;
+                let name = format!("mt_{}_{}", modifier.to_lowercase(), alias_part(key));
+                self.alias(name, format!("(tap-hold {TAP_TIMEOUT} {TAP_TIMEOUT} {tap} {hold})"))
+            }
+            Key::LayerTap { layer, key, .. } => {
+                let tap = keycodes::to_kanata(key)?;
+                let layer = layer_name(layer);
+                let name = format!("lt_{layer}_{}", alias_part(key));
+                self.alias(name, format!("(tap-hold {TAP_TIMEOUT} {TAP_TIMEOUT} {tap} (layer-while-held {layer}))"))
+            }
+            Key::LayerChange { action, layer, .. } => {
+                let layer = layer_name(layer);
+                let kanata_action = match action.as_str() {
+                    "MO" => format!("(layer-while-held {layer})"),
+                    "TO" | "DF" | "PDF" => format!("(layer-switch {layer})"),
+                    "OSL" => format!("(one-shot {ONE_SHOT_TIMEOUT} (layer-while-held {layer}))"),
+                    _ => return None,
+                };
+                self.alias(format!("{}_{layer}", action.to_lowercase()), kanata_action)
+            }
+            // actions read from a Kanata config
+            Key::Custom(name) if name.starts_with('(') => name.to_string(),
+            Key::Custom(name) => match self.keymap.custom_keycodes.get(name).and_then(|c| c.action.clone()) {
+                Some(CustomAction::Tap(key)) => self.action(&key)?,
+                Some(CustomAction::DefaultLayer(layer)) => {
+                    let layer = layer_name(&layer);
+                    self.alias(format!("df_{layer}"), format!("(layer-switch {layer})"))
+                }
+                _ => return None,
+            },
+            Key::Unknown(keycode) => match OSM.captures(keycode) {
+                Some(osm) => {
+                    let modifier = keycodes::modifier_to_kanata(&osm[1])?;
+                    self.alias(format!("os_{modifier}"), format!("(one-shot {ONE_SHOT_TIMEOUT} {modifier})"))
+                }
+                None => keycodes::to_kanata(keycode)?,
+            },
+            Key::SpecialKey(_) | Key::Unicode(..) | Key::TapDance(_) | Key::Macro(_) => return None,
+        };
+        Some(action)
+    }
+
+    fn report(&mut self, layer: Option<usize>, index: Option<usize>, message: String) {
+        self.unsupported.push(Unsupported { layer, index, message });
+    }
+
+    fn key_action(&mut self, layer: usize, index: usize, key: &Key) -> String {
+        if let Some(action) = self.action(key) {
+            return action;
+        }
+        let message = match key {
+            Key::Unicode(..) => format!("unicode keycode {} is not supported", parser::keycode(key)),
+            _ => format!("{} has no Kanata equivalent", parser::keycode(key)),
+        };
+        self.report(Some(layer), Some(index), message);
+        String::from("XX")
+    }
+}
+
+// Rows of items with the columns aligned
+fn aligned(rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in rows {
+        for (c, item) in row.iter().enumerate() {
+            if widths.len() <= c {
+                widths.push(0);
+            }
+            widths[c] = widths[c].max(item.chars().count());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let cells: Vec<String> = row.iter().enumerate().map(|(c, item)| format!("{item:<width$}", width = widths[c])).collect();
+            format!("  {}", cells.join(" ").trim_end())
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Writes the keymap as a Kanata config, for the keys of the `defsrc` it was read from or for a
+// laptop keyboard, see `LAPTOP_ROWS`. The positions without a source key, and the keys and
+// features Kanata doesn't support, are returned separately.
+pub fn keymap_to_kanata(keymap: &Keymap, layer_names: &LayerNames) -> (String, Vec<Unsupported>) {
+    let mut writer = Writer {
+        keymap,
+        aliases: Vec::new(),
+        unsupported: Vec::new(),
+    };
+    let num_keys = keymap.num_rows * keymap.num_columns;
+    let mapped: Vec<usize> = (0..num_keys).filter(|index| source_key(keymap, *index).is_some()).collect();
+    for index in (0..num_keys).filter(|index| !mapped.contains(index)) {
+        let dropped: Vec<String> = keymap
+            .layers
+            .iter()
+            .filter_map(|layer| layer.get(index))
+            .filter(|key| !matches!(key, Key::No | Key::Trans))
+            .map(parser::keycode)
+            .collect();
+        if !dropped.is_empty() {
+            writer.report(None, Some(index), format!("no source key at this position, {} dropped", dropped.join(", ")));
+        }
+    }
+
+    // the mapped positions, split in rows
+    let mut rows: Vec<Vec<usize>> = Vec::new();
+    for index in mapped {
+        match rows.last_mut() {
+            Some(row) if row[0] / keymap.num_columns == index / keymap.num_columns => row.push(index),
+            _ => rows.push(vec![index]),
+        }
+    }
+    let source: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|index| source_key(keymap, *index).unwrap_or_default()).collect())
+        .collect();
+    let mut layers = Vec::new();
+    for (l, layer) in keymap.layers.iter().enumerate() {
+        let actions: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| row.iter().map(|index| writer.key_action(l, *index, &layer[*index])).collect())
+            .collect();
+        layers.push((layer_name(&layer_names.get(l)), actions));
+    }
+    for feature in [
+        (!keymap.combos.is_empty(), "combos"),
+        (!keymap.key_overrides.is_empty(), "key overrides"),
+        (keymap.encoders.iter().any(|e| !e.is_empty()), "encoders"),
+    ] {
+        if feature.0 {
+            writer.report(None, None, format!("{} are not exported", feature.1));
+        }
+    }
+
+    let mut kanata = String::new();
+    if !keymap.keyboard.is_empty() {
+        kanata.push_str(&format!(";; Converted from the QMK keymap {}:{}\n\n", keymap.keyboard, keymap.keymap));
+    }
+    kanata.push_str("(defcfg\n  process-unmapped-keys yes\n)\n\n");
+    kanata.push_str(&format!("(defsrc\n{}\n)\n", aligned(&source)));
+    if !writer.aliases.is_empty() {
+        let aliases: Vec<Vec<String>> = writer.aliases.iter().map(|(name, action)| vec![name.to_string(), action.to_string()]).collect();
+        kanata.push_str(&format!("\n(defalias\n{}\n)\n", aligned(&aliases)));
+    }
+    for (name, rows) in &layers {
+        kanata.push_str(&format!("\n(deflayer {name}\n{}\n)\n", aligned(rows)));
+    }
+    (kanata, writer.unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kanata::parser::keymap_from_str;
+
+    fn keycodes(keymap: &Keymap) -> Vec<Vec<String>> {
+        keymap.layers.iter().map(|layer| layer.iter().map(parser::keycode).collect()).collect()
+    }
+
+    #[test]
+    fn configs_round_trip() {
+        let config = "(defcfg\n  process-unmapped-keys yes\n)\n\n\
+            (defsrc\n  q w e\n  a s d\n)\n\n\
+            (defalias\n  mt_lctl_a  (tap-hold 200 200 a lctl)\n  lt_nav_spc (tap-hold 200 200 spc (layer-while-held nav))\n  mo_nav     (layer-while-held nav)\n)\n\n\
+            (deflayer base\n  q       @mt_lctl_a  @lt_nav_spc\n  @mo_nav (unicode é) XX\n)\n\n\
+            (deflayer nav\n  _ XX left\n  _ _  rght\n)\n";
+        let (keymap, layer_names) = keymap_from_str(config).unwrap();
+        let (kanata, unsupported) = keymap_to_kanata(&keymap, &layer_names);
+        assert!(unsupported.is_empty(), "{:?}", unsupported.iter().map(|u| &u.message).collect::<Vec<_>>());
+        assert_eq!(kanata, config);
+        let (imported, _) = keymap_from_str(&kanata).unwrap();
+        assert_eq!(keycodes(&imported), keycodes(&keymap));
+    }
+
+    #[test]
+    fn keymaps_are_written_for_a_laptop() {
+        let layer_names = LayerNames::new(vec![String::from("Base")]);
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "", "layers": [["KC_Q", "KC_W", "QK_BOOT", "KC_A", "KC_S", "KC_D"]]}"#;
+        let keymap = parser::keymap_from_str(json, 2, &layer_names).unwrap();
+        let (kanata, unsupported) = keymap_to_kanata(&keymap, &layer_names);
+        assert!(kanata.contains("(defsrc\n  tab  q w\n  caps a s\n)\n"), "{kanata}");
+        assert!(kanata.contains("(deflayer base\n  q w XX\n  a s d\n)\n"), "{kanata}");
+        assert_eq!(unsupported.len(), 1);
+        assert_eq!(unsupported[0].message, "QK_BOOT has no Kanata equivalent");
+    }
+}
@@ -0,0 +1,2 @@
+pub mod keycodes;
+pub mod parser;
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+
+// (QMK keycode, Keyberon names), the `KeyCode` variants and the characters which the `layout!`
+// macro accepts for them. Letters, digits, function keys and keypad digits are generated by
+// `keycodes`.
+const KEYCODES: &[(&str, &[&str])] = &[
+    ("KC_ENT", &["Enter"]),
+    ("KC_ESC", &["Escape"]),
+    ("KC_BSPC", &["BSpace"]),
+    ("KC_TAB", &["Tab"]),
+    ("KC_SPC", &["Space"]),
+    ("KC_MINS", &["Minus", "-"]),
+    ("KC_EQL", &["Equal", "="]),
+    ("KC_LBRC", &["LBracket", "["]),
+    ("KC_RBRC", &["RBracket", "]"]),
+    ("KC_BSLS", &["Bslash", "\\"]),
+    ("KC_NUHS", &["NonUsHash"]),
+    ("KC_SCLN", &["SColon", ";"]),
+    ("KC_QUOT", &["Quote", "'"]),
+    ("KC_GRV", &["Grave", "`"]),
+    ("KC_COMM", &["Comma", ","]),
+    ("KC_DOT", &["Dot", "."]),
+    ("KC_SLSH", &["Slash", "/"]),
+    ("KC_CAPS", &["CapsLock"]),
+    ("KC_PSCR", &["PScreen"]),
+    ("KC_SCRL", &["ScrollLock"]),
+    ("KC_PAUS", &["Pause"]),
+    ("KC_INS", &["Insert"]),
+    ("KC_HOME", &["Home"]),
+    ("KC_PGUP", &["PgUp"]),
+    ("KC_DEL", &["Delete"]),
+    ("KC_END", &["End"]),
+    ("KC_PGDN", &["PgDown"]),
+    ("KC_RGHT", &["Right"]),
+    ("KC_LEFT", &["Left"]),
+    ("KC_DOWN", &["Down"]),
+    ("KC_UP", &["Up"]),
+    ("KC_NUM", &["NumLock"]),
+    ("KC_PSLS", &["KpSlash"]),
+    ("KC_PAST", &["KpAsterisk"]),
+    ("KC_PMNS", &["KpMinus"]),
+    ("KC_PPLS", &["KpPlus"]),
+    ("KC_PENT", &["KpEnter"]),
+    ("KC_PDOT", &["KpDot"]),
+    ("KC_PEQL", &["KpEqual"]),
+    ("KC_NUBS", &["NonUsBslash"]),
+    ("KC_APP", &["Application"]),
+    ("KC_PWR", &["Power"]),
+    ("KC_EXEC", &["Execute"]),
+    ("KC_HELP", &["Help"]),
+    ("KC_MENU", &["Menu"]),
+    ("KC_SLCT", &["Select"]),
+    ("KC_STOP", &["Stop"]),
+    ("KC_AGIN", &["Again"]),
+    ("KC_UNDO", &["Undo"]),
+    ("KC_CUT", &["Cut"]),
+    ("KC_COPY", &["Copy"]),
+    ("KC_PSTE", &["Paste"]),
+    ("KC_FIND", &["Find"]),
+    ("KC_MUTE", &["Mute", "MediaMute"]),
+    ("KC_VOLU", &["VolUp", "MediaVolUp"]),
+    ("KC_VOLD", &["VolDown", "MediaVolDown"]),
+    ("KC_MPLY", &["MediaPlayPause"]),
+    ("KC_MSTP", &["MediaStopCD", "MediaStop"]),
+    ("KC_MPRV", &["MediaPreviousSong"]),
+    ("KC_MNXT", &["MediaNextSong"]),
+    ("KC_EJCT", &["MediaEjectCD"]),
+    ("KC_MFFD", &["MediaForward"]),
+    ("KC_MRWD", &["MediaBack"]),
+    ("KC_WSCH", &["MediaFind"]),
+    ("KC_CALC", &["MediaCalc"]),
+    ("KC_SLEP", &["MediaSleep"]),
+    ("KC_LCTL", &["LCtrl"]),
+    ("KC_LSFT", &["LShift"]),
+    ("KC_LALT", &["LAlt"]),
+    ("KC_LGUI", &["LGui"]),
+    ("KC_RCTL", &["RCtrl"]),
+    ("KC_RSFT", &["RShift"]),
+    ("KC_RALT", &["RAlt"]),
+    ("KC_RGUI", &["RGui"]),
+];
+
+// (QMK keycode, Keyberon names) for all the known keycodes
+fn keycodes() -> Vec<(String, Vec<String>)> {
+    let mut keycodes: Vec<(String, Vec<String>)> = Vec::new();
+    for c in 'A'..='Z' {
+        keycodes.push((format!("KC_{c}"), vec![c.to_string()]));
+    }
+    for n in 0..10 {
+        keycodes.push((format!("KC_{n}"), vec![format!("Kb{n}"), n.to_string()]));
+        keycodes.push((format!("KC_P{n}"), vec![format!("Kp{n}")]));
+    }
+    for n in 1..=24 {
+        keycodes.push((format!("KC_F{n}"), vec![format!("F{n}")]));
+    }
+    for (qmk, keyberon) in KEYCODES {
+        keycodes.push((qmk.to_string(), keyberon.iter().map(|name| name.to_string()).collect()));
+    }
+    keycodes
+}
+
+lazy_static! {
+    // Keyberon name -> QMK keycode
+    static ref TO_QMK: HashMap<String, String> = keycodes()
+        .into_iter()
+        .flat_map(|(qmk, keyberon)| keyberon.into_iter().map(move |name| (name, qmk.to_string())))
+        .collect();
+}
+
+// QMK keycode of a Keyberon key name, `KeyCode::` prefix included, `BSpace` -> `KC_BSPC`
+pub fn to_qmk(keyberon: &str) -> Option<String> {
+    let name = keyberon.strip_prefix("KeyCode::").unwrap_or(keyberon);
+    TO_QMK.get(name).map(|qmk| qmk.to_string())
+}
@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::keyberon::keycodes;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{Key, Keymap};
+use crate::qmk::keymap_extras::US_SHIFTED;
+use crate::qmk::parser;
+
+const MODIFIERS: &[&str] = &["LCTL", "LSFT", "LALT", "LGUI", "RCTL", "RSFT", "RALT", "RGUI"];
+
+// Item of a `layout!` row
+enum Item {
+    // `Tab`, `,` or `'['`, without the quotes
+    Key(String),
+    // `(1)`, the layer is active while the key is held
+    Layer(String),
+    // `[LShift A]`, keys pressed together
+    Keys(Vec<String>),
+    // `{CUT}`, any action expression
+    Action(String),
+}
+
+struct Reader {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Reader {
+    // Skips the whitespace and the `//` comments, and returns the next character
+    fn peek(&mut self) -> Option<char> {
+        loop {
+            let c = *self.chars.get(self.pos)?;
+            if c.is_whitespace() {
+                self.pos += 1;
+            } else if c == '/' && self.chars.get(self.pos + 1) == Some(&'/') {
+                while self.chars.get(self.pos).is_some_and(|c| *c != '\n') {
+                    self.pos += 1;
+                }
+            } else {
+                return Some(c);
+            }
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), &'static str> {
+        if self.peek() != Some(c) {
+            return Err("unexpected character in the layout! macro");
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // Text up to the `close` delimiter matching the current `open` one, the delimiters excluded
+    fn delimited(&mut self, open: char, close: char) -> Result<String, &'static str> {
+        self.expect(open)?;
+        let start = self.pos;
+        let mut depth = 1;
+        while let Some(c) = self.chars.get(self.pos) {
+            self.pos += 1;
+            if *c == open {
+                depth += 1;
+            } else if *c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(self.chars[start..self.pos - 1].iter().collect());
+                }
+            }
+        }
+        Err("unbalanced delimiters in the layout! macro")
+    }
+
+    // Key name, a quoted character like `'['` or a word like `Tab` or `,`
+    fn key(&mut self) -> Result<String, &'static str> {
+        if self.peek() == Some('\'') {
+            self.pos += 1;
+            let mut c = *self.chars.get(self.pos).ok_or("unterminated character")?;
+            if c == '\\' {
+                self.pos += 1;
+                c = *self.chars.get(self.pos).ok_or("unterminated character")?;
+            }
+            self.pos += 1;
+            self.expect('\'')?;
+            return Ok(c.to_string());
+        }
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| !c.is_whitespace() && !"[](){}".contains(*c)) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err("unexpected character in the layout! macro");
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn row(&mut self) -> Result<Vec<Item>, &'static str> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            let item = match self.peek().ok_or("unterminated row")? {
+                ']' => {
+                    self.pos += 1;
+                    return Ok(items);
+                }
+                '[' => {
+                    self.pos += 1;
+                    let mut keys = Vec::new();
+                    while self.peek().ok_or("unterminated row")? != ']' {
+                        keys.push(self.key()?);
+                    }
+                    self.pos += 1;
+                    Item::Keys(keys)
+                }
+                '(' => Item::Layer(self.delimited('(', ')')?.trim().to_string()),
+                '{' => Item::Action(self.delimited('{', '}')?.trim().to_string()),
+                _ => Item::Key(self.key()?),
+            };
+            items.push(item);
+        }
+    }
+
+    fn layer(&mut self) -> Result<Vec<Vec<Item>>, &'static str> {
+        self.expect('{')?;
+        let mut rows = Vec::new();
+        while self.peek().ok_or("unterminated layer")? != '}' {
+            rows.push(self.row()?);
+        }
+        self.pos += 1;
+        Ok(rows)
+    }
+}
+
+// Layers of the first `layout!` macro of a Rust source file, as rows of items
+fn layout(data: &str) -> Result<Vec<Vec<Vec<Item>>>, &'static str> {
+    let start = data.find("layout!").ok_or("no layout! macro")? + "layout!".len();
+    let mut reader = Reader {
+        chars: data[start..].chars().collect(),
+        pos: 0,
+    };
+    reader.expect('{')?;
+    let mut layers = Vec::new();
+    while reader.peek().ok_or("unterminated layout! macro")? != '}' {
+        layers.push(reader.layer()?);
+    }
+    Ok(layers)
+}
+
+// `const` and `static` items of a Rust source file, name -> expression with its whitespace
+// collapsed
+fn constants(data: &str) -> HashMap<String, String> {
+    lazy_static! {
+        static ref ITEM: Regex = Regex::new(r"(?s)\b(?:const|static)\s+(\w+)\s*:[^=;]*=\s*([^;]*);").unwrap();
+    }
+    ITEM.captures_iter(data)
+        .map(|item| (item[1].to_string(), item[2].split_whitespace().collect::<Vec<_>>().join(" ")))
+        .collect()
+}
+
+struct Context<'a> {
+    layer_names: &'a LayerNames,
+    num_layers: usize,
+    constants: HashMap<String, String>,
+}
+
+impl Context<'_> {
+    // Name and index of a layer number
+    fn layer(&self, layer: &str) -> Option<(String, Option<usize>)> {
+        let idx: usize = layer.trim().parse().ok()?;
+        (idx < self.num_layers).then(|| (self.layer_names.get(idx), Some(idx)))
+    }
+
+    // QMK keycode of keys pressed together, modifiers and a single other key, `[LShift A]` ->
+    // `LSFT(KC_A)` and `[LShift Kb1]` -> `KC_EXLM`
+    fn keys(&self, names: &[String]) -> Option<String> {
+        let keycodes: Vec<String> = names.iter().map(|name| keycodes::to_qmk(name.trim())).collect::<Option<_>>()?;
+        let (modifiers, keys): (Vec<&String>, Vec<&String>) =
+            keycodes.iter().partition(|k| k.strip_prefix("KC_").is_some_and(|m| MODIFIERS.contains(&m)) && keycodes.len() > 1);
+        let [key] = keys.as_slice() else {
+            return None;
+        };
+        let mut keycode = key.to_string();
+        for modifier in modifiers.iter().rev().filter_map(|m| m.strip_prefix("KC_")) {
+            // shifted symbols have their own keycode
+            let shifted = US_SHIFTED.iter().find(|(_, unshifted, _)| *unshifted == keycode);
+            keycode = match shifted {
+                Some((shifted, _, _)) if modifier == "LSFT" => shifted.to_string(),
+                _ => format!("{modifier}({keycode})"),
+            };
+        }
+        Some(keycode)
+    }
+
+    // Key of an action expression, `k(A)`, `l(1)`, `d(0)`, `m(&[LShift, A].as_slice())`, simple
+    // `HoldTap` actions and constants defined as one of them
+    fn action(&self, expr: &str, depth: usize) -> Option<Key> {
+        lazy_static! {
+            static ref KEY: Regex = Regex::new(r"^(?:Action::KeyCode|k)\(([\w:]+)\)$").unwrap();
+            static ref LAYER: Regex = Regex::new(r"^(?:Action::Layer|l)\((\d+)\)$").unwrap();
+            static ref DEFAULT_LAYER: Regex = Regex::new(r"^(?:Action::DefaultLayer|d)\((\d+)\)$").unwrap();
+            static ref KEYS: Regex = Regex::new(r"^(?:Action::MultipleKeyCodes|m)\(&+\[([^\]]*)\]").unwrap();
+            static ref HOLD_TAP: Regex = Regex::new(r"^(?:Action::)?HoldTap\(").unwrap();
+            static ref HOLD: Regex = Regex::new(r"\bhold\s*:\s*([^,}]+)").unwrap();
+            static ref TAP: Regex = Regex::new(r"\btap\s*:\s*([^,}]+)").unwrap();
+            static ref CONSTANT: Regex = Regex::new(r"^\w+$").unwrap();
+        }
+        let expr = expr.trim().trim_start_matches('&');
+        if let Some(key) = KEY.captures(expr) {
+            return Some(parser::parse_keycode(&keycodes::to_qmk(&key[1])?, self.layer_names));
+        }
+        if let Some(layer) = LAYER.captures(expr) {
+            let (layer, index) = self.layer(&layer[1])?;
+            return Some(Key::LayerChange {
+                action: String::from("MO"),
+                layer,
+                index,
+            });
+        }
+        if let Some(layer) = DEFAULT_LAYER.captures(expr) {
+            let (layer, index) = self.layer(&layer[1])?;
+            return Some(Key::LayerChange {
+                action: String::from("DF"),
+                layer,
+                index,
+            });
+        }
+        if let Some(keys) = KEYS.captures(expr) {
+            let names: Vec<String> = keys[1].split(',').map(|k| k.trim().to_string()).filter(|k| !k.is_empty()).collect();
+            return Some(parser::parse_keycode(&self.keys(&names)?, self.layer_names));
+        }
+        if HOLD_TAP.is_match(expr) {
+            let hold = self.action(&HOLD.captures(expr)?[1], depth + 1)?;
+            let Key::BasicKey(tap) = self.action(&TAP.captures(expr)?[1], depth + 1)? else {
+                return None;
+            };
+            return match hold {
+                Key::LayerChange { action, layer, index } if action == "MO" => Some(Key::LayerTap {
+                    layer,
+                    key: format!("KC_{tap}"),
+                    index,
+                }),
+                Key::BasicKey(modifier) if MODIFIERS.contains(&modifier.as_str()) => Some(Key::ModTap { modifier, key: tap }),
+                _ => None,
+            };
+        }
+        if CONSTANT.is_match(expr) && depth < 8 {
+            return self.action(self.constants.get(expr)?, depth + 1);
+        }
+        None
+    }
+
+    // Items without a QMK equivalent are kept as custom keys, named after the constant for the
+    // actions which are constants
+    fn key(&self, item: &Item) -> Key {
+        let key = match item {
+            Item::Key(name) => match name.as_str() {
+                "t" => Some(Key::Trans),
+                "n" => Some(Key::No),
+                _ => keycodes::to_qmk(name).map(|keycode| parser::parse_keycode(&keycode, self.layer_names)),
+            },
+            Item::Layer(layer) => self.layer(layer).map(|(layer, index)| Key::LayerChange {
+                action: String::from("MO"),
+                layer,
+                index,
+            }),
+            Item::Keys(names) => self.keys(names).map(|keycode| parser::parse_keycode(&keycode, self.layer_names)),
+            Item::Action(expr) => self.action(expr, 0),
+        };
+        key.unwrap_or_else(|| match item {
+            Item::Key(name) => Key::Custom(name.to_string()),
+            Item::Layer(layer) => Key::Custom(format!("({layer})")),
+            Item::Keys(names) => Key::Custom(format!("[{}]", names.join(" "))),
+            Item::Action(expr) if expr.chars().all(|c| c.is_alphanumeric() || c == '_') => Key::Custom(expr.to_string()),
+            Item::Action(expr) => Key::Custom(format!("{{{expr}}}")),
+        })
+    }
+}
+
+// Reads the `layout!` macro of a Keyberon firmware source file. The layers are split in rows
+// following the rows of the macro, shorter rows being padded with `KC_NO`. Keyberon layers have
+// no names, they are named after their number, `layer0`, `layer1`, ...
+pub fn keymap_from_str(data: &str) -> Result<(Keymap, LayerNames), &'static str> {
+    let layout = layout(data)?;
+    if layout.is_empty() {
+        return Err("no layers in the layout! macro");
+    }
+    let mut layer_names = LayerNames::new(Vec::new());
+    layer_names.fill(layout.len());
+    let num_rows = layout.iter().map(|rows| rows.len()).max().unwrap_or(1);
+    let num_columns = layout.iter().flatten().map(|row| row.len()).max().unwrap_or(1);
+    let context = Context {
+        layer_names: &layer_names,
+        num_layers: layout.len(),
+        constants: constants(data),
+    };
+    let layers = layout
+        .iter()
+        .map(|rows| {
+            let mut keys = vec![Key::No; num_rows * num_columns];
+            for (r, row) in rows.iter().enumerate() {
+                for (c, item) in row.iter().enumerate() {
+                    keys[r * num_columns + c] = context.key(item);
+                }
+            }
+            keys
+        })
+        .collect();
+    let keymap = Keymap {
+        keymap: String::from("keyberon"),
+        // no QMK keyboard, the keymap is drawn as an ortho layout
+        keyboard: String::new(),
+        layout: String::new(),
+        num_rows,
+        num_columns,
+        layers,
+        tap_dances: HashMap::new(),
+        combos: Vec::new(),
+        macros: Vec::new(),
+        custom_keycodes: HashMap::new(),
+        key_overrides: Vec::new(),
+        encoders: Vec::new(),
+        extra_fields: serde_json::Map::new(),
+        info_json: None,
+        source_keys: Vec::new(),
+    };
+    Ok((keymap, layer_names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+use keyberon::action::{k, l, d, m, Action, HoldTapAction, HoldTapConfig};
+
+const CUT: Action = m(&[LShift, Delete].as_slice());
+const L1_SP: Action = HoldTap(&HoldTapAction {
+    timeout: 200,
+    hold: l(1),
+    tap: k(Space),
+    config: HoldTapConfig::Default,
+    tap_hold_interval: 0,
+});
+
+pub static LAYERS: keyberon::layout::Layers<3, 2, 2> = keyberon::layout::layout! {
+    {
+        [Tab '[' {L1_SP}]
+        [(1) [LShift A]]
+    }
+    {
+        // comment
+        [t n {d(0)}]
+        [{CUT} [LShift Kb1] Escape]
+    }
+};
+"#;
+
+    fn keycodes(keymap: &Keymap) -> Vec<Vec<String>> {
+        keymap.layers.iter().map(|layer| layer.iter().map(parser::keycode).collect()).collect()
+    }
+
+    #[test]
+    fn layouts_are_parsed() {
+        let (keymap, layer_names) = keymap_from_str(SOURCE).unwrap();
+        assert_eq!(layer_names.names(), &[String::from("layer0"), String::from("layer1")]);
+        assert_eq!((keymap.num_rows, keymap.num_columns), (2, 3));
+        // the first layer's second row is shorter than the others
+        assert_eq!(keycodes(&keymap), [
+            ["KC_TAB", "KC_LBRC", "LT(1, KC_SPC)", "MO(1)", "LSFT(KC_A)", "KC_NO"],
+            ["KC_TRNS", "KC_NO", "DF(0)", "LSFT(KC_DEL)", "KC_EXLM", "KC_ESC"],
+        ]);
+    }
+
+    #[test]
+    fn layouts_round_trip_through_qmk_json() {
+        let (keymap, layer_names) = keymap_from_str(SOURCE).unwrap();
+        let json = parser::keymap_to_json(&keymap).unwrap();
+        let imported = parser::keymap_from_str(&json, keymap.num_rows, &layer_names).unwrap();
+        assert_eq!(keycodes(&imported), keycodes(&keymap));
+    }
+
+    #[test]
+    fn unknown_items_are_kept() {
+        let (keymap, _) = keymap_from_str("layout! { { [Foo (7) {Custom(1)}] } }").unwrap();
+        let keys = [Key::Custom(String::from("Foo")), Key::Custom(String::from("(7)")), Key::Custom(String::from("{Custom(1)}"))];
+        assert_eq!(keymap.layers[0], keys);
+    }
+}
@@ -1,46 +1,286 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::PathBuf;
 use crate::LayerNames;
+use crate::keymap::labels::Labels;
+use lazy_static::lazy_static;
+use regex::Regex;
 
+pub mod coverage;
+pub mod diff;
+pub mod edit;
+pub mod find;
+pub mod labels;
 pub mod layer;
+pub mod lint;
 
+// CSS added to the keymap-drawer SVG for the keys of the `changed` type
+const HIGHLIGHT_STYLE: &str = "rect.changed { fill: #ffd5d5; }";
+
+// Adds the `changed` type to a key rendered by `Keymap::key_to_yaml`
+fn highlight(key_yaml: &str) -> String {
+    lazy_static! {
+        static ref TYPE: Regex = Regex::new(r", type: \w+").unwrap();
+    }
+    if key_yaml == "null" {
+        return String::from("{t: \"\", type: changed}");
+    }
+    match key_yaml.strip_prefix('{').and_then(|k| k.strip_suffix('}')) {
+        Some(legends) => format!("{{{}, type: changed}}", TYPE.replace_all(legends, "")),
+        None => format!("{{t: {key_yaml}, type: changed}}"),
+    }
+}
+
+// Quotes `s` if it can't be used as is in a YAML flow sequence, which is the case for most of the
+// symbols used as key labels, and for the labels YAML reads as null, booleans, numbers or other
+// values.
+pub fn yaml_str(s: &str) -> String {
+    const SPECIAL: &[char] = &['[', ']', '{', '}', ',', ':', '#', '&', '*', '!', '|', '>', '\'', '"', '%', '@', '`', '?', '-', '\\', '~'];
+    lazy_static! {
+        static ref NOT_A_STRING: Regex = Regex::new(
+            r"(?i)^(null|true|false|yes|no|on|off|[-+]?\.inf|\.nan)$|^(=|<<)$|^[-+]?(\.[0-9]+|[0-9][0-9_]*(\.[0-9]*)?)(e[-+]?[0-9]+)?$|^0(x[0-9a-f]+|o[0-7]+)$"
+        ).unwrap();
+    }
+    if s.is_empty() || s.contains(SPECIAL) || s.starts_with(' ') || s.ends_with(' ') || NOT_A_STRING.is_match(s) {
+        return format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    }
+    s.to_string()
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Key {
     No,
     Trans,
     BasicKey(String),
     SpecialKey(String),
     Unicode(String, Option<String>),
-    LayerTap { layer: String, key: String },
+    // `index` is set when the layer is referred to by its number, `LT(3, KC_A)`, as in most
+    // keymap.json files
+    LayerTap { layer: String, key: String, index: Option<usize> },
     ModTap { modifier: String, key: String },
     ModKey { modifier: String, key: String },
-    LayerChange { _action: String, layer: String },
+    LayerChange { action: String, layer: String, index: Option<usize> },
+    TapDance(String),
+    // QK_MACRO_n, index in `Keymap::macros`
+    Macro(usize),
+    // keycode defined in keymap.c from SAFE_RANGE, see `Keymap::custom_keycodes`
+    Custom(String),
     Unknown(String),
 }
 
+// Step of a keymap.json macro
+#[derive(Clone, Debug, PartialEq)]
+pub enum MacroStep {
+    Text(String),
+    Tap(Vec<String>),
+    Down(Vec<String>),
+    Up(Vec<String>),
+    Delay(u64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Macro {
+    pub steps: Vec<MacroStep>,
+}
+
+// What a custom keycode does, when it can be recognised in `process_record_user()`
+#[derive(Clone, Debug, PartialEq)]
+pub enum CustomAction {
+    SendString(String),
+    DefaultLayer(String),
+    Tap(Key),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomKeycode {
+    pub name: String,
+    pub action: Option<CustomAction>,
+}
+
+// Behaviour of a tap dance key, from the `tap_dance_actions[]` table in keymap.c
+pub struct TapDance {
+    pub tap: Option<Key>,
+    pub double_tap: Option<Key>,
+    pub hold: Option<Key>,
+    // function handling the tap dance when it's not one of the predefined actions
+    pub function: Option<String>,
+}
+
 impl Key {
+    // Returns the key sent on tap by mod-taps and layer-taps
+    pub fn tap_key(&self) -> Option<Key> {
+        match self {
+            Key::LayerTap { key, .. } => Some(match key.strip_prefix("KC_") {
+                Some(basic) => Key::BasicKey(basic.to_string()),
+                None => Key::SpecialKey(key.to_string()),
+            }),
+            Key::ModTap { key, .. } => Some(Key::BasicKey(key.to_string())),
+            _ => None,
+        }
+    }
+
+    pub fn hold_legend(&self) -> Option<String> {
+        match self {
+            Key::LayerTap { layer, .. } => Some(layer.to_string()),
+            Key::ModTap { modifier, .. } => Some(modifier.to_string()),
+            _ => None,
+        }
+    }
+
+    // Returns the text shown on the key for its tap action
+    pub fn legend(&self, labels: &Labels) -> String {
+        match self {
+            Key::No => String::new(),
+            Key::Trans => String::from("▽"),
+            Key::BasicKey(key) | Key::SpecialKey(key) => labels.label(key),
+            Key::Unicode(lower, _) => format!("UM({lower})"),
+            Key::LayerTap { key, .. } | Key::ModTap { key, .. } => labels.label(key),
+            Key::ModKey { modifier, key } => labels.label_with_modifier(modifier, key),
+            Key::LayerChange { layer, .. } => layer.to_string(),
+            Key::TapDance(name) => format!("TD({name})"),
+            Key::Macro(idx) => format!("QK_MACRO_{idx}"),
+            Key::Custom(name) => labels.label(name),
+            Key::Unknown(key) => key.to_string(),
+        }
+    }
+
     // KC_TRNS "{t: ▽, type: trans}"
     // KC_NO ""
-    pub fn to_yaml(&self) -> String {
+    pub fn to_yaml(&self, labels: &Labels) -> String {
         match self {
-            Key::BasicKey(key) => key.to_string(),
-            Key::SpecialKey(key) => key.to_string(),
+            Key::BasicKey(key) => yaml_str(&labels.label(key)),
+            Key::SpecialKey(key) => yaml_str(&labels.label(key)),
             Key::No => "null".to_string(),
             Key::Trans => "{t: ▽, type: trans}".to_string(),
             Key::Unicode(uc, None) => format!("UM({uc})"),
             Key::Unicode(lower, Some(upper)) => format!("'UP({lower}, {upper})'"),
-            Key::LayerTap { layer, key } => format!("{{t: {key}, h: {layer}}}"),
-            Key::ModTap { modifier, key } => format!("{{t: {key}, h: {modifier}}}"),
-            Key::ModKey { modifier, key } => format!("{modifier}({key})"),
-            Key::LayerChange { _action: _, layer } => layer.to_string(),
-            Key::Unknown(key) => {
-                println!("unknown key {key}");
-                key.to_string()
-            } //_ => panic!("missing to_yaml implementation"),
+            Key::LayerTap { layer, key, .. } => format!("{{t: {}, h: {}}}", yaml_str(&labels.label(key)), yaml_str(layer)),
+            Key::ModTap { modifier, key } => format!("{{t: {}, h: {modifier}}}", yaml_str(&labels.label(key))),
+            Key::ModKey { modifier, key } => yaml_str(&labels.label_with_modifier(modifier, key)),
+            Key::LayerChange { layer, .. } => yaml_str(layer),
+            Key::TapDance(name) => yaml_str(&format!("TD({name})")),
+            Key::Macro(idx) => format!("QK_MACRO_{idx}"),
+            Key::Custom(name) => yaml_str(&labels.label(name)),
+            Key::Unknown(key) => yaml_str(key),
+            //_ => panic!("missing to_yaml implementation"),
+        }
+    }
+}
+
+impl Macro {
+    // Text typed by the macro, with the tapped keycodes written as `Ctrl+C`
+    pub fn legend(&self, labels: &Labels) -> String {
+        let mut legend = Vec::new();
+        for step in &self.steps {
+            match step {
+                MacroStep::Text(text) => legend.push(text.to_string()),
+                MacroStep::Tap(keycodes) => legend.push(keycodes.iter().map(|k| labels.label(k)).collect::<Vec<String>>().join("+")),
+                _ => (),
+            }
         }
+        legend.join(" ")
     }
 }
 
+impl CustomKeycode {
+    // The label of the keycode if it has one, else what it does
+    pub fn legend(&self, labels: &Labels) -> String {
+        if let Some(label) = labels.get(&self.name) {
+            return label.to_string();
+        }
+        match &self.action {
+            Some(CustomAction::SendString(text)) => text.to_string(),
+            Some(CustomAction::DefaultLayer(layer)) => layer.to_string(),
+            Some(CustomAction::Tap(key)) => key.legend(labels),
+            None => self.name.to_string(),
+        }
+    }
+}
+
+impl TapDance {
+    // The double tap action is drawn as the shifted legend, as keymap-drawer has no dedicated
+    // position for it.
+    pub fn to_yaml(&self, labels: &Labels) -> String {
+        let mut legends = Vec::new();
+        match (&self.tap, &self.function) {
+            (Some(tap), _) => legends.push(format!("t: {}", yaml_str(&tap.legend(labels)))),
+            (None, Some(function)) => legends.push(format!("t: {}", yaml_str(function))),
+            (None, None) => (),
+        }
+        if let Some(double_tap) = &self.double_tap {
+            legends.push(format!("s: {}", yaml_str(&format!("2× {}", double_tap.legend(labels)))));
+        }
+        if let Some(hold) = &self.hold {
+            legends.push(format!("h: {}", yaml_str(&hold.legend(labels))));
+        }
+        format!("{{{}}}", legends.join(", "))
+    }
+}
+
+// A QMK combo, `keys` pressed together send `result`
+pub struct Combo {
+    pub name: String,
+    pub keys: Vec<Key>,
+    pub result: Key,
+    // layers where the combo is active, all of them when `None`
+    pub layers: Option<Vec<String>>,
+    // positions of `keys` in the layer used to match combos, see `Keymap::locate_combos`
+    pub positions: Vec<usize>,
+}
+
+// A QMK key override, `trigger` is replaced with `replacement` while `modifiers` are held
+pub struct KeyOverride {
+    pub name: String,
+    // modifier mask, `MOD_MASK_SHIFT`
+    pub modifiers: String,
+    pub trigger: Key,
+    pub replacement: Key,
+    // layers where the override is active, all of them when `None`
+    pub layers: Option<Vec<String>>,
+}
+
+impl KeyOverride {
+    // Shifted overrides are drawn as the shifted legend, the other ones get the modifier name
+    // in front of the legend: `Ctrl Del`
+    pub fn legend(&self, labels: &Labels) -> String {
+        let legend = self.replacement.legend(labels);
+        let modifiers = self.modifiers.strip_prefix("MOD_MASK_").unwrap_or(&self.modifiers);
+        match modifiers {
+            "SHIFT" => legend,
+            "CTRL" => format!("Ctrl {legend}"),
+            "ALT" => format!("Alt {legend}"),
+            "GUI" => format!("Gui {legend}"),
+            _ => format!("{modifiers} {legend}"),
+        }
+    }
+}
+
+// A rotary encoder, turning it counter-clockwise sends `ccw`, clockwise sends `cw`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Encoder {
+    pub ccw: Key,
+    pub cw: Key,
+}
+
+impl Encoder {
+    fn to_yaml(&self, labels: &Labels) -> String {
+        format!(
+            "{{t: {}, s: {}, type: encoder}}",
+            yaml_str(&format!("↻ {}", self.cw.legend(labels))),
+            yaml_str(&format!("↺ {}", self.ccw.legend(labels))),
+        )
+    }
+}
+
+// A key or feature which has no equivalent in the firmware a keymap is exported to, the keys are
+// replaced with the no-op key of that firmware
+pub struct Unsupported {
+    pub layer: Option<usize>,
+    pub index: Option<usize>,
+    pub message: String,
+}
+
 pub struct Keymap {
     pub keymap: String,
     pub keyboard: String,
@@ -48,6 +288,22 @@ pub struct Keymap {
     pub num_rows: usize,
     pub num_columns: usize,
     pub layers: Vec<Vec<Key>>,
+    pub tap_dances: HashMap<String, TapDance>,
+    pub combos: Vec<Combo>,
+    pub macros: Vec<Macro>,
+    pub custom_keycodes: HashMap<String, CustomKeycode>,
+    pub key_overrides: Vec<KeyOverride>,
+    // encoders of each layer, in the same order as `layers`
+    pub encoders: Vec<Vec<Encoder>>,
+    // keymap.json fields which are not used by the crate (`version`, `author`, `notes`, ...),
+    // they are kept so that they can be written back
+    pub extra_fields: serde_json::Map<String, serde_json::Value>,
+    // info.json with the physical layout the keymap is drawn with, instead of the layout of
+    // `keyboard`, its layout macro is `LAYOUT`
+    pub info_json: Option<PathBuf>,
+    // Names of the physical keys at each position, the `defsrc` keys of a Kanata config, which
+    // are written back when the keymap is exported to Kanata. Empty for the other formats.
+    pub source_keys: Vec<Option<String>>,
 }
 
 // layout:
@@ -57,28 +313,150 @@ pub struct Keymap {
 //       columns: 12
 //       thumbs: MIT  # also try "2x2u" with one fewer key on the last row
 impl Keymap {
-    pub fn to_yaml(&self, layer_names: &LayerNames) -> Result<String, &'static str> {
-        let mut yaml = String::from("layout:");
+    // Finds the positions of the combo keys in `reference_layer`, which is the layer set with
+    // `COMBO_ONLY_FROM_LAYER`, or the base layer. Returns the names of the combos whose keys
+    // could not all be found.
+    pub fn locate_combos(&mut self, reference_layer: usize) -> Vec<String> {
+        let mut missing = Vec::new();
+        let Some(layer) = self.layers.get(reference_layer) else {
+            return self.combos.iter().map(|c| c.name.to_string()).collect();
+        };
+        for combo in self.combos.iter_mut() {
+            combo.positions = combo.keys.iter().filter_map(|k| layer.iter().position(|key| key == k)).collect();
+            if combo.positions.len() != combo.keys.len() {
+                missing.push(combo.name.to_string());
+            }
+        }
+        missing
+    }
+
+    // Key positions an encoder is drawn below, two keys of the bottom row with the encoders spread
+    // along it
+    fn encoder_positions(&self, encoder: usize, num_encoders: usize) -> Vec<usize> {
+        let bottom_row = (self.num_rows - 1) * self.num_columns;
+        if self.num_columns < 2 {
+            return vec![bottom_row];
+        }
+        let column = ((encoder + 1) * self.num_columns / (num_encoders + 1)).clamp(1, self.num_columns - 1);
+        vec![bottom_row + column - 1, bottom_row + column]
+    }
+
+    // Combos, and the encoders which are not keys of the physical layout, drawn as combos below the
+    // keys of their layer
+    fn combos_to_yaml(&self, layer_names: &LayerNames, labels: &Labels) -> String {
+        let mut yaml = String::new();
+        for combo in &self.combos {
+            if combo.positions.len() != combo.keys.len() {
+                continue;
+            }
+            let positions: Vec<String> = combo.positions.iter().map(|p| p.to_string()).collect();
+            yaml.push_str(&format!("  - {{p: [{}], k: {}", positions.join(", "), yaml_str(&combo.result.legend(labels))));
+            if let Some(layers) = &combo.layers {
+                let layers: Vec<String> = layers.iter().map(|layer| yaml_str(layer)).collect();
+                yaml.push_str(&format!(", l: [{}]", layers.join(", ")));
+            }
+            yaml.push_str("}\n");
+        }
+        for (i, encoders) in self.encoders.iter().enumerate() {
+            for (e, encoder) in encoders.iter().enumerate() {
+                let positions: Vec<String> = self.encoder_positions(e, encoders.len()).iter().map(|p| p.to_string()).collect();
+                yaml.push_str(&format!(
+                    "  - {{p: [{}], k: {}, l: [{}], a: bottom}}\n",
+                    positions.join(", "),
+                    encoder.to_yaml(labels),
+                    yaml_str(&layer_names.get(i)),
+                ));
+            }
+        }
+        if yaml.is_empty() {
+            return yaml;
+        }
+        format!("combos:\n{yaml}")
+    }
+
+    fn key_to_yaml(&self, layer: &str, key: &Key, labels: &Labels) -> String {
+        let key_override = self.key_overrides.iter().find(|ko| {
+            let active = ko.layers.as_ref().is_none_or(|layers| layers.iter().any(|l| l == layer));
+            active && (ko.trigger == *key || key.tap_key().is_some_and(|tap| ko.trigger == tap))
+        });
+        if let Some(ko) = key_override {
+            let mut legends = vec![format!("t: {}", yaml_str(&key.legend(labels)))];
+            legends.push(format!("s: {}", yaml_str(&ko.legend(labels))));
+            if let Some(hold) = key.hold_legend() {
+                legends.push(format!("h: {}", yaml_str(&hold)));
+            }
+            return format!("{{{}}}", legends.join(", "));
+        }
+        match key {
+            Key::TapDance(name) => {
+                if let Some(td) = self.tap_dances.get(name) {
+                    return td.to_yaml(labels);
+                }
+            }
+            Key::Macro(idx) => {
+                if let Some(m) = self.macros.get(*idx) {
+                    return yaml_str(&m.legend(labels));
+                }
+            }
+            Key::Custom(name) => {
+                if let Some(custom) = self.custom_keycodes.get(name) {
+                    return yaml_str(&custom.legend(labels));
+                }
+            }
+            _ => (),
+        }
+        key.to_yaml(labels)
+    }
+
+    // Turns the keys named after `custom_keycodes` into `Key::Custom`, they are parsed as special
+    // or unknown keycodes as the keymap.c is not available when parsing the keymap layers.
+    pub fn set_custom_keycodes(&mut self, custom_keycodes: HashMap<String, CustomKeycode>) {
+        for layer in self.layers.iter_mut() {
+            for key in layer.iter_mut() {
+                if let Key::SpecialKey(name) | Key::Unknown(name) = key {
+                    if custom_keycodes.contains_key(name) {
+                        *key = Key::Custom(name.to_string());
+                    }
+                }
+            }
+        }
+        self.custom_keycodes = custom_keycodes;
+    }
+
+    pub fn to_yaml(&self, layer_names: &LayerNames, labels: &Labels) -> Result<String, &'static str> {
+        let yaml = self.to_yaml_highlighted(layer_names, labels, &[]);
+        Ok(yaml)
+        //Err("unimplemented")
+    }
+
+    // Renders the keymap with the keys at the (layer, index) positions of `highlighted` drawn with
+    // the `changed` type
+    pub fn to_yaml_highlighted(&self, layer_names: &LayerNames, labels: &Labels, highlighted: &[(usize, usize)]) -> String {
+        let mut yaml = String::new();
+        if !highlighted.is_empty() {
+            yaml.push_str(&format!("draw_config:\n  svg_extra_style: {}\n", yaml_str(HIGHLIGHT_STYLE)));
+        }
+        yaml.push_str("layout:");
         yaml.push(' ');
         // {qmk_keyboard: planck/rev7, layout_name: LAYOUT_ortho_4x12}
-        yaml.push_str(
-            format!(
-                "{{qmk_keyboard: {}, layout_name: {}}}",
-                self.keyboard, self.layout
-            )
-            .as_str(),
-        );
+        // keymaps which don't come from QMK have no keyboard, they are drawn as a grid
+        let layout = match (&self.info_json, self.keyboard.is_empty()) {
+            (Some(info_json), _) => format!("{{qmk_info_json: {}, layout_name: LAYOUT}}", yaml_str(&info_json.display().to_string())),
+            (None, true) => format!("{{ortho_layout: {{split: false, rows: {}, columns: {}}}}}", self.num_rows, self.num_columns),
+            (None, false) => format!("{{qmk_keyboard: {}, layout_name: {}}}", self.keyboard, self.layout),
+        };
+        yaml.push_str(&layout);
         yaml.push('\n');
         yaml.push_str("layers:");
         yaml.push('\n');
 
-        let mut i = 0;
-        for layer in &self.layers {
+        for (i, layer) in self.layers.iter().enumerate() {
             yaml.push_str("  ");
-            yaml.push_str(&layer_names.get(i));
+            yaml.push_str(&yaml_str(&layer_names.get(i)));
             yaml.push_str(":\n");
             //println!("layer #{i}: {} elements: {:?}", layer.len(), layer);
-            for chunk in layer.chunks(self.num_columns) {
+            let layer_name = layer_names.get(i);
+            for (row, chunk) in layer.chunks(self.num_columns).enumerate() {
                 // transform keycode
                 // enum {
                 // UnicodeSingle,
@@ -89,27 +467,165 @@ impl Keymap {
                 // UG_*, UM(), UP(), LT()
                 yaml.push_str("  ");
                 yaml.push_str("- [");
-                let yaml_keys: Vec<String> = chunk.iter().map(|key| key.to_yaml()).collect();
+                let yaml_keys: Vec<String> = chunk
+                    .iter()
+                    .enumerate()
+                    .map(|(column, key)| {
+                        let key_yaml = self.key_to_yaml(&layer_name, key, labels);
+                        match highlighted.contains(&(i, row * self.num_columns + column)) {
+                            true => highlight(&key_yaml),
+                            false => key_yaml,
+                        }
+                    })
+                    .collect();
                 yaml.push_str(&yaml_keys.join(", "));
                 yaml.push_str("]\n");
             }
-            i += 1;
         }
-        println!("{}", yaml);
-        return Ok(yaml);
-        //Err("unimplemented")
+        yaml.push_str(&self.combos_to_yaml(layer_names, labels));
+        yaml
     }
 
-    pub fn to_file(&self, path: &PathBuf, layer_names: &LayerNames) -> Result<(), &'static str> {
-        let mut file = match File::create(&path) {
+    pub fn to_file(&self, path: &PathBuf, layer_names: &LayerNames, labels: &Labels) -> Result<(), &'static str> {
+        let mut file = match File::create(path) {
             Err(_) => return Err("couldn't create file"),
             Ok(f) => f,
         };
 
-        let yaml = self.to_yaml(layer_names)?;
+        let yaml = self.to_yaml(layer_names, labels)?;
         match file.write_all(yaml.as_bytes()) {
             Err(_) => Err("couldn't write to file"),
             Ok(_) => Ok(()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaml_str_quotes_the_labels_which_are_not_strings() {
+        for label in ["~", "null", "Null", "true", "False", "yes", "NO", "on", "off", "1", "0", "-1", "1.5", ".5", "1e3", "0x1F", ".inf", "=", "<<", ""] {
+            assert!(yaml_str(label).starts_with('"'), "{label}");
+        }
+        for label in ["A", "F1", "Esc", "Caps Word", "⌫", "Y", "N", "1st", "1.2.3", ".", "(", "^"] {
+            assert_eq!(yaml_str(label), label);
+        }
+        assert_eq!(yaml_str("\""), r#""\"""#);
+        assert_eq!(yaml_str("\\"), r#""\\""#);
+    }
+
+    #[test]
+    fn encoders_are_drawn_as_combos() {
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "LAYOUT", "layers": [["KC_A", "KC_B", "KC_C", "KC_D", "KC_E", "KC_F"], ["KC_1", "KC_2", "KC_3", "KC_4", "KC_5", "KC_6"]],
+            "encoders": [[{"ccw": "KC_VOLD", "cw": "KC_VOLU"}], [{"ccw": "KC_PGUP", "cw": "KC_PGDN"}]]}"#;
+        let layer_names = LayerNames::new(vec![String::from("Base"), String::from("Lower")]);
+        let keymap = crate::qmk::parser::keymap_from_str(json, 2, &layer_names).unwrap();
+        let yaml = keymap.to_yaml_highlighted(&layer_names, &Labels::new(), &[]);
+        let (layers, combos) = yaml.split_once("combos:\n").unwrap();
+        assert_eq!(layers.lines().filter(|line| line.starts_with("  - [")).count(), 4);
+        let combos: Vec<&str> = combos.lines().collect();
+        assert_eq!(combos.len(), 2);
+        assert!(combos[0].starts_with("  - {p: [3, 4], k: {t: "));
+        assert!(combos[0].ends_with(", type: encoder}, l: [Base], a: bottom}"));
+        assert!(combos[1].ends_with(", type: encoder}, l: [Lower], a: bottom}"));
+    }
+
+    #[test]
+    fn layer_and_unknown_legends_are_quoted() {
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "LAYOUT", "layers": [["LT(FOO, KC_A)", "MO(_ON)", "LT(_ON, KC_B)", "KC_C"]],
+            "combos": [{"keys": ["KC_A", "KC_C"], "output": "KC_ESC", "layers": ["_ON"]}]}"#;
+        let layer_names = LayerNames::new(vec![String::from("On")]);
+        let mut keymap = crate::qmk::parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        keymap.combos[0].positions = vec![0, 3];
+        let yaml = keymap.to_yaml(&layer_names, &Labels::new()).unwrap();
+        assert!(yaml.contains("\n  \"On\":\n"), "{yaml}");
+        assert!(yaml.contains(r#"- ["LT(FOO, KC_A)", "On", {t: B, h: "On"}, C]"#), "{yaml}");
+        assert!(yaml.contains(r#"l: ["On"]"#), "{yaml}");
+    }
+
+    #[test]
+    fn custom_keycodes_use_the_label_overrides() {
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "LAYOUT", "layers": [["ERGOL", "QWERTY", "DVORAK"]]}"#;
+        let layer_names = LayerNames::new(vec![String::from("Qwerty")]);
+        let mut keymap = crate::qmk::parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        let custom = |name: &str, layer: &str| (name.to_string(), CustomKeycode { name: name.to_string(), action: Some(CustomAction::DefaultLayer(layer.to_string())) });
+        keymap.set_custom_keycodes(HashMap::from([custom("ERGOL", "ErgoL"), custom("DVORAK", "Dvorak")]));
+        keymap.layers[0][1] = Key::Custom(String::from("QWERTY"));
+        let mut labels = Labels::new();
+        labels.add_overrides(&HashMap::from([(String::from("ERGOL"), String::from("Ergo L")), (String::from("QWERTY"), String::from("Qwerty"))]));
+        let yaml = keymap.to_yaml(&layer_names, &labels).unwrap();
+        assert!(yaml.contains("- [Ergo L, Qwerty, Dvorak]"), "{yaml}");
+    }
+
+    #[test]
+    fn tap_dances_are_drawn_with_their_actions() {
+        let keymap_c = "
+            tap_dance_action_t tap_dance_actions[] = {
+                [TD_ESC] = ACTION_TAP_DANCE_DOUBLE(KC_ESC, KC_CAPS),
+                [TD_HOLD] = ACTION_TAP_DANCE_TAP_HOLD(KC_A, KC_LCTL),
+                [TD_LAYER] = ACTION_TAP_DANCE_LAYER_TOGGLE(KC_B, _LOWER),
+                [TD_FN] = ACTION_TAP_DANCE_FN_ADVANCED(NULL, dance_finished, dance_reset),
+            };";
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "", "layers": [["TD(TD_ESC)", "TD(TD_HOLD)", "TD(TD_LAYER)", "TD(TD_FN)", "TD(TD_NONE)"], ["KC_TRNS", "KC_TRNS", "KC_TRNS", "KC_TRNS", "KC_TRNS"]]}"#;
+        let layer_names = LayerNames::new(vec![String::from("Base"), String::from("Lower")]);
+        let mut keymap = crate::qmk::parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        keymap.tap_dances = crate::qmk::keymap_c::tap_dances(keymap_c, &layer_names);
+        let yaml = keymap.to_yaml(&layer_names, &Labels::new()).unwrap();
+        assert!(yaml.contains("\n  - [{t: Esc, s: 2× Caps}, {t: A, h: Ctrl}, {t: B, s: 2× Lower}, {t: dance_finished}, TD(TD_NONE)]\n"), "{yaml}");
+    }
+
+    #[test]
+    fn combos_are_drawn_on_their_layers() {
+        let keymap_c = "
+            const uint16_t PROGMEM jk_combo[] = {KC_J, KC_K, COMBO_END};
+            const uint16_t PROGMEM df_combo[] = {KC_D, KC_F, COMBO_END};
+            const uint16_t PROGMEM xz_combo[] = {KC_X, KC_Z, COMBO_END};
+            combo_t key_combos[] = {
+                [JK_ESC] = COMBO(jk_combo, KC_ESC),
+                [DF_TAB] = COMBO(df_combo, KC_TAB),
+                [XZ_UNDO] = COMBO(xz_combo, LCTL(KC_Z)),
+            };
+            bool combo_should_trigger(uint16_t combo_index, combo_t *combo, uint16_t keycode, keyrecord_t *record) {
+                switch (combo_index) {
+                    case DF_TAB:
+                        if (layer_state_is(_LOWER)) return false;
+                }
+                return true;
+            }";
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "", "layers": [["KC_D", "KC_F", "KC_J", "KC_K"], ["KC_1", "KC_2", "KC_3", "KC_4"], ["KC_5", "KC_6", "KC_7", "KC_8"]]}"#;
+        let layer_names = LayerNames::new(["Base", "Lower", "Raise"].iter().map(|name| name.to_string()).collect());
+        let mut keymap = crate::qmk::parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        (keymap.combos, _) = crate::qmk::keymap_c::combos(keymap_c, &layer_names, layer_names.names());
+        assert_eq!(keymap.locate_combos(0), [String::from("XZ_UNDO")]);
+        let yaml = keymap.to_yaml(&layer_names, &Labels::new()).unwrap();
+        let (_, combos) = yaml.split_once("combos:\n").unwrap();
+        // the combos whose keys are not on the base layer are not drawn
+        assert_eq!(combos, "  - {p: [2, 3], k: Esc}\n  - {p: [0, 1], k: ⇥, l: [Base, Raise]}\n");
+    }
+
+    #[test]
+    fn key_overrides_are_drawn_as_shifted_legends() {
+        let keymap_c = "
+            const key_override_t delete_key_override = ko_make_basic(MOD_MASK_SHIFT, KC_BSPC, KC_DEL);
+            const key_override_t home_key_override = ko_make_with_layers(MOD_MASK_CTRL, KC_LEFT, KC_HOME, 1 << _LOWER);
+            const key_override_t unused_key_override = ko_make_basic(MOD_MASK_SHIFT, KC_A, KC_B);
+            const key_override_t *key_overrides[] = {
+                &delete_key_override,
+                &home_key_override,
+                NULL
+            };";
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "", "layers": [["KC_BSPC", "KC_LEFT", "KC_A"], ["LT(_BASE, KC_BSPC)", "KC_LEFT", "KC_TRNS"]]}"#;
+        let layer_names = LayerNames::new(vec![String::from("Base"), String::from("Lower")]);
+        let mut keymap = crate::qmk::parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        keymap.key_overrides = crate::qmk::keymap_c::key_overrides(keymap_c, &layer_names, layer_names.names());
+        assert_eq!(keymap.key_overrides.len(), 2);
+        let yaml = keymap.to_yaml(&layer_names, &Labels::new()).unwrap();
+        let rows: Vec<&str> = yaml.lines().filter(|line| line.starts_with("  - [")).collect();
+        assert_eq!(rows, [
+            "  - [{t: ⌫, s: Del}, ←, A]",
+            "  - [{t: ⌫, s: Del, h: Base}, {t: ←, s: Ctrl Home}, {t: ▽, type: trans}]",
+        ]);
+    }
+}
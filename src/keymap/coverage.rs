@@ -0,0 +1,94 @@
+use crate::keymap::find::{self, Match};
+use crate::keymap::layer::LayerNames;
+use crate::keymap::Keymap;
+use crate::qmk::keymap_c::UnicodeMap;
+use crate::qmk::keymap_extras::Locale;
+
+const FRENCH_LOWER: &str = "àâæçéèêëîïôœùûüÿ";
+const FRENCH_SYMBOLS: &str = "€«»";
+
+// Returns the characters of a predefined character set, `ascii` or `french`
+pub fn charset(name: &str) -> Option<Vec<char>> {
+    match name {
+        "ascii" => Some((' '..='~').collect()),
+        "french" => {
+            let mut chars: Vec<char> = FRENCH_LOWER.chars().collect();
+            chars.extend(FRENCH_LOWER.chars().flat_map(|c| c.to_uppercase()));
+            chars.extend(FRENCH_SYMBOLS.chars());
+            Some(chars)
+        }
+        _ => None,
+    }
+}
+
+// Returns the characters used in `text` in order of first appearance, without duplicates.
+// Control characters are skipped, they are typed with keys such as Enter or Tab.
+pub fn text_chars(text: &str) -> Vec<char> {
+    let mut chars = Vec::new();
+    for c in text.chars() {
+        if !c.is_control() && !chars.contains(&c) {
+            chars.push(c);
+        }
+    }
+    chars
+}
+
+pub struct CharCoverage {
+    pub character: char,
+    // the way to type the character needing the fewest key presses, `None` if it can't be typed
+    pub best: Option<Match>,
+}
+
+impl CharCoverage {
+    // number of keys pressed to type the character, including layer keys and modifiers
+    pub fn presses(&self) -> Option<usize> {
+        self.best.as_ref().map(|m| m.layer_keys.len() + m.modifiers.len() + 1)
+    }
+}
+
+// Matches on layers which can't be reached with layer keys are ignored
+pub fn coverage(keymap: &Keymap, layer_names: &LayerNames, chars: &[char], locale: &Locale, unicode: &UnicodeMap) -> Vec<CharCoverage> {
+    let mut coverage = Vec::new();
+    for c in chars {
+        let matches = find::find(keymap, layer_names, &c.to_string(), locale, unicode);
+        let best = matches
+            .into_iter()
+            .filter(|m| m.reachable && !m.hold)
+            .min_by_key(|m| m.layer_keys.len() + m.modifiers.len());
+        coverage.push(CharCoverage { character: *c, best });
+    }
+    coverage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qmk::parser;
+
+    #[test]
+    fn characters_are_counted_with_the_fewest_key_presses() {
+        let layer_names = LayerNames::new(["Base", "Lower", "Hidden"].iter().map(|name| name.to_string()).collect());
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "", "layers": [
+            ["KC_A", "LT(_LOWER, KC_SPC)", "KC_SLSH"],
+            ["KC_QUES", "KC_TRNS", "KC_B"],
+            ["KC_C", "KC_NO", "KC_NO"]
+        ]}"#;
+        let keymap = parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        let chars = text_chars("aA ?b\tc?");
+        assert_eq!(chars, ['a', 'A', ' ', '?', 'b', 'c']);
+        let coverage = coverage(&keymap, &layer_names, &chars, &Locale::us(), &UnicodeMap::new());
+        let presses: Vec<Option<usize>> = coverage.iter().map(|c| c.presses()).collect();
+        // `?` is on the lower layer, and shifted on the base one; the hidden layer can't be reached
+        assert_eq!(presses, [Some(1), Some(2), Some(1), Some(2), Some(2), None]);
+        assert_eq!(coverage.iter().filter(|c| c.best.is_some()).count(), 5);
+    }
+
+    #[test]
+    fn charsets_are_predefined() {
+        assert_eq!(charset("ascii").map(|chars| chars.len()), Some(95));
+        let french = charset("french").unwrap();
+        assert_eq!(french.len(), 35);
+        assert!(french.contains(&'Ÿ') && french.contains(&'€'));
+        assert_eq!(charset("klingon"), None);
+    }
+}
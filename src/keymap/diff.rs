@@ -0,0 +1,126 @@
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{Key, Keymap};
+
+// Layers with less than this ratio of identical keys are not considered the same layer
+const MIN_SIMILARITY: f64 = 0.5;
+
+// A difference between two keymaps. Key positions are indexes in the layers of the new keymap,
+// except for removed layers.
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    LayerAdded { layer: usize },
+    LayerRemoved { layer: usize },
+    // `from` is the layer index in the old keymap, `to` in the new one
+    LayerMoved { from: usize, to: usize },
+    LayerRenamed { layer: usize, old_name: String },
+    KeyChanged { layer: usize, index: usize, old: Key, new: Key },
+    // same tap key, different hold behaviour
+    HoldChanged { layer: usize, index: usize, old: Key, new: Key },
+    KeyMoved { layer: usize, from: usize, to: usize, key: Key },
+}
+
+impl Change {
+    // Position of the changed key in the new keymap
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match self {
+            Change::KeyChanged { layer, index, .. } | Change::HoldChanged { layer, index, .. } => Some((*layer, *index)),
+            Change::KeyMoved { layer, to, .. } => Some((*layer, *to)),
+            _ => None,
+        }
+    }
+}
+
+fn similarity(old: &[Key], new: &[Key]) -> f64 {
+    if old.len() != new.len() || old.is_empty() {
+        return 0.0;
+    }
+    old.iter().zip(new).filter(|(o, n)| o == n).count() as f64 / old.len() as f64
+}
+
+// Returns for each layer of `new` the index of the matching layer in `old`. Layers are matched
+// on their content, and on their name or position when their content changed a lot.
+fn match_layers(old: &Keymap, old_names: &LayerNames, new: &Keymap, new_names: &LayerNames) -> Vec<Option<usize>> {
+    let mut pairs = Vec::new();
+    for (n, new_layer) in new.layers.iter().enumerate() {
+        for (o, old_layer) in old.layers.iter().enumerate() {
+            let mut score = similarity(old_layer, new_layer);
+            // on similar content, keep the layers with the same name or position together
+            if old_names.get(o) == new_names.get(n) {
+                score += 0.01;
+            }
+            if o == n {
+                score += 0.005;
+            }
+            if score >= MIN_SIMILARITY || old_names.get(o) == new_names.get(n) {
+                pairs.push((score, n, o));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut matches = vec![None; new.layers.len()];
+    let mut used = vec![false; old.layers.len()];
+    for (_, n, o) in pairs {
+        if matches[n].is_none() && !used[o] {
+            matches[n] = Some(o);
+            used[o] = true;
+        }
+    }
+    matches
+}
+
+fn hold_changed(old: &Key, new: &Key) -> bool {
+    let tap = |key: &Key| key.tap_key().unwrap_or(key.clone());
+    tap(old) == tap(new) && (old.tap_key().is_some() || new.tap_key().is_some())
+}
+
+fn diff_layer(layer: usize, old: &[Key], new: &[Key], changes: &mut Vec<Change>) {
+    let changed: Vec<usize> = (0..new.len()).filter(|i| old.get(*i) != Some(&new[*i])).collect();
+    for &to in &changed {
+        let key = &new[to];
+        let Some(old_key) = old.get(to) else {
+            changes.push(Change::KeyChanged { layer, index: to, old: Key::No, new: key.clone() });
+            continue;
+        };
+        // a key is moved when it was at another changed position which doesn't have it anymore
+        let from = changed.iter().find(|&&from| old.get(from) == Some(key) && new[from] != *key);
+        match from {
+            Some(&from) if !matches!(key, Key::No | Key::Trans) => {
+                changes.push(Change::KeyMoved { layer, from, to, key: key.clone() })
+            }
+            _ if hold_changed(old_key, key) => {
+                changes.push(Change::HoldChanged { layer, index: to, old: old_key.clone(), new: key.clone() })
+            }
+            _ => changes.push(Change::KeyChanged { layer, index: to, old: old_key.clone(), new: key.clone() }),
+        }
+    }
+}
+
+// Compares two keymaps key by key. Layer references in the old keymap are not rewritten, a
+// renamed layer also shows up as changed `MO()`, `LT()`, ... keys.
+pub fn diff(old: &Keymap, old_names: &LayerNames, new: &Keymap, new_names: &LayerNames) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let matches = match_layers(old, old_names, new, new_names);
+    for (o, _) in old.layers.iter().enumerate() {
+        if !matches.contains(&Some(o)) {
+            changes.push(Change::LayerRemoved { layer: o });
+        }
+    }
+    for (n, matching) in matches.iter().enumerate() {
+        let Some(o) = *matching else {
+            changes.push(Change::LayerAdded { layer: n });
+            continue;
+        };
+        // the names of moved layers are not compared, they often come from their position
+        if o == n && old_names.get(o) != new_names.get(n) {
+            changes.push(Change::LayerRenamed { layer: n, old_name: old_names.get(o) });
+        }
+        // only report the layers which moved relatively to the other ones
+        let moved = matches.iter().take(n).flatten().any(|&p| p > o);
+        if o != n && moved {
+            changes.push(Change::LayerMoved { from: o, to: n });
+        }
+        diff_layer(n, &old.layers[o], &new.layers[n], &mut changes);
+    }
+    changes
+}
+
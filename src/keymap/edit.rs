@@ -0,0 +1,410 @@
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{CustomAction, Key, Keymap};
+use crate::qmk::parser::layer_id;
+
+// Calls `f` on every key of the keymap: layers, encoders, combos, tap dances and key overrides
+fn for_each_key(keymap: &mut Keymap, mut f: impl FnMut(&mut Key)) {
+    keymap.layers.iter_mut().flatten().for_each(&mut f);
+    for encoder in keymap.encoders.iter_mut().flatten() {
+        f(&mut encoder.ccw);
+        f(&mut encoder.cw);
+    }
+    for combo in keymap.combos.iter_mut() {
+        combo.keys.iter_mut().for_each(&mut f);
+        f(&mut combo.result);
+    }
+    for td in keymap.tap_dances.values_mut() {
+        td.tap.iter_mut().chain(td.double_tap.iter_mut()).chain(td.hold.iter_mut()).for_each(&mut f);
+    }
+    for ko in keymap.key_overrides.iter_mut() {
+        f(&mut ko.trigger);
+        f(&mut ko.replacement);
+    }
+    for custom in keymap.custom_keycodes.values_mut() {
+        if let Some(CustomAction::Tap(key)) = &mut custom.action {
+            f(key);
+        }
+    }
+}
+
+// Returns the layer referenced by `key`, if any
+pub fn layer_reference(key: &Key) -> Option<&str> {
+    match key {
+        Key::LayerTap { layer, .. } | Key::LayerChange { layer, .. } => Some(layer),
+        _ => None,
+    }
+}
+
+// Rewrites the references to the layer `from`. Keys referencing a layer which is deleted
+// (`to` is `None`) are replaced with their tap key for layer-taps, and with `KC_NO` otherwise.
+fn rewrite_layer_references(keymap: &mut Keymap, from: &str, to: Option<&str>) {
+    for_each_key(keymap, |key| {
+        if !layer_reference(key).is_some_and(|layer| layer.eq_ignore_ascii_case(from)) {
+            return;
+        }
+        match (to, &mut *key) {
+            (Some(to), Key::LayerTap { layer, .. } | Key::LayerChange { layer, .. }) => *layer = to.to_string(),
+            (None, _) => *key = key.tap_key().unwrap_or(Key::No),
+            _ => (),
+        }
+    });
+    let rename = |layers: &mut Option<Vec<String>>| {
+        if let Some(layers) = layers {
+            match to {
+                Some(to) => layers.iter_mut().filter(|l| l.eq_ignore_ascii_case(from)).for_each(|l| *l = to.to_string()),
+                None => layers.retain(|l| !l.eq_ignore_ascii_case(from)),
+            }
+        }
+    };
+    keymap.combos.iter_mut().for_each(|combo| rename(&mut combo.layers));
+    keymap.key_overrides.iter_mut().for_each(|ko| rename(&mut ko.layers));
+    for custom in keymap.custom_keycodes.values_mut() {
+        if let (Some(CustomAction::DefaultLayer(layer)), Some(to)) = (&mut custom.action, to) {
+            if layer.eq_ignore_ascii_case(from) {
+                *layer = to.to_string();
+            }
+        }
+    }
+}
+
+// Renumbers the keys referring to layers by number once the layers are reordered, the layer `i`
+// becoming the layer `numbers[i]`. Keys referring to a deleted layer (`None`) are removed as in
+// `rewrite_layer_references`, the numbers of the layers which don't exist are kept.
+fn renumber_layer_references(keymap: &mut Keymap, numbers: &[Option<usize>]) {
+    for_each_key(keymap, |key| {
+        let (Key::LayerTap { index: Some(idx), .. } | Key::LayerChange { index: Some(idx), .. }) = key else {
+            return;
+        };
+        match numbers.get(*idx) {
+            Some(Some(number)) => *idx = *number,
+            Some(None) => *key = key.tap_key().unwrap_or(Key::No),
+            None => (),
+        }
+    });
+}
+
+// Identifiers of the layers which change number, the layer `i` becoming the layer `numbers[i]`,
+// and which keys refer to by name. These are constants of the layer enum of keymap.c, which gives
+// them their number, so they must be reordered there too.
+fn named_layer_references(keymap: &mut Keymap, layer_names: &LayerNames, numbers: &[Option<usize>]) -> Vec<String> {
+    let mut identifiers: Vec<String> = Vec::new();
+    for_each_key(keymap, |key| {
+        let (Key::LayerTap { layer, index: None, .. } | Key::LayerChange { layer, index: None, .. }) = key else {
+            return;
+        };
+        let moved = layer_names.index(layer).is_some_and(|idx| numbers.get(idx).is_some_and(|n| n.is_some_and(|n| n != idx)));
+        if moved && !identifiers.contains(&layer_id(layer)) {
+            identifiers.push(layer_id(layer));
+        }
+    });
+    identifiers
+}
+
+fn layer_index(keymap: &Keymap, layer_names: &LayerNames, layer: &str) -> Result<usize, &'static str> {
+    let idx = match layer.parse::<usize>() {
+        Ok(idx) => Some(idx),
+        Err(_) => layer_names.pretty_name(layer).and_then(|name| layer_names.index(&name)),
+    };
+    idx.filter(|idx| *idx < keymap.layers.len()).ok_or("unknown layer")
+}
+
+fn check_new_name(layer_names: &LayerNames, name: &str) -> Result<(), &'static str> {
+    if layer_names.pretty_name(name).is_some() {
+        return Err("a layer with this name already exists");
+    }
+    Ok(())
+}
+
+// Swaps the keys at positions `a` and `b` on all the layers
+pub fn swap_keys(keymap: &mut Keymap, a: usize, b: usize) -> Result<(), &'static str> {
+    if a >= keymap.num_rows * keymap.num_columns || b >= keymap.num_rows * keymap.num_columns {
+        return Err("key position out of range");
+    }
+    for layer in keymap.layers.iter_mut() {
+        layer.swap(a, b);
+    }
+    Ok(())
+}
+
+// Moves `layer` so that it becomes the layer number `to`. The keys referring to layers by number
+// are renumbered, the ones referring to them by name are not changed: the identifiers of the
+// moved layers they use are returned, they must be reordered in keymap.c.
+pub fn move_layer(keymap: &mut Keymap, layer_names: &mut LayerNames, layer: &str, to: usize) -> Result<Vec<String>, &'static str> {
+    let from = layer_index(keymap, layer_names, layer)?;
+    if to >= keymap.layers.len() {
+        return Err("layer position out of range");
+    }
+    layer_names.fill(keymap.layers.len());
+    let mut order: Vec<usize> = (0..keymap.layers.len()).collect();
+    order.remove(from);
+    order.insert(to, from);
+    let mut numbers = vec![None; order.len()];
+    for (number, idx) in order.into_iter().enumerate() {
+        numbers[idx] = Some(number);
+    }
+    let identifiers = named_layer_references(keymap, layer_names, &numbers);
+    renumber_layer_references(keymap, &numbers);
+    let keys = keymap.layers.remove(from);
+    keymap.layers.insert(to, keys);
+    let name = layer_names.remove(from);
+    layer_names.insert(to, name);
+    if from < keymap.encoders.len() {
+        let encoders = keymap.encoders.remove(from);
+        keymap.encoders.insert(to.min(keymap.encoders.len()), encoders);
+    }
+    Ok(identifiers)
+}
+
+// Adds a copy of `layer` named `name` right after it. Keys referencing `layer` keep on
+// referencing the original layer, the layers after it are renumbered as in `move_layer`.
+pub fn duplicate_layer(keymap: &mut Keymap, layer_names: &mut LayerNames, layer: &str, name: &str) -> Result<Vec<String>, &'static str> {
+    let idx = layer_index(keymap, layer_names, layer)?;
+    check_new_name(layer_names, name)?;
+    layer_names.fill(keymap.layers.len());
+    let numbers: Vec<Option<usize>> = (0..keymap.layers.len()).map(|i| Some(if i > idx { i + 1 } else { i })).collect();
+    let identifiers = named_layer_references(keymap, layer_names, &numbers);
+    renumber_layer_references(keymap, &numbers);
+    keymap.layers.insert(idx + 1, keymap.layers[idx].clone());
+    layer_names.insert(idx + 1, name.to_string());
+    if idx < keymap.encoders.len() {
+        keymap.encoders.insert(idx + 1, keymap.encoders[idx].clone());
+    }
+    Ok(identifiers)
+}
+
+// Deletes `layer`, the keys which were activating it are removed too, see
+// `rewrite_layer_references`. The layers after it are renumbered as in `move_layer`.
+pub fn delete_layer(keymap: &mut Keymap, layer_names: &mut LayerNames, layer: &str) -> Result<Vec<String>, &'static str> {
+    let idx = layer_index(keymap, layer_names, layer)?;
+    if keymap.layers.len() == 1 {
+        return Err("can't delete the only layer");
+    }
+    layer_names.fill(keymap.layers.len());
+    let numbers: Vec<Option<usize>> = (0..keymap.layers.len())
+        .map(|i| match i.cmp(&idx) {
+            Ordering::Less => Some(i),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(i - 1),
+        })
+        .collect();
+    let identifiers = named_layer_references(keymap, layer_names, &numbers);
+    keymap.layers.remove(idx);
+    let name = layer_names.remove(idx);
+    if idx < keymap.encoders.len() {
+        keymap.encoders.remove(idx);
+    }
+    rewrite_layer_references(keymap, &name, None);
+    renumber_layer_references(keymap, &numbers);
+    Ok(identifiers)
+}
+
+// Renames `layer` to `name`, rewriting all the keys referencing it. When keys refer to it by name,
+// the old and new identifiers are returned: the constant of the layer enum of keymap.c must be
+// renamed too.
+pub fn rename_layer(keymap: &mut Keymap, layer_names: &mut LayerNames, layer: &str, name: &str) -> Result<Option<(String, String)>, &'static str> {
+    let idx = layer_index(keymap, layer_names, layer)?;
+    check_new_name(layer_names, name)?;
+    layer_names.fill(keymap.layers.len());
+    let old_name = layer_names.remove(idx);
+    layer_names.insert(idx, name.to_string());
+    let mut named = false;
+    for_each_key(keymap, |key| {
+        if let Key::LayerTap { layer, index: None, .. } | Key::LayerChange { layer, index: None, .. } = key {
+            named |= layer.eq_ignore_ascii_case(&old_name);
+        }
+    });
+    rewrite_layer_references(keymap, &old_name, Some(name));
+    Ok(Some((layer_id(&old_name), layer_id(name))).filter(|_| named))
+}
+
+// Replaces `from` with `to` everywhere in the keymap, returns the number of replaced keys
+pub fn replace_key(keymap: &mut Keymap, from: &Key, to: &Key) -> usize {
+    let mut count = 0;
+    for_each_key(keymap, |key| {
+        if key == from {
+            *key = to.clone();
+            count += 1;
+        }
+    });
+    count
+}
+
+// Copies `layer` from `other` at the end of `keymap`, named `name`. The copied keys referring to
+// layers by number are given the numbers of the layers with the same names in `keymap`. Returns
+// the layers referenced by the copied keys which don't exist in `keymap`.
+pub fn copy_layer(
+    keymap: &mut Keymap,
+    layer_names: &mut LayerNames,
+    other: &Keymap,
+    other_names: &LayerNames,
+    layer: &str,
+    name: &str,
+) -> Result<Vec<String>, &'static str> {
+    let idx = layer_index(other, other_names, layer)?;
+    check_new_name(layer_names, name)?;
+    if other.layers[idx].len() != keymap.num_rows * keymap.num_columns {
+        return Err("the keymaps don't have the same number of keys");
+    }
+    layer_names.fill(keymap.layers.len());
+    layer_names.insert(keymap.layers.len(), name.to_string());
+    let mut keys = other.layers[idx].clone();
+    for key in keys.iter_mut() {
+        if let Key::LayerTap { layer, index: index @ Some(_), .. } | Key::LayerChange { layer, index: index @ Some(_), .. } = key {
+            *index = layer_names.index(layer);
+        }
+    }
+    keymap.layers.push(keys);
+    if let Some(encoders) = other.encoders.get(idx).filter(|e| !e.is_empty()) {
+        keymap.encoders.resize(keymap.layers.len() - 1, Vec::new());
+        keymap.encoders.push(encoders.clone());
+    }
+    let mut missing = Vec::new();
+    for key in &other.layers[idx] {
+        if let Some(layer) = layer_reference(key) {
+            if layer_names.pretty_name(layer).is_none() && !missing.iter().any(|m| m == layer) {
+                missing.push(layer.to_string());
+            }
+        }
+    }
+    Ok(missing)
+}
+
+// Edit operations of the `edit` subcommand, layers are given by name or by index
+pub enum Operation {
+    SwapKeys(usize, usize),
+    MoveLayer { layer: String, to: usize },
+    DuplicateLayer { layer: String, name: String },
+    DeleteLayer(String),
+    RenameLayer { layer: String, name: String },
+    ReplaceKey { from: String, to: String },
+    // `from` is a keymap.json file
+    CopyLayer { from: PathBuf, layer: String, name: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qmk::parser;
+
+    // Layer keys referring to the layers by name and by number, on each of the layers
+    const KEYMAP: &str = r#"{
+        "keyboard": "planck/rev6",
+        "keymap": "test",
+        "layout": "LAYOUT_ortho_1x4",
+        "layers": [
+            ["MO(_LOWER)", "MO(1)", "LT(3, KC_SPC)", "LT(_ADJUST, KC_ENT)"],
+            ["KC_A", "TG(2)", "KC_TRNS", "KC_TRNS"],
+            ["KC_B", "KC_TRNS", "TO(0)", "KC_TRNS"],
+            ["KC_C", "KC_TRNS", "KC_TRNS", "DF(_BASE)"]
+        ]
+    }"#;
+
+    fn keymap() -> (Keymap, LayerNames) {
+        let layer_names = LayerNames::new(["Base", "Lower", "Raise", "Adjust"].iter().map(|name| name.to_string()).collect());
+        let keymap = parser::keymap_from_str(KEYMAP, 1, &layer_names).unwrap();
+        (keymap, layer_names)
+    }
+
+    fn keycodes(keymap: &Keymap) -> Vec<Vec<String>> {
+        keymap.layers.iter().map(|layer| layer.iter().map(parser::keycode).collect()).collect()
+    }
+
+    #[test]
+    fn numeric_layer_references_are_parsed() {
+        let (keymap, _) = keymap();
+        let lower = Key::LayerChange { action: String::from("MO"), layer: String::from("Lower"), index: Some(1) };
+        assert_eq!(keymap.layers[0][1], lower);
+        assert_eq!(layer_reference(&keymap.layers[0][2]), Some("Adjust"));
+        assert_eq!(keycodes(&keymap)[0], ["MO(_LOWER)", "MO(1)", "LT(3, KC_SPC)", "LT(_ADJUST, KC_ENT)"]);
+    }
+
+    #[test]
+    fn move_layer_renumbers_references() {
+        let (mut keymap, mut layer_names) = keymap();
+        move_layer(&mut keymap, &mut layer_names, "Lower", 3).unwrap();
+        assert_eq!(layer_names.names(), ["Base", "Raise", "Adjust", "Lower"]);
+        assert_eq!(keycodes(&keymap), [
+            ["MO(_LOWER)", "MO(3)", "LT(2, KC_SPC)", "LT(_ADJUST, KC_ENT)"],
+            ["KC_B", "KC_TRNS", "TO(0)", "KC_TRNS"],
+            ["KC_C", "KC_TRNS", "KC_TRNS", "DF(_BASE)"],
+            ["KC_A", "TG(1)", "KC_TRNS", "KC_TRNS"],
+        ]);
+    }
+
+    #[test]
+    fn move_layer_to_the_top() {
+        let (mut keymap, mut layer_names) = keymap();
+        move_layer(&mut keymap, &mut layer_names, "2", 0).unwrap();
+        assert_eq!(layer_names.names(), ["Raise", "Base", "Lower", "Adjust"]);
+        assert_eq!(keycodes(&keymap)[0], ["KC_B", "KC_TRNS", "TO(1)", "KC_TRNS"]);
+        assert_eq!(keycodes(&keymap)[1], ["MO(_LOWER)", "MO(2)", "LT(3, KC_SPC)", "LT(_ADJUST, KC_ENT)"]);
+        assert_eq!(keycodes(&keymap)[2], ["KC_A", "TG(0)", "KC_TRNS", "KC_TRNS"]);
+    }
+
+    #[test]
+    fn delete_layer_removes_and_renumbers_references() {
+        let (mut keymap, mut layer_names) = keymap();
+        delete_layer(&mut keymap, &mut layer_names, "Lower").unwrap();
+        assert_eq!(layer_names.names(), ["Base", "Raise", "Adjust"]);
+        assert_eq!(keycodes(&keymap), [
+            ["KC_NO", "KC_NO", "LT(2, KC_SPC)", "LT(_ADJUST, KC_ENT)"],
+            ["KC_B", "KC_TRNS", "TO(0)", "KC_TRNS"],
+            ["KC_C", "KC_TRNS", "KC_TRNS", "DF(_BASE)"],
+        ]);
+    }
+
+    #[test]
+    fn delete_layer_keeps_the_tap_keys() {
+        let (mut keymap, mut layer_names) = keymap();
+        delete_layer(&mut keymap, &mut layer_names, "_ADJUST").unwrap();
+        assert_eq!(keycodes(&keymap)[0], ["MO(_LOWER)", "MO(1)", "KC_SPC", "KC_ENT"]);
+        assert_eq!(keycodes(&keymap)[1], ["KC_A", "TG(2)", "KC_TRNS", "KC_TRNS"]);
+    }
+
+    #[test]
+    fn duplicate_layer_renumbers_the_following_layers() {
+        let (mut keymap, mut layer_names) = keymap();
+        duplicate_layer(&mut keymap, &mut layer_names, "Lower", "Symbols").unwrap();
+        assert_eq!(layer_names.names(), ["Base", "Lower", "Symbols", "Raise", "Adjust"]);
+        assert_eq!(keycodes(&keymap), [
+            ["MO(_LOWER)", "MO(1)", "LT(4, KC_SPC)", "LT(_ADJUST, KC_ENT)"],
+            ["KC_A", "TG(3)", "KC_TRNS", "KC_TRNS"],
+            ["KC_A", "TG(3)", "KC_TRNS", "KC_TRNS"],
+            ["KC_B", "KC_TRNS", "TO(0)", "KC_TRNS"],
+            ["KC_C", "KC_TRNS", "KC_TRNS", "DF(_BASE)"],
+        ]);
+    }
+
+    #[test]
+    fn named_references_of_moved_layers_are_reported() {
+        let reported = |edit: fn(&mut Keymap, &mut LayerNames) -> Result<Vec<String>, &'static str>| {
+            let (mut keymap, mut layer_names) = keymap();
+            let identifiers = edit(&mut keymap, &mut layer_names).unwrap();
+            // the named references are left as they are
+            assert_eq!(keycodes(&keymap)[0][0], "MO(_LOWER)");
+            identifiers
+        };
+        assert_eq!(reported(|keymap, layer_names| move_layer(keymap, layer_names, "Lower", 3)), ["_LOWER", "_ADJUST"]);
+        assert_eq!(reported(|keymap, layer_names| duplicate_layer(keymap, layer_names, "Lower", "Symbols")), ["_ADJUST"]);
+        assert_eq!(reported(|keymap, layer_names| delete_layer(keymap, layer_names, "Raise")), ["_ADJUST"]);
+        assert!(reported(|keymap, layer_names| move_layer(keymap, layer_names, "Raise", 2)).is_empty());
+    }
+
+    #[test]
+    fn rename_layer_reports_the_identifier_to_rename() {
+        let (mut keymap, mut layer_names) = keymap();
+        let renamed = rename_layer(&mut keymap, &mut layer_names, "Lower", "Nav").unwrap();
+        assert_eq!(renamed, Some((String::from("_LOWER"), String::from("_NAV"))));
+        assert_eq!(layer_names.names(), ["Base", "Nav", "Raise", "Adjust"]);
+        assert_eq!(keycodes(&keymap)[0], ["MO(_NAV)", "MO(1)", "LT(3, KC_SPC)", "LT(_ADJUST, KC_ENT)"]);
+    }
+
+    #[test]
+    fn rename_layer_referred_to_by_number() {
+        let (mut keymap, mut layer_names) = keymap();
+        assert_eq!(rename_layer(&mut keymap, &mut layer_names, "Raise", "Symbols").unwrap(), None);
+        assert_eq!(keycodes(&keymap)[1], ["KC_A", "TG(2)", "KC_TRNS", "KC_TRNS"]);
+    }
+}
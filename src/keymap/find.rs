@@ -0,0 +1,324 @@
+use std::collections::VecDeque;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{CustomAction, Key, Keymap};
+use crate::qmk::keymap_c::UnicodeMap;
+use crate::qmk::keymap_extras::{Level, Locale, US_SHIFTED};
+
+const LEVELS: [Level; 4] = [Level::Base, Level::Shift, Level::AltGr, Level::ShiftAltGr];
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Position {
+    pub layer: usize,
+    pub index: usize,
+}
+
+impl Position {
+    pub fn row(&self, keymap: &Keymap) -> usize {
+        self.index / keymap.num_columns
+    }
+
+    pub fn column(&self, keymap: &Keymap) -> usize {
+        self.index % keymap.num_columns
+    }
+}
+
+// A key which needs to be used to activate a layer
+#[derive(Clone)]
+pub struct LayerKey {
+    pub position: Position,
+    // "hold", "toggle", ... see `layer_action`
+    pub action: &'static str,
+}
+
+// How a layer can be activated from the base layer
+#[derive(Clone)]
+pub struct Activation {
+    // active layers, from the bottom to the top one
+    pub stack: Vec<usize>,
+    pub keys: Vec<LayerKey>,
+}
+
+// Something a key types, `modifiers` need to be held in addition to the key
+pub struct Output {
+    pub text: String,
+    pub modifiers: Vec<String>,
+    pub hold: bool,
+}
+
+// A way to type the searched character or keycode
+pub struct Match {
+    pub position: Position,
+    pub layer_keys: Vec<LayerKey>,
+    // false when no layer key activates the layer, it may be activated from keymap.c code
+    pub reachable: bool,
+    pub modifiers: Vec<String>,
+    pub hold: bool,
+}
+
+fn layer_action(action: &str) -> &'static str {
+    match action {
+        "TG" => "toggle",
+        "TO" => "switch to",
+        "OSL" => "one-shot",
+        "DF" | "PDF" => "set default",
+        _ => "hold",
+    }
+}
+
+// Returns the key at `index` when the layers in `stack` are active, skipping transparent keys
+pub fn effective_key<'a>(keymap: &'a Keymap, stack: &[usize], index: usize) -> &'a Key {
+    for layer in stack.iter().rev() {
+        let key = &keymap.layers[*layer][index];
+        if !matches!(key, Key::Trans) {
+            return key;
+        }
+    }
+    &keymap.layers[stack[0]][index]
+}
+
+// Computes how each layer can be reached from the base layer, using the shortest sequence of
+// layer keys. Unreachable layers (for example the ones only activated from keymap.c code) are
+// `None`.
+pub fn activations(keymap: &Keymap, layer_names: &LayerNames) -> Vec<Option<Activation>> {
+    let mut activations: Vec<Option<Activation>> = vec![None; keymap.layers.len()];
+    if keymap.layers.is_empty() {
+        return activations;
+    }
+    activations[0] = Some(Activation { stack: vec![0], keys: Vec::new() });
+    let mut queue = VecDeque::from([0]);
+    while let Some(layer) = queue.pop_front() {
+        let activation = activations[layer].clone().unwrap();
+        for index in 0..keymap.layers[layer].len() {
+            let (target, action) = match effective_key(keymap, &activation.stack, index) {
+                Key::LayerTap { layer, .. } => (layer, "hold"),
+                Key::LayerChange { action, layer, .. } => (layer, layer_action(action)),
+                Key::Custom(name) => match keymap.custom_keycodes.get(name).and_then(|c| c.action.as_ref()) {
+                    Some(CustomAction::DefaultLayer(layer)) => (layer, "set default"),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let Some(target) = layer_names.index(target) else {
+                continue;
+            };
+            if target >= activations.len() || activations[target].is_some() {
+                continue;
+            }
+            let mut next = activation.clone();
+            next.stack.push(target);
+            next.keys.push(LayerKey { position: Position { layer, index }, action });
+            activations[target] = Some(next);
+            queue.push_back(target);
+        }
+    }
+    activations
+}
+
+// Returns what the OS types for `keycode` at `level`. Letters are only listed in uppercase in
+// keymap_extras headers, their lowercase version is typed without shift.
+fn level_output(locale: &Locale, keycode: &str, level: Level) -> Option<String> {
+    if let Some(key) = locale.output(keycode, level) {
+        let mut chars = key.output.chars();
+        if let (Some(c), None, Level::Base) = (chars.next(), chars.next(), level) {
+            if c.is_alphabetic() {
+                return Some(c.to_lowercase().to_string());
+            }
+        }
+        return Some(key.output.to_string());
+    }
+    if level == Level::Shift {
+        let base = locale.output(keycode, Level::Base)?;
+        let mut chars = base.output.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c.is_alphabetic() {
+                return Some(c.to_uppercase().to_string());
+            }
+        }
+    }
+    None
+}
+
+fn basic_outputs(keycode: &str, locale: &Locale) -> Vec<Output> {
+    let mut outputs = Vec::new();
+    if let Some((_, base, _)) = US_SHIFTED.iter().find(|(shifted, _, _)| *shifted == keycode) {
+        if let Some(text) = level_output(locale, base, Level::Shift) {
+            outputs.push(Output { text, modifiers: Vec::new(), hold: false });
+        }
+        return outputs;
+    }
+    for level in LEVELS {
+        if let Some(text) = level_output(locale, keycode, level) {
+            let modifiers = level.modifiers().iter().map(|m| m.to_string()).collect();
+            outputs.push(Output { text, modifiers, hold: false });
+        }
+    }
+    outputs
+}
+
+fn with_kc_prefix(key: &str) -> String {
+    match key.strip_prefix("KC_") {
+        Some(_) => key.to_string(),
+        None => format!("KC_{key}"),
+    }
+}
+
+// Returns everything `key` can type, including with shift and AltGr
+pub fn key_outputs(key: &Key, locale: &Locale, unicode: &UnicodeMap) -> Vec<Output> {
+    match key {
+        Key::BasicKey(key) => basic_outputs(&with_kc_prefix(key), locale),
+        Key::ModTap { key, .. } | Key::LayerTap { key, .. } => basic_outputs(&with_kc_prefix(key), locale),
+        Key::SpecialKey(name) => {
+            let Some(locale_key) = locale.get(name) else {
+                return Vec::new();
+            };
+            match level_output(locale, &locale_key.keycode, locale_key.level) {
+                Some(text) => vec![Output { text, modifiers: Vec::new(), hold: false }],
+                None => Vec::new(),
+            }
+        }
+        Key::ModKey { modifier, key } => {
+            let level = match modifier.as_str() {
+                "S" | "LSFT" | "RSFT" => Level::Shift,
+                "ALGR" | "RALT" => Level::AltGr,
+                _ => return Vec::new(),
+            };
+            match level_output(locale, &with_kc_prefix(key), level) {
+                Some(text) => vec![Output { text, modifiers: Vec::new(), hold: false }],
+                None => Vec::new(),
+            }
+        }
+        Key::Unicode(lower, upper) => {
+            let mut outputs = Vec::new();
+            if let Some(c) = unicode.get(lower) {
+                outputs.push(Output { text: c.to_string(), modifiers: Vec::new(), hold: false });
+            }
+            if let Some(c) = upper.as_ref().and_then(|upper| unicode.get(upper)) {
+                outputs.push(Output { text: c.to_string(), modifiers: vec![String::from("Shift")], hold: false });
+            }
+            outputs
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Returns the keycodes sent by `key`, as outputs so that `LSFT(KC_1)` can be found when
+// looking for `KC_1`, and the hold keycode of mod-taps is found too.
+pub fn key_keycodes(key: &Key) -> Vec<Output> {
+    let output = |text: String, modifiers: Vec<String>, hold: bool| Output { text, modifiers, hold };
+    match key {
+        Key::BasicKey(key) => vec![output(with_kc_prefix(key), Vec::new(), false)],
+        Key::SpecialKey(key) => vec![output(key.to_string(), Vec::new(), false)],
+        Key::ModTap { modifier, key } => vec![
+            output(with_kc_prefix(key), Vec::new(), false),
+            output(with_kc_prefix(modifier), Vec::new(), true),
+        ],
+        Key::LayerTap { key, .. } => vec![output(with_kc_prefix(key), Vec::new(), false)],
+        Key::ModKey { modifier, key } => vec![
+            output(format!("{modifier}({})", with_kc_prefix(key)), Vec::new(), false),
+            output(with_kc_prefix(key), vec![modifier.to_string()], false),
+        ],
+        Key::Unicode(lower, None) => vec![output(format!("UM({lower})"), Vec::new(), false)],
+        Key::Unicode(lower, Some(upper)) => vec![output(format!("UP({lower}, {upper})"), Vec::new(), false)],
+        Key::TapDance(name) => vec![output(format!("TD({name})"), Vec::new(), false)],
+        Key::Macro(idx) => vec![output(format!("QK_MACRO_{idx}"), Vec::new(), false)],
+        Key::Custom(name) => vec![output(name.to_string(), Vec::new(), false)],
+        Key::Unknown(key) => vec![output(key.to_string(), Vec::new(), false)],
+        _ => Vec::new(),
+    }
+}
+
+// Returns true if `query` is a single character, false if it should be handled as a keycode
+pub fn is_character(query: &str) -> bool {
+    query.chars().count() == 1
+}
+
+// Finds all the ways to type `query` (a character or a keycode) with `keymap`
+pub fn find(keymap: &Keymap, layer_names: &LayerNames, query: &str, locale: &Locale, unicode: &UnicodeMap) -> Vec<Match> {
+    let character = is_character(query);
+    let mut matches = Vec::new();
+    for (layer, activation) in activations(keymap, layer_names).into_iter().enumerate() {
+        let reachable = activation.is_some();
+        let activation = activation.unwrap_or(Activation { stack: vec![layer], keys: Vec::new() });
+        for index in 0..keymap.layers[layer].len() {
+            let position = Position { layer, index };
+            // keys held to activate the layer can't be used
+            if activation.keys.iter().any(|k| k.action == "hold" && k.position.index == index) {
+                continue;
+            }
+            let key = &keymap.layers[layer][index];
+            // transparent keys are found on the layers below
+            if matches!(key, Key::Trans) {
+                continue;
+            }
+            let outputs = match character {
+                true => key_outputs(key, locale, unicode),
+                false => key_keycodes(key),
+            };
+            for output in outputs.into_iter().filter(|o| o.text == query) {
+                matches.push(Match {
+                    position,
+                    layer_keys: activation.keys.clone(),
+                    reachable,
+                    modifiers: output.modifiers,
+                    hold: output.hold,
+                });
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qmk::parser;
+
+    fn keymap() -> (Keymap, LayerNames) {
+        let layer_names = LayerNames::new(["Base", "Lower", "Raise"].iter().map(|name| name.to_string()).collect());
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "", "layers": [
+            ["KC_A", "LT(_LOWER, KC_SPC)", "LSFT_T(KC_B)", "KC_NO"],
+            ["KC_TRNS", "KC_TRNS", "LSFT(KC_SLSH)", "TG(_RAISE)"],
+            ["KC_QUES", "KC_TRNS", "KC_TRNS", "KC_TRNS"]
+        ]}"#;
+        (parser::keymap_from_str(json, 1, &layer_names).unwrap(), layer_names)
+    }
+
+    // (layer, index, layer keys, modifiers, hold) of a match
+    type Found = (usize, usize, Vec<(usize, usize, &'static str)>, Vec<String>, bool);
+
+    fn found(query: &str) -> Vec<Found> {
+        let (keymap, layer_names) = keymap();
+        find(&keymap, &layer_names, query, &Locale::us(), &UnicodeMap::new())
+            .into_iter()
+            .map(|m| {
+                let keys = m.layer_keys.iter().map(|k| (k.position.layer, k.position.index, k.action)).collect();
+                (m.position.layer, m.position.index, keys, m.modifiers, m.hold)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn characters_are_found_with_the_keys_activating_their_layer() {
+        assert_eq!(found("?"), [
+            (1, 2, vec![(0, 1, "hold")], vec![], false),
+            (2, 0, vec![(0, 1, "hold"), (1, 3, "toggle")], vec![], false),
+        ]);
+        assert_eq!(found("a"), [(0, 0, vec![], vec![], false)]);
+        assert_eq!(found("A"), [(0, 0, vec![], vec![String::from("Shift")], false)]);
+        assert!(found("€").is_empty());
+    }
+
+    #[test]
+    fn keycodes_are_found_with_their_modifiers() {
+        assert_eq!(found("KC_SLSH"), [(1, 2, vec![(0, 1, "hold")], vec![String::from("LSFT")], false)]);
+        assert_eq!(found("KC_LSFT"), [(0, 2, vec![], vec![], true)]);
+        assert_eq!(found("TG(_RAISE)"), []);
+    }
+
+    #[test]
+    fn transparent_keys_are_resolved_on_the_layers_below() {
+        let (keymap, _) = keymap();
+        assert_eq!(effective_key(&keymap, &[0, 1, 2], 1), &keymap.layers[0][1]);
+        assert_eq!(effective_key(&keymap, &[0, 1], 2), &keymap.layers[1][2]);
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::qmk::keymap_extras::{Level, Locale, US_SHIFTED};
+
+// Short labels for the keycodes which are not readable once their `KC_` prefix is stripped.
+// Keycodes missing from this table fall back to the label from the QMK keycode definitions if
+// available, and to their name otherwise.
+const BUILTIN_LABELS: &[(&str, &str)] = &[
+    ("KC_ENT", "⏎"),
+    ("KC_ENTER", "⏎"),
+    ("KC_ESC", "Esc"),
+    ("KC_ESCAPE", "Esc"),
+    ("KC_BSPC", "⌫"),
+    ("KC_BACKSPACE", "⌫"),
+    ("KC_TAB", "⇥"),
+    ("KC_SPC", "␣"),
+    ("KC_SPACE", "␣"),
+    ("KC_MINS", "-"),
+    ("KC_MINUS", "-"),
+    ("KC_EQL", "="),
+    ("KC_EQUAL", "="),
+    ("KC_LBRC", "["),
+    ("KC_LEFT_BRACKET", "["),
+    ("KC_RBRC", "]"),
+    ("KC_RIGHT_BRACKET", "]"),
+    ("KC_BSLS", "\\"),
+    ("KC_BACKSLASH", "\\"),
+    ("KC_NUHS", "#"),
+    ("KC_NONUS_HASH", "#"),
+    ("KC_SCLN", ";"),
+    ("KC_SEMICOLON", ";"),
+    ("KC_QUOT", "'"),
+    ("KC_QUOTE", "'"),
+    ("KC_GRV", "`"),
+    ("KC_GRAVE", "`"),
+    ("KC_COMM", ","),
+    ("KC_COMMA", ","),
+    ("KC_DOT", "."),
+    ("KC_SLSH", "/"),
+    ("KC_SLASH", "/"),
+    ("KC_NUBS", "\\"),
+    ("KC_NONUS_BACKSLASH", "\\"),
+    ("KC_CAPS", "Caps"),
+    ("KC_CAPS_LOCK", "Caps"),
+    ("KC_PSCR", "PrtSc"),
+    ("KC_PRINT_SCREEN", "PrtSc"),
+    ("KC_SCRL", "ScrLk"),
+    ("KC_SCROLL_LOCK", "ScrLk"),
+    ("KC_PAUS", "Pause"),
+    ("KC_PAUSE", "Pause"),
+    ("KC_INS", "Ins"),
+    ("KC_INSERT", "Ins"),
+    ("KC_HOME", "Home"),
+    ("KC_PGUP", "PgUp"),
+    ("KC_PAGE_UP", "PgUp"),
+    ("KC_DEL", "Del"),
+    ("KC_DELETE", "Del"),
+    ("KC_END", "End"),
+    ("KC_PGDN", "PgDn"),
+    ("KC_PAGE_DOWN", "PgDn"),
+    ("KC_RGHT", "→"),
+    ("KC_RIGHT", "→"),
+    ("KC_LEFT", "←"),
+    ("KC_DOWN", "↓"),
+    ("KC_UP", "↑"),
+    ("KC_APP", "Menu"),
+    ("KC_APPLICATION", "Menu"),
+    ("KC_LCTL", "Ctrl"),
+    ("KC_LEFT_CTRL", "Ctrl"),
+    ("KC_LSFT", "Shift"),
+    ("KC_LEFT_SHIFT", "Shift"),
+    ("KC_LALT", "Alt"),
+    ("KC_LEFT_ALT", "Alt"),
+    ("KC_LGUI", "Gui"),
+    ("KC_LEFT_GUI", "Gui"),
+    ("KC_RCTL", "Ctrl"),
+    ("KC_RIGHT_CTRL", "Ctrl"),
+    ("KC_RSFT", "Shift"),
+    ("KC_RIGHT_SHIFT", "Shift"),
+    ("KC_RALT", "AltGr"),
+    ("KC_RIGHT_ALT", "AltGr"),
+    ("KC_RGUI", "Gui"),
+    ("KC_RIGHT_GUI", "Gui"),
+    ("KC_TILD", "~"),
+    ("KC_TILDE", "~"),
+    ("KC_EXLM", "!"),
+    ("KC_EXCLAIM", "!"),
+    ("KC_AT", "@"),
+    ("KC_HASH", "#"),
+    ("KC_DLR", "$"),
+    ("KC_DOLLAR", "$"),
+    ("KC_PERC", "%"),
+    ("KC_PERCENT", "%"),
+    ("KC_CIRC", "^"),
+    ("KC_CIRCUMFLEX", "^"),
+    ("KC_AMPR", "&"),
+    ("KC_AMPERSAND", "&"),
+    ("KC_ASTR", "*"),
+    ("KC_ASTERISK", "*"),
+    ("KC_LPRN", "("),
+    ("KC_LEFT_PAREN", "("),
+    ("KC_RPRN", ")"),
+    ("KC_RIGHT_PAREN", ")"),
+    ("KC_UNDS", "_"),
+    ("KC_UNDERSCORE", "_"),
+    ("KC_PLUS", "+"),
+    ("KC_LCBR", "{"),
+    ("KC_LEFT_CURLY_BRACE", "{"),
+    ("KC_RCBR", "}"),
+    ("KC_RIGHT_CURLY_BRACE", "}"),
+    ("KC_PIPE", "|"),
+    ("KC_COLN", ":"),
+    ("KC_COLON", ":"),
+    ("KC_DQUO", "\""),
+    ("KC_DQT", "\""),
+    ("KC_DOUBLE_QUOTE", "\""),
+    ("KC_LT", "<"),
+    ("KC_LABK", "<"),
+    ("KC_LEFT_ANGLE_BRACKET", "<"),
+    ("KC_GT", ">"),
+    ("KC_RABK", ">"),
+    ("KC_RIGHT_ANGLE_BRACKET", ">"),
+    ("KC_QUES", "?"),
+    ("KC_QUESTION", "?"),
+    ("KC_MNXT", "⏭"),
+    ("KC_MEDIA_NEXT_TRACK", "⏭"),
+    ("KC_MPRV", "⏮"),
+    ("KC_MEDIA_PREV_TRACK", "⏮"),
+    ("KC_MPLY", "⏯"),
+    ("KC_MEDIA_PLAY_PAUSE", "⏯"),
+    ("KC_MSTP", "⏹"),
+    ("KC_MEDIA_STOP", "⏹"),
+    ("KC_MUTE", "Mute"),
+    ("KC_AUDIO_MUTE", "Mute"),
+    ("KC_VOLU", "Vol+"),
+    ("KC_AUDIO_VOL_UP", "Vol+"),
+    ("KC_VOLD", "Vol-"),
+    ("KC_AUDIO_VOL_DOWN", "Vol-"),
+    ("KC_BRIU", "Bri+"),
+    ("KC_BRIGHTNESS_UP", "Bri+"),
+    ("KC_BRID", "Bri-"),
+    ("KC_BRIGHTNESS_DOWN", "Bri-"),
+    ("QK_BOOT", "Boot"),
+    ("QK_BOOTLOADER", "Boot"),
+    ("QK_RBT", "Reboot"),
+    ("QK_REBOOT", "Reboot"),
+    ("DB_TOGG", "Debug"),
+    ("QK_DEBUG_TOGGLE", "Debug"),
+    ("EE_CLR", "EE Clr"),
+    ("QK_CLEAR_EEPROM", "EE Clr"),
+    ("QK_LLCK", "Lock"),
+    ("QK_LAYER_LOCK", "Lock"),
+    ("CW_TOGG", "Caps Word"),
+    ("QK_CAPS_WORD_TOGGLE", "Caps Word"),
+    ("UG_TOGG", "RGB"),
+    ("UG_NEXT", "Mode+"),
+    ("UG_PREV", "Mode-"),
+    ("UG_HUEU", "Hue+"),
+    ("UG_HUED", "Hue-"),
+    ("UG_SATU", "Sat+"),
+    ("UG_SATD", "Sat-"),
+    ("UG_VALU", "Bri+"),
+    ("UG_VALD", "Bri-"),
+    ("UG_SPDU", "Spd+"),
+    ("UG_SPDD", "Spd-"),
+    ("MU_TOGG", "Music"),
+    ("MU_ON", "Music On"),
+    ("MU_OFF", "Music Off"),
+    ("MU_NEXT", "Music Mode"),
+    ("AU_TOGG", "Audio"),
+    ("AU_ON", "Audio On"),
+    ("AU_OFF", "Audio Off"),
+    ("AU_NEXT", "Voice+"),
+    ("AU_PREV", "Voice-"),
+    ("UC_NEXT", "Uni Mode+"),
+    ("UC_PREV", "Uni Mode-"),
+    ("UC_MAC", "Uni macOS"),
+    ("UC_LINX", "Uni Linux"),
+    ("UC_WIN", "Uni Win"),
+    ("UC_WINC", "Uni WinC"),
+];
+
+pub struct Labels {
+    labels: HashMap<String, String>,
+}
+
+impl Labels {
+    pub fn new() -> Labels {
+        let mut labels: HashMap<String, String> = BUILTIN_LABELS.iter().map(|(k, l)| (k.to_string(), l.to_string())).collect();
+        for (_, keycode, output) in US_SHIFTED {
+            labels.insert(Level::Shift.wrap(keycode), output.to_string());
+        }
+        Labels {
+            labels,
+        }
+    }
+
+    // `spec_labels` are the labels from the QMK keycode definitions, they are only used for the
+    // keycodes which are not in the builtin table.
+    pub fn add_spec_labels(&mut self, spec_labels: HashMap<String, String>) {
+        for (keycode, label) in spec_labels.into_iter() {
+            self.labels.entry(keycode).or_insert(label);
+        }
+    }
+
+    // Labels basic keycodes with what the OS types when using `locale` as its input language.
+    // The locale specific keycodes (`FR_EACU`) are labelled too, as well as the shifted US
+    // keycodes (`KC_EXLM` is `S(KC_1)`, which types `1` with a French layout).
+    pub fn add_locale(&mut self, locale: &Locale) {
+        for key in &locale.keys {
+            self.labels.insert(key.name.to_string(), key.output.to_string());
+            self.labels.insert(key.level.wrap(&key.keycode), key.output.to_string());
+        }
+        for (shifted, keycode, _) in US_SHIFTED {
+            if let Some(key) = locale.output(keycode, Level::Shift) {
+                self.labels.insert(shifted.to_string(), key.output.to_string());
+            }
+        }
+    }
+
+    // `overrides` come from the keymap configuration and take precedence over everything else.
+    pub fn add_overrides(&mut self, overrides: &HashMap<String, String>) {
+        for (keycode, label) in overrides.iter() {
+            self.labels.insert(keycode.to_string(), label.to_string());
+        }
+    }
+
+    pub fn get(&self, keycode: &str) -> Option<&str> {
+        self.labels.get(keycode).map(|label| label.as_str())
+    }
+
+    // Returns the label for `keycode`, or the keycode without its `KC_` prefix if there is none.
+    // `keycode` can be given with or without its `KC_` prefix.
+    pub fn label(&self, keycode: &str) -> String {
+        if let Some(label) = self.get(keycode) {
+            return label.to_string();
+        }
+        if let Some(basic) = keycode.strip_prefix("KC_") {
+            return basic.to_string();
+        }
+        if let Some(label) = self.get(&format!("KC_{keycode}")) {
+            return label.to_string();
+        }
+        keycode.to_string()
+    }
+
+    // Returns the label for `modifier(keycode)`, this is what the OS types if the modifier is
+    // shift or AltGr and the output is known, `modifier(label)` otherwise.
+    pub fn label_with_modifier(&self, modifier: &str, keycode: &str) -> String {
+        let level = match modifier {
+            "S" | "LSFT" | "RSFT" => Some(Level::Shift),
+            "ALGR" | "RALT" => Some(Level::AltGr),
+            _ => None,
+        };
+        let basic = match keycode.strip_prefix("KC_") {
+            Some(_) => keycode.to_string(),
+            None => format!("KC_{keycode}"),
+        };
+        if let Some(label) = level.and_then(|level| self.get(&level.wrap(&basic))) {
+            return label.to_string();
+        }
+        format!("{modifier}({})", self.label(keycode))
+    }
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Labels::new()
+    }
+}
+
+// Reads label overrides from a JSON object mapping keycodes to labels, for example
+// `{"KC_SPC": "Space", "QWERTY": "Qwerty"}`.
+pub fn overrides_from_file(path: &Path) -> Result<HashMap<String, String>, &'static str> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(_) => return Err("Unable to read file"),
+    };
+
+    match serde_json::from_str::<HashMap<String, String>>(&data) {
+        Ok(overrides) => Ok(overrides),
+        Err(_) => Err("Unable to parse file"),
+    }
+}
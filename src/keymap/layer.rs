@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct LayerNames {
     names: Vec<String>,
 }
@@ -23,6 +24,10 @@ impl LayerNames {
         None
     }
 
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|layer| layer.eq_ignore_ascii_case(name))
+    }
+
     pub fn get(&self, idx: usize) -> String {
         if let Some(name) = self.names.get(idx) {
             name.to_string()
@@ -30,4 +35,24 @@ impl LayerNames {
             format!("layer{idx}")
         }
     }
+
+    // Makes sure all the `len` layers have a name, unnamed layers get the `layerN` name
+    // returned by `get`
+    pub fn fill(&mut self, len: usize) {
+        for idx in self.names.len()..len {
+            self.names.push(self.get(idx));
+        }
+    }
+
+    pub fn insert(&mut self, idx: usize, name: String) {
+        self.names.insert(idx, name);
+    }
+
+    pub fn remove(&mut self, idx: usize) -> String {
+        self.names.remove(idx)
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
 }
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::keymap::find;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{CustomAction, Key, Keymap};
+use crate::qmk::diagnostics::{self, KeycodeDatabase};
+use crate::qmk::keymap_extras::US_SHIFTED;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Off,
+    Warning,
+    Error,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "off" | "allow" => Some(Level::Off),
+            "warning" | "warn" => Some(Level::Warning),
+            "error" | "deny" => Some(Level::Error),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Off => "off",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+// (name, default level, description)
+pub const RULES: &[(&str, Level, &str)] = &[
+    ("tap-not-basic", Level::Error, "LT()/MT() tap keycode which is not a basic keycode, QMK truncates it"),
+    ("trans-on-base", Level::Warning, "KC_TRNS on the base layer"),
+    ("missing-layer", Level::Error, "layer key activating a layer which doesn't exist"),
+    ("no-exit", Level::Error, "layer switched to or toggled on without any key to leave it"),
+    ("duplicate-mod", Level::Warning, "same home-row mod twice on the same hand"),
+    ("no-under-hold", Level::Warning, "KC_NO on a layer under the key held to activate it"),
+    ("unknown-keycode", Level::Warning, "keycode which is not known or could not be parsed"),
+];
+
+pub struct Violation {
+    pub rule: &'static str,
+    pub level: Level,
+    pub layer: Option<usize>,
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+// Level of each rule, see `RULES` for the defaults
+pub struct LintConfig {
+    levels: HashMap<String, Level>,
+}
+
+impl LintConfig {
+    pub fn new() -> LintConfig {
+        LintConfig {
+            levels: RULES.iter().map(|(name, level, _)| (name.to_string(), *level)).collect(),
+        }
+    }
+
+    pub fn set(&mut self, rule: &str, level: Level) -> Result<(), &'static str> {
+        match self.levels.get_mut(rule) {
+            Some(current) => {
+                *current = level;
+                Ok(())
+            }
+            None => Err("unknown lint rule"),
+        }
+    }
+
+    // JSON object with the rule names as keys, and `off`, `warning` or `error` as values
+    pub fn from_json(data: &str) -> Result<LintConfig, &'static str> {
+        let levels: HashMap<String, String> = serde_json::from_str(data).map_err(|_| "Unable to parse lint configuration")?;
+        let mut config = LintConfig::new();
+        for (rule, level) in levels {
+            config.set(&rule, Level::from_name(&level).ok_or("unknown lint level")?)?;
+        }
+        Ok(config)
+    }
+
+    fn level(&self, rule: &str) -> Level {
+        self.levels.get(rule).copied().unwrap_or(Level::Off)
+    }
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_basic(keycode: &str) -> bool {
+    keycode.starts_with("KC_") && !US_SHIFTED.iter().any(|(shifted, _, _)| *shifted == keycode)
+}
+
+fn tap_not_basic(key: &Key) -> Option<String> {
+    lazy_static! {
+        // LT() and MT() which could not be parsed, `LT(_LOWER, LSFT(KC_A))`
+        static ref TAP_HOLD: Regex = Regex::new(r"^(?:LT\(\s*\w+\s*,|[A-Z_]+_T\()\s*(.+)\)$").unwrap();
+    }
+    let tap = match key {
+        Key::LayerTap { key, .. } => key.to_string(),
+        Key::ModTap { key, .. } => format!("KC_{key}"),
+        Key::Unknown(keycode) => TAP_HOLD.captures(keycode)?[1].to_string(),
+        _ => return None,
+    };
+    match is_basic(&tap) {
+        true => None,
+        false => Some(format!("tap keycode {tap} is not a basic keycode")),
+    }
+}
+
+// Returns the layer activated by `key` if it doesn't exist
+fn missing_layer(key: &Key, keymap: &Keymap, layer_names: &LayerNames) -> Option<String> {
+    lazy_static! {
+        static ref LAYER_KEY: Regex = Regex::new(r"^(?:MO|TG|TO|TT|OSL|DF|PDF|LT)\(\s*(\w+)").unwrap();
+    }
+    let layer = match key {
+        Key::LayerTap { index: Some(idx), .. } | Key::LayerChange { index: Some(idx), .. } => idx.to_string(),
+        Key::LayerTap { layer, .. } | Key::LayerChange { layer, .. } => layer.to_string(),
+        Key::Unknown(keycode) => LAYER_KEY.captures(keycode)?[1].to_string(),
+        _ => return None,
+    };
+    let idx = match layer.parse::<usize>() {
+        Ok(idx) => Some(idx),
+        Err(_) => layer_names.pretty_name(&layer).and_then(|name| layer_names.index(&name)),
+    };
+    match idx.filter(|idx| *idx < keymap.layers.len()) {
+        Some(_) => None,
+        None => Some(format!("layer {layer} does not exist")),
+    }
+}
+
+// Layer changed by `key` when it's a default layer change: `DF()`, `PDF()` or a custom keycode
+// calling `set_single_persistent_default_layer()`
+fn default_layer_change<'a>(key: &'a Key, keymap: &'a Keymap) -> Option<&'a str> {
+    match key {
+        Key::LayerChange { action, layer, .. } if action == "DF" || action == "PDF" => Some(layer),
+        Key::Custom(name) => match keymap.custom_keycodes.get(name).and_then(|c| c.action.as_ref()) {
+            Some(CustomAction::DefaultLayer(layer)) => Some(layer),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Layers which stay active once their key is released, with `true` for the default layers and
+// `false` for the toggled or switched to layers
+fn sticky_layers(keymap: &Keymap, layer_names: &LayerNames) -> Vec<(usize, bool)> {
+    let mut layers = Vec::new();
+    for key in keymap.layers.iter().flatten() {
+        let (layer, default) = match (key, default_layer_change(key, keymap)) {
+            (_, Some(layer)) => (layer, true),
+            (Key::LayerChange { action, layer, .. }, None) if action == "TG" || action == "TO" => (layer.as_str(), false),
+            _ => continue,
+        };
+        if let Some(idx) = layer_names.index(layer).filter(|idx| *idx != 0 && *idx < keymap.layers.len()) {
+            if !layers.contains(&(idx, default)) {
+                layers.push((idx, default));
+            }
+        }
+    }
+    layers
+}
+
+// A default layer can be left with any other default layer change. A toggled layer needs a key
+// toggling it off or switching to another layer, on the layer itself or on one of the layers
+// momentarily activated from it.
+fn has_exit(keymap: &Keymap, layer_names: &LayerNames, layer: usize, default: bool) -> bool {
+    if default {
+        return keymap.layers.iter().flatten().any(|key| {
+            default_layer_change(key, keymap).is_some_and(|target| layer_names.index(target) != Some(layer))
+        });
+    }
+    let is_exit = |key: &Key| match key {
+        Key::LayerChange { action, layer: name, .. } => match action.as_str() {
+            "TG" | "TT" => layer_names.index(name) == Some(layer),
+            "TO" => layer_names.index(name) != Some(layer),
+            _ => false,
+        },
+        _ => false,
+    };
+    let stack = [0, layer];
+    let mut stacks = vec![stack.to_vec()];
+    for index in 0..keymap.layers[layer].len() {
+        let momentary = match find::effective_key(keymap, &stack, index) {
+            Key::LayerTap { layer, .. } => layer,
+            Key::LayerChange { action, layer, .. } if action == "MO" || action == "OSL" => layer,
+            _ => continue,
+        };
+        if let Some(idx) = layer_names.index(momentary).filter(|idx| *idx < keymap.layers.len()) {
+            stacks.push(vec![0, layer, idx]);
+        }
+    }
+    stacks.iter().any(|stack| (0..keymap.layers[layer].len()).any(|index| is_exit(find::effective_key(keymap, stack, index))))
+}
+
+fn base_modifier(modifier: &str) -> &str {
+    modifier.strip_prefix('L').or(modifier.strip_prefix('R')).unwrap_or(modifier)
+}
+
+// Runs the enabled rules over `keymap`, the keycodes which are not in `database` are unknown
+pub fn lint(keymap: &Keymap, layer_names: &LayerNames, config: &LintConfig, database: &KeycodeDatabase) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut report = |rule: &'static str, layer: Option<usize>, index: Option<usize>, message: String| {
+        let level = config.level(rule);
+        if level != Level::Off {
+            violations.push(Violation { rule, level, layer, index, message });
+        }
+    };
+
+    for (l, layer) in keymap.layers.iter().enumerate() {
+        for (index, key) in layer.iter().enumerate() {
+            if let Some(message) = tap_not_basic(key) {
+                report("tap-not-basic", Some(l), Some(index), message);
+            }
+            if l == 0 && *key == Key::Trans {
+                report("trans-on-base", Some(l), Some(index), String::from("transparent key on the base layer"));
+            }
+            if let Some(message) = missing_layer(key, keymap, layer_names) {
+                report("missing-layer", Some(l), Some(index), message);
+            }
+        }
+
+        // home-row mods, one row at a time, the left hand being the left half of the columns
+        for (row, keys) in layer.chunks(keymap.num_columns).enumerate() {
+            let mut seen: Vec<(bool, &str, usize)> = Vec::new();
+            for (column, key) in keys.iter().enumerate() {
+                let Key::ModTap { modifier, .. } = key else {
+                    continue;
+                };
+                let left = column < keymap.num_columns / 2;
+                let modifier = base_modifier(modifier);
+                let index = row * keymap.num_columns + column;
+                match seen.iter().find(|(l, m, _)| *l == left && *m == modifier) {
+                    Some((_, _, first)) => report(
+                        "duplicate-mod",
+                        Some(l),
+                        Some(index),
+                        format!("{modifier} is already on column {} of the same hand", first % keymap.num_columns + 1),
+                    ),
+                    None => seen.push((left, modifier, index)),
+                }
+            }
+        }
+    }
+
+    for diagnostic in diagnostics::diagnose(keymap, database) {
+        report("unknown-keycode", Some(diagnostic.layer), Some(diagnostic.index), diagnostic.message());
+    }
+
+    for (layer, action) in sticky_layers(keymap, layer_names) {
+        if !has_exit(keymap, layer_names, layer, action) {
+            report("no-exit", Some(layer), None, String::from("no key to leave this layer"));
+        }
+    }
+
+    for (source, layer) in keymap.layers.iter().enumerate() {
+        for (index, key) in layer.iter().enumerate() {
+            let target = match key {
+                Key::LayerTap { layer, .. } => layer,
+                Key::LayerChange { action, layer, .. } if matches!(action.as_str(), "MO" | "TT" | "OSL") => layer,
+                _ => continue,
+            };
+            let Some(target) = layer_names.index(target).filter(|t| *t != source && *t < keymap.layers.len()) else {
+                continue;
+            };
+            if keymap.layers[target][index] == Key::No {
+                report("no-under-hold", Some(target), Some(index), format!("KC_NO under the key held on {} to activate the layer", layer_names.get(source)));
+            }
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qmk::parser;
+
+    // (rule, layer, index, message) of the violations of a keymap with 2 rows of 4 keys
+    fn violations(layers: &str) -> Vec<(&'static str, Option<usize>, Option<usize>, String)> {
+        let layer_names = LayerNames::new(["Base", "Lower", "Raise"].iter().map(|name| name.to_string()).collect());
+        let json = format!(r#"{{"keyboard": "test", "keymap": "test", "layout": "LAYOUT", "layers": {layers}}}"#);
+        let keymap = parser::keymap_from_str(&json, 2, &layer_names).unwrap();
+        lint(&keymap, &layer_names, &LintConfig::new(), &KeycodeDatabase::builtin())
+            .into_iter()
+            .map(|v| (v.rule, v.layer, v.index, v.message))
+            .collect()
+    }
+
+    #[test]
+    fn clean_keymap() {
+        let layers = r#"[["KC_A", "KC_B", "KC_C", "KC_D", "MO(_LOWER)", "KC_SPC", "KC_ENT", "KC_E"],
+            ["KC_1", "KC_2", "KC_3", "KC_4", "KC_TRNS", "KC_TRNS", "KC_TRNS", "KC_TRNS"]]"#;
+        assert!(violations(layers).is_empty());
+    }
+
+    #[test]
+    fn tap_keycodes_must_be_basic() {
+        let layers = r#"[["LT(_LOWER, KC_EXLM)", "KC_B", "KC_C", "KC_D", "KC_E", "KC_F", "KC_G", "KC_H"],
+            ["KC_1", "KC_2", "KC_3", "KC_4", "KC_5", "KC_6", "KC_7", "KC_8"]]"#;
+        assert_eq!(violations(layers), [("tap-not-basic", Some(0), Some(0), String::from("tap keycode KC_EXLM is not a basic keycode"))]);
+    }
+
+    #[test]
+    fn base_layer_has_no_transparent_keys() {
+        let layers = r#"[["KC_A", "KC_TRNS", "KC_C", "KC_D", "KC_E", "KC_F", "KC_G", "KC_H"]]"#;
+        assert_eq!(violations(layers), [("trans-on-base", Some(0), Some(1), String::from("transparent key on the base layer"))]);
+    }
+
+    #[test]
+    fn layer_keys_activate_existing_layers() {
+        let layers = r#"[["MO(_RAISE)", "MO(4)", "KC_C", "KC_D", "KC_E", "KC_F", "KC_G", "KC_H"],
+            ["KC_1", "KC_2", "KC_3", "KC_4", "KC_5", "KC_6", "KC_7", "KC_8"]]"#;
+        let violations = violations(layers);
+        assert_eq!(violations, [
+            ("missing-layer", Some(0), Some(0), String::from("layer Raise does not exist")),
+            ("missing-layer", Some(0), Some(1), String::from("layer 4 does not exist")),
+        ]);
+    }
+
+    #[test]
+    fn toggled_layers_can_be_left() {
+        let toggled = r#"[["TG(_LOWER)", "KC_B", "KC_C", "KC_D", "KC_E", "KC_F", "KC_G", "KC_H"],
+            ["KC_1", "KC_2", "KC_3", "KC_4", "KC_5", "KC_6", "KC_7", "KC_8"]]"#;
+        assert_eq!(violations(toggled), [("no-exit", Some(1), None, String::from("no key to leave this layer"))]);
+        let left = r#"[["TG(_LOWER)", "KC_B", "KC_C", "KC_D", "KC_E", "KC_F", "KC_G", "KC_H"],
+            ["KC_TRNS", "KC_2", "KC_3", "KC_4", "KC_5", "KC_6", "KC_7", "KC_8"]]"#;
+        assert!(violations(left).is_empty());
+    }
+
+    #[test]
+    fn home_row_mods_are_not_duplicated() {
+        let layers = r#"[["KC_A", "KC_B", "KC_C", "KC_D", "LSFT_T(KC_E)", "RSFT_T(KC_F)", "LSFT_T(KC_G)", "KC_H"]]"#;
+        assert_eq!(violations(layers), [("duplicate-mod", Some(0), Some(5), String::from("SFT is already on column 1 of the same hand"))]);
+    }
+
+    #[test]
+    fn held_keys_are_not_disabled() {
+        let layers = r#"[["MO(_LOWER)", "KC_B", "KC_C", "KC_D", "KC_E", "KC_F", "KC_G", "KC_H"],
+            ["KC_NO", "KC_2", "KC_3", "KC_4", "KC_5", "KC_6", "KC_7", "KC_8"]]"#;
+        assert_eq!(violations(layers), [("no-under-hold", Some(1), Some(0), String::from("KC_NO under the key held on Base to activate the layer"))]);
+    }
+
+    #[test]
+    fn unknown_keycodes_are_reported() {
+        let layers = r#"[["KC_A", "KC_SPCE", "KC_C", "KC_D", "KC_E", "KC_F", "KC_G", "KC_H"]]"#;
+        let violations = violations(layers);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].0, "unknown-keycode");
+        assert!(violations[0].3.starts_with("unknown keycode KC_SPCE, did you mean KC_SPACE"), "{}", violations[0].3);
+    }
+
+    #[test]
+    fn rules_can_be_turned_off() {
+        let mut config = LintConfig::from_json(r#"{"trans-on-base": "off"}"#).unwrap();
+        assert_eq!(config.level("trans-on-base"), Level::Off);
+        config.set("no-exit", Level::Error).unwrap();
+        assert_eq!(config.level("no-exit"), Level::Error);
+        assert!(config.set("no-such-rule", Level::Error).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::Path;
+use serde_json::Value;
+use crate::qmk::format::Grid;
+use crate::qmk::info::InfoKey;
+
+// Rows of a KLE layout, from a file downloaded from keyboard-layout-editor.com or from the
+// content of its "Raw data" tab, which has no outer brackets and unquoted property names
+fn rows(data: &str) -> Result<Vec<Value>, &'static str> {
+    if let Ok(Value::Array(rows)) = serde_json::from_str(data) {
+        return Ok(rows);
+    }
+    match serde_hjson::from_str::<Value>(&format!("[{data}]")) {
+        Ok(Value::Array(rows)) => Ok(rows),
+        _ => Err("Unable to parse KLE JSON"),
+    }
+}
+
+// Keys of a KLE layout, in the order they are listed, which has to be the order of the keymap.
+// The positions are absolute, as in QMK info.json files, and the decals are skipped.
+pub fn keys_from_str(data: &str) -> Result<Vec<InfoKey>, &'static str> {
+    let mut keys = Vec::new();
+    // rotation of the current cluster of keys, x and y are reset to its origin on each change
+    let (mut r, mut rx, mut ry) = (0.0, 0.0, 0.0);
+    let (mut x, mut y) = (0.0, 0.0);
+    let (mut w, mut h, mut decal) = (1.0, 1.0, false);
+    // the first element of the layout is its metadata when it is an object
+    for row in rows(data)?.iter().filter_map(|row| row.as_array()) {
+        for item in row {
+            match item {
+                Value::Object(properties) => {
+                    let number = |name: &str| properties.get(name).and_then(|v| v.as_f64());
+                    if let Some(angle) = number("r") {
+                        r = angle;
+                    }
+                    if number("rx").is_some() || number("ry").is_some() {
+                        rx = number("rx").unwrap_or(rx);
+                        ry = number("ry").unwrap_or(ry);
+                        (x, y) = (rx, ry);
+                    }
+                    x += number("x").unwrap_or(0.0);
+                    y += number("y").unwrap_or(0.0);
+                    w = number("w").unwrap_or(w);
+                    h = number("h").unwrap_or(h);
+                    decal = properties.get("d").and_then(|d| d.as_bool()).unwrap_or(decal);
+                }
+                Value::String(_) => {
+                    if !decal {
+                        keys.push(InfoKey { x, y, w, h, r, rx, ry });
+                    }
+                    x += w;
+                    (w, h, decal) = (1.0, 1.0, false);
+                }
+                _ => return Err("unexpected value in a KLE row"),
+            }
+        }
+        y += 1.0;
+        x = rx;
+    }
+    if keys.is_empty() {
+        return Err("no keys in the KLE layout");
+    }
+    Ok(keys)
+}
+
+pub fn keys_from_file(path: &Path) -> Result<Vec<InfoKey>, &'static str> {
+    let data = fs::read_to_string(path).map_err(|_| "Unable to read file")?;
+    keys_from_str(&data)
+}
+
+// Number of rows to split the keymap layers in: the rows of the layout when all of them have
+// the same number of keys, else the largest number of rows which splits the keys evenly, up to
+// the number of rows of the layout
+pub fn num_rows(keys: &[InfoKey]) -> usize {
+    let rows = Grid::from_info_keys(keys).num_rows();
+    (1..=rows).rev().find(|n| keys.len().is_multiple_of(*n)).unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(keys: &[InfoKey]) -> Vec<(f64, f64, f64, f64, f64)> {
+        keys.iter().map(|k| (k.x, k.y, k.w, k.h, k.r)).collect()
+    }
+
+    #[test]
+    fn downloaded_layouts_are_parsed() {
+        let data = r#"[
+            {"name": "test"},
+            ["Esc", {"w": 1.5}, "Tab", {"x": 0.5, "d": true}, "decal", "Q"],
+            [{"y": 0.25, "h": 2}, "Enter", "A"],
+            [{"r": 15, "rx": 4, "ry": 3}, "Thumb", {"y": 1}, "Space"]
+        ]"#;
+        let keys = keys_from_str(data).unwrap();
+        assert_eq!(positions(&keys), [
+            (0.0, 0.0, 1.0, 1.0, 0.0),
+            (1.0, 0.0, 1.5, 1.0, 0.0),
+            (4.0, 0.0, 1.0, 1.0, 0.0),
+            (0.0, 1.25, 1.0, 2.0, 0.0),
+            (1.0, 1.25, 1.0, 1.0, 0.0),
+            (4.0, 3.0, 1.0, 1.0, 15.0),
+            (5.0, 4.0, 1.0, 1.0, 15.0),
+        ]);
+        assert_eq!((keys[6].rx, keys[6].ry), (4.0, 3.0));
+    }
+
+    #[test]
+    fn raw_data_is_parsed() {
+        let keys = keys_from_str("[\"Q\",\"W\",\"E\"],\n[{x:0.5},\"A\",\"S\",\"D\"]").unwrap();
+        assert_eq!(positions(&keys)[3], (0.5, 1.0, 1.0, 1.0, 0.0));
+        assert_eq!(num_rows(&keys), 2);
+        assert_eq!(keys_from_str("[]").err(), Some("no keys in the KLE layout"));
+    }
+}
@@ -0,0 +1,193 @@
+use serde_json::{json, Map, Value};
+use crate::keymap::labels::Labels;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{Key, Keymap, Unsupported};
+use crate::qmk::info::InfoKey;
+
+// Keys of a grid of `num_rows` rows of `num_columns` 1u keys
+pub fn grid_keys(num_rows: usize, num_columns: usize) -> Vec<InfoKey> {
+    (0..num_rows * num_columns)
+        .map(|i| InfoKey {
+            x: (i % num_columns) as f64,
+            y: (i / num_columns) as f64,
+            w: 1.0,
+            h: 1.0,
+            r: 0.0,
+            rx: 0.0,
+            ry: 0.0,
+        })
+        .collect()
+}
+
+// KLE legends are HTML
+fn escape(legend: &str) -> String {
+    legend.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Tap legend of the key, with the names of the tap dances, macros and custom keycodes resolved
+fn tap_legend(keymap: &Keymap, key: &Key, labels: &Labels) -> String {
+    match key {
+        Key::TapDance(name) => match keymap.tap_dances.get(name).and_then(|td| td.tap.as_ref()) {
+            Some(tap) => tap.legend(labels),
+            None => key.legend(labels),
+        },
+        Key::Macro(idx) => keymap.macros.get(*idx).map_or(key.legend(labels), |m| m.legend(labels)),
+        Key::Custom(name) => keymap.custom_keycodes.get(name).map_or(key.legend(labels), |c| c.legend(labels)),
+        _ => key.legend(labels),
+    }
+}
+
+// KLE legend string of the key: the tap legend in the top left corner and the hold one in the
+// bottom right corner, the positions are separated by newlines
+fn legends(keymap: &Keymap, key: &Key, labels: &Labels) -> String {
+    let tap = escape(&tap_legend(keymap, key, labels));
+    match key.hold_legend() {
+        Some(hold) => format!("{tap}\n\n\n{}", escape(&hold)),
+        None => tap,
+    }
+}
+
+// Writes KLE rows of keys, the positions being relative to the previous key as in KLE. The
+// rotation can only change at the start of a row, a new row is started when it does.
+struct Rows {
+    rows: Vec<Vec<Value>>,
+    x: f64,
+    y: f64,
+    // rotation of the current row (r, rx, ry)
+    rotation: (f64, f64, f64),
+}
+
+impl Rows {
+    fn new_row(&mut self) {
+        if self.rows.last().is_some_and(|row| row.is_empty()) {
+            return;
+        }
+        if !self.rows.is_empty() {
+            self.y += 1.0;
+        }
+        self.x = self.rotation.1;
+        self.rows.push(Vec::new());
+    }
+
+    fn push(&mut self, key: &InfoKey, legend: String, decal: bool) {
+        let mut properties = Map::new();
+        let rotation = (key.r, key.rx, key.ry);
+        if rotation != self.rotation {
+            self.new_row();
+            self.rotation = rotation;
+            (self.x, self.y) = (key.rx, key.ry);
+            properties.insert(String::from("r"), json!(key.r));
+            properties.insert(String::from("rx"), json!(key.rx));
+            properties.insert(String::from("ry"), json!(key.ry));
+        }
+        let row = self.rows.last_mut().unwrap();
+        for (name, value, default) in [("x", key.x - self.x, 0.0), ("y", key.y - self.y, 0.0), ("w", key.w, 1.0), ("h", key.h, 1.0)] {
+            if value != default {
+                properties.insert(String::from(name), json!(value));
+            }
+        }
+        if decal {
+            properties.insert(String::from("d"), json!(true));
+        }
+        if !properties.is_empty() {
+            row.push(Value::Object(properties));
+        }
+        row.push(json!(legend));
+        (self.x, self.y) = (key.x + key.w, key.y);
+    }
+}
+
+// Writes the layers of the keymap as a KLE layout, one below the other with the layer name above
+// each of them. The keys are placed following `keys`, which are in keymap order.
+pub fn keymap_to_kle(keymap: &Keymap, layer_names: &LayerNames, labels: &Labels, keys: &[InfoKey]) -> (String, Vec<Unsupported>) {
+    let mut unsupported = Vec::new();
+    let height = keys.iter().map(|key| key.y + key.h).fold(0.0, f64::max);
+    let mut rows = Rows {
+        rows: vec![Vec::new()],
+        x: 0.0,
+        y: 0.0,
+        rotation: (0.0, 0.0, 0.0),
+    };
+    for (l, layer) in keymap.layers.iter().enumerate() {
+        let top = l as f64 * (height + 1.5);
+        if l > 0 {
+            rows.new_row();
+        }
+        let title = InfoKey { x: 0.0, y: top, w: 3.0, h: 1.0, r: 0.0, rx: 0.0, ry: 0.0 };
+        rows.push(&title, escape(&layer_names.get(l)), true);
+        rows.new_row();
+        let mut previous: Option<&InfoKey> = None;
+        for (key, info) in layer.iter().zip(keys) {
+            // the keys on the left of the previous one start a new row
+            if previous.is_some_and(|prev| info.x <= prev.x || info.r != prev.r) {
+                rows.new_row();
+            }
+            let info_key = InfoKey {
+                y: info.y + top + 1.0,
+                ry: if info.r != 0.0 { info.ry + top + 1.0 } else { info.ry },
+                ..info.clone()
+            };
+            rows.push(&info_key, legends(keymap, key, labels), false);
+            previous = Some(info);
+        }
+        if layer.len() > keys.len() {
+            let message = format!("{} keys beyond the layout are not exported", layer.len() - keys.len());
+            unsupported.push(Unsupported { layer: Some(l), index: None, message });
+        }
+    }
+    let rows: Vec<String> = rows.rows.iter().map(|row| Value::Array(row.to_vec()).to_string()).collect();
+    (format!("[\n{}\n]\n", rows.join(",\n")), unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kle::parser::keys_from_str;
+    use crate::qmk::parser;
+
+    fn positions(keys: &[InfoKey]) -> Vec<(f64, f64, f64, f64, f64, f64, f64)> {
+        keys.iter().map(|k| (k.x, k.y, k.w, k.h, k.r, k.rx, k.ry)).collect()
+    }
+
+    #[test]
+    fn layers_are_written_with_their_names() {
+        let layer_names = LayerNames::new(vec![String::from("Base"), String::from("Lower")]);
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "", "layers": [["KC_A", "LT(_LOWER, KC_B)"], ["KC_TRNS", "KC_LT"]]}"#;
+        let keymap = parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        let (kle, unsupported) = keymap_to_kle(&keymap, &layer_names, &Labels::new(), &grid_keys(1, 2));
+        assert!(unsupported.is_empty());
+        let expected = concat!(
+            "[\n",
+            "[{\"d\":true,\"w\":3.0},\"Base\"],\n",
+            "[\"A\",\"B\\n\\n\\nLower\"],\n",
+            "[{\"d\":true,\"w\":3.0,\"y\":0.5},\"Lower\"],\n",
+            "[\"▽\",\"&lt;\"]\n",
+            "]\n",
+        );
+        assert_eq!(kle, expected);
+    }
+
+    #[test]
+    fn layouts_round_trip() {
+        let layout = r#"[
+            ["Esc", {"w": 1.5}, "Tab", {"x": 0.5}, "Q"],
+            [{"y": 0.25, "h": 2}, "Enter", "A"],
+            [{"r": 15, "rx": 4, "ry": 3}, "Thumb", {"y": 1}, "Space"]
+        ]"#;
+        let keys = keys_from_str(layout).unwrap();
+        let layer_names = LayerNames::new(vec![String::from("Base")]);
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "", "layers": [["KC_1", "KC_2", "KC_3", "KC_4", "KC_5", "KC_6", "KC_7"]]}"#;
+        let keymap = parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        let (kle, _) = keymap_to_kle(&keymap, &layer_names, &Labels::new(), &keys);
+        // the layer is written below its name
+        let expected: Vec<InfoKey> = keys
+            .iter()
+            .map(|k| InfoKey {
+                y: k.y + 1.0,
+                ry: if k.r != 0.0 { k.ry + 1.0 } else { k.ry },
+                ..k.clone()
+            })
+            .collect();
+        assert_eq!(positions(&keys_from_str(&kle).unwrap()), positions(&expected));
+    }
+}
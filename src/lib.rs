@@ -1,9 +1,27 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::keymap::diff::Change;
+use crate::keymap::edit::{self, Operation};
+use crate::keymap::lint::{Level, LintConfig, Violation};
+use crate::qmk::convert::Conversion;
+use crate::qmk::diagnostics::{Diagnostic, KeycodeDatabase};
+use crate::qmk::info::InfoKey;
+use crate::keymap::labels::Labels;
 use crate::keymap::layer::LayerNames;
+use crate::keymap::Keymap;
+use crate::qmk::keymap_c::UnicodeMap;
+use crate::qmk::keymap_extras::Locale;
+use crate::qmk::numeric::{self, KeycodeTable};
 
+pub mod git;
+pub mod kanata;
+pub mod keyberon;
 pub mod keymap;
+pub mod kle;
 pub mod qmk;
+pub mod rmk;
+pub mod zmk;
 
 // This struct stores keyboard specific knowlegde which is not available from QMK
 // JSON. These parameters are currently hardcoded in the source code, this struct
@@ -13,11 +31,26 @@ pub struct CustomConfig {
     // FIXME: not sure how to use this is layers.rs yet
     pub aliases: HashMap<String, String>,
     pub layer_names: Vec<&'static str>,
+    // keycode -> label, these take precedence over the builtin labels
+    pub labels: HashMap<String, String>,
 }
 
 pub struct Config {
     src_json: PathBuf,
     dest_yaml: PathBuf,
+    // path to a qmk_firmware checkout, used to get the keycode labels
+    pub qmk_src_path: Option<PathBuf>,
+    // keymap.c matching the QMK JSON keymap, for the features which are not in the JSON file
+    pub keymap_c: Option<PathBuf>,
+    // input language of the OS the keyboard is used with, see `qmk::keymap_extras::Locale::load`
+    pub host_locale: Option<String>,
+    // git revision the keymap files are read at, the working tree files are used if not set
+    pub revision: Option<String>,
+    // JSON file the keycode diagnostics are written to, they are only printed on stderr if not set
+    pub diagnostics: Option<PathBuf>,
+    // KLE JSON file with the physical layout of the keyboard, its rows are used to split the
+    // layers instead of `num_rows`
+    pub kle: Option<PathBuf>,
     pub custom: CustomConfig,
 }
 
@@ -26,30 +59,705 @@ impl Config {
         Config {
             src_json,
             dest_yaml,
+            qmk_src_path: None,
+            keymap_c: None,
+            host_locale: None,
+            revision: None,
+            diagnostics: None,
+            kle: None,
             custom: CustomConfig {
                 num_rows,
                 aliases: HashMap::new(),
                 layer_names: Vec::new(),
+                labels: HashMap::new(),
             }
         }
     }
 }
 
-pub fn run(config: Config) -> Result<(), &'static str> {
+// Reads `path`, from the git repository if a revision is set in `config`
+fn read_source(config: &Config, path: &Path) -> Result<String, &'static str> {
+    match &config.revision {
+        Some(revision) => git::show(revision, path),
+        None => fs::read_to_string(path).map_err(|_| "Unable to read file"),
+    }
+}
+
+// Parses a QMK JSON keymap, a Vial .vil file or VIA backup, a ZMK .keymap file, a Kanata config,
+// an RMK keyboard.toml or the `layout!` macro of a Keyberon firmware. The layer names of the
+// keymaps which don't come from QMK are given by their importer, the QMK ones keep `layer_names`.
+fn parse_keymap(config: &Config, data: &str, layer_names: LayerNames) -> Result<(Keymap, LayerNames), &'static str> {
+    if data.contains("zmk,keymap") {
+        return zmk::parser::keymap_from_str(data, config.custom.num_rows);
+    }
+    if data.contains("(defsrc") {
+        return kanata::parser::keymap_from_str(data);
+    }
+    if data.contains("layout!") {
+        return keyberon::parser::keymap_from_str(data);
+    }
+    if data.contains("[layout]") || data.contains("[[layer]]") {
+        return rmk::parser::keymap_from_str(data);
+    }
+    if qmk::vial::is_vial(data) {
+        let table = keycode_table(config, None)?;
+        let keymap = qmk::vial::keymap_from_str(data, config.custom.num_rows, &layer_names, &table)?;
+        return Ok((keymap, layer_names));
+    }
+    let keymap = qmk::parser::keymap_from_str(data, config.custom.num_rows, &layer_names)?;
+    Ok((keymap, layer_names))
+}
+
+fn load_keymap(config: &Config) -> Result<(Keymap, LayerNames), &'static str> {
     // FIXME: might make more sense to not split the keys per rows during parsing, but to only do
     // it during rendering
     let layer_names = LayerNames::new(config.custom.layer_names.iter().map(|x| x.to_string()).collect());
-    let keymap = qmk::parser::keymap_from_file(&config.src_json, config.custom.num_rows, &layer_names);
+    let keymap = parse_keymap(config, &read_source(config, &config.src_json)?, layer_names);
     if keymap.is_err() {
         return Err("could not read keymap from file");
     }
-    let mut keymap = keymap.unwrap();
+    let (mut keymap, layer_names) = keymap.unwrap();
+    let mut combo_layer = None;
+    if let Some(path) = &config.keymap_c {
+        let data = read_source(config, path)?;
+        keymap.tap_dances = qmk::keymap_c::tap_dances(&data, &layer_names);
+        keymap.set_custom_keycodes(qmk::keymap_c::custom_keycodes(&data, &layer_names));
+        let names: Vec<String> = (0..keymap.layers.len()).map(|i| layer_names.get(i)).collect();
+        let (combos, only_from_layer) = qmk::keymap_c::combos(&data, &layer_names, &names);
+        keymap.key_overrides = qmk::keymap_c::key_overrides(&data, &layer_names, &names);
+        keymap.combos.extend(combos);
+        let encoders = qmk::keymap_c::encoders(&data, &layer_names, &names);
+        if encoders.iter().any(|e| !e.is_empty()) {
+            keymap.encoders = encoders;
+        }
+        combo_layer = only_from_layer.and_then(|layer| match layer.parse::<usize>() {
+            Ok(idx) => Some(idx),
+            Err(_) => layer_names.pretty_name(&layer).and_then(|name| layer_names.index(&name)),
+        });
+    }
+    for combo in keymap.locate_combos(combo_layer.unwrap_or(0)) {
+        eprintln!("combo {combo}: keys not found in the keymap");
+    }
+    Ok((keymap, layer_names))
+}
+
+// Keys of the KLE layout of `config`, the number of rows of the keymap is set from them
+fn load_kle(config: &mut Config) -> Result<Option<Vec<InfoKey>>, &'static str> {
+    let Some(path) = &config.kle else {
+        return Ok(None);
+    };
+    let keys = kle::parser::keys_from_file(path)?;
+    config.custom.num_rows = kle::parser::num_rows(&keys);
+    Ok(Some(keys))
+}
+
+fn load_locale(config: &Config) -> Result<Locale, &'static str> {
+    match &config.host_locale {
+        Some(host_locale) => Locale::load(host_locale, config.qmk_src_path.as_deref()),
+        None => Ok(Locale::us()),
+    }
+}
+
+fn load_unicode_map(config: &Config) -> Result<UnicodeMap, &'static str> {
+    match &config.keymap_c {
+        Some(path) => Ok(UnicodeMap::from_keymap_c(&read_source(config, path)?)),
+        None => Ok(UnicodeMap::new()),
+    }
+}
+
+fn diagnostics_to_json(diagnostics: &[Diagnostic], keymap: &Keymap, layer_names: &LayerNames, file: &Path) -> serde_json::Value {
+    let diagnostics: Vec<serde_json::Value> = diagnostics
+        .iter()
+        .map(|d| {
+            serde_json::json!({
+                "file": file.to_string_lossy(),
+                "layer": layer_names.get(d.layer),
+                "index": d.index,
+                "row": d.index / keymap.num_columns + 1,
+                "column": d.index % keymap.num_columns + 1,
+                "keycode": d.keycode,
+                "problem": d.problem.name(),
+                "suggestions": d.suggestions,
+            })
+        })
+        .collect();
+    serde_json::Value::Array(diagnostics)
+}
+
+// Checks the keycodes of the keymap, against all the QMK keycodes if the QMK source tree is set.
+// The diagnostics are printed on stderr, and written to the diagnostics file of `config`.
+// Keycodes of the QMK source tree if set, else the builtin ones
+fn keycode_database(config: &Config) -> KeycodeDatabase {
+    match &config.qmk_src_path {
+        Some(path) => KeycodeDatabase::from_qmk_src(path),
+        None => KeycodeDatabase::builtin(),
+    }
+}
+
+fn report_diagnostics(config: &Config, keymap: &Keymap, layer_names: &LayerNames) -> Result<(), &'static str> {
+    let diagnostics = qmk::diagnostics::diagnose(keymap, &keycode_database(config));
+    for d in &diagnostics {
+        eprintln!(
+            "warning: {}: {}, {}: {}",
+            config.src_json.display(),
+            layer_names.get(d.layer),
+            position(keymap, d.index),
+            d.message(),
+        );
+    }
+    if let Some(path) = &config.diagnostics {
+        let json = diagnostics_to_json(&diagnostics, keymap, layer_names, &config.src_json);
+        fs::write(path, format!("{json:#}\n")).map_err(|_| "Unable to write file")?;
+    }
+    Ok(())
+}
+
+pub fn run(mut config: Config) -> Result<(), &'static str> {
+    let kle_keys = load_kle(&mut config)?;
+    let (mut keymap, layer_names) = load_keymap(&config)?;
+    report_diagnostics(&config, &keymap, &layer_names)?;
+    // the KLE layout is drawn from an info.json written next to the YAML file
+    if let Some(keys) = kle_keys {
+        if keys.len() != keymap.layers.first().map_or(0, |layer| layer.len()) {
+            return Err("the KLE layout does not have the same number of keys as the keymap");
+        }
+        let path = config.dest_yaml.with_extension("info.json");
+        fs::write(&path, qmk::info::info_json(&keys, "LAYOUT")).map_err(|_| "Unable to write file")?;
+        keymap.info_json = Some(path);
+    }
     //println!("keyboard: {}", keymap.keyboard);
     //println!("keymap: {}", keymap.keymap);
     //println!("layout: {}", keymap.layout);
     if let Some(alias) = config.custom.aliases.get(&keymap.layout) {
         keymap.layout = String::from(alias);
     }
+    let spec_labels = match &config.qmk_src_path {
+        Some(path) => qmk::constants::labels(path),
+        None => HashMap::new(),
+    };
+    let mut labels = Labels::new();
+    labels.add_spec_labels(spec_labels);
+    if config.host_locale.is_some() {
+        labels.add_locale(&load_locale(&config)?);
+    }
+    labels.add_overrides(&config.custom.labels);
     //keymap.to_yaml().expect("conversion to yaml failed");
-    keymap.to_file(&config.dest_yaml, &layer_names)
+    keymap.to_file(&config.dest_yaml, &layer_names, &labels)
+}
+
+// Prints all the ways to type `query`, which is either a character or a keycode
+pub fn find(config: Config, query: &str) -> Result<(), &'static str> {
+    let (keymap, layer_names) = load_keymap(&config)?;
+    let locale = load_locale(&config)?;
+    let unicode = load_unicode_map(&config)?;
+
+    let matches = keymap::find::find(&keymap, &layer_names, query, &locale, &unicode);
+    if matches.is_empty() {
+        println!("{query}: not found");
+        return Ok(());
+    }
+    println!("{query}:");
+    for m in matches {
+        let mut description = format!(
+            "  {}, row {}, column {}{}",
+            layer_names.get(m.position.layer),
+            m.position.row(&keymap) + 1,
+            m.position.column(&keymap) + 1,
+            if m.hold { " (hold)" } else { " (tap)" },
+        );
+        for layer_key in &m.layer_keys {
+            description.push_str(&format!(
+                ", {} the key at {}, row {}, column {}",
+                layer_key.action,
+                layer_names.get(layer_key.position.layer),
+                layer_key.position.row(&keymap) + 1,
+                layer_key.position.column(&keymap) + 1,
+            ));
+        }
+        if !m.reachable {
+            description.push_str(", layer not reachable with layer keys");
+        }
+        if !m.modifiers.is_empty() {
+            description.push_str(&format!(", with {}", m.modifiers.join("+")));
+        }
+        println!("{description}");
+    }
+    Ok(())
+}
+
+// Prints which of `chars` can be typed with the keymap, and how many key presses they need
+pub fn coverage(config: Config, chars: &[char]) -> Result<(), &'static str> {
+    let (keymap, layer_names) = load_keymap(&config)?;
+    let locale = load_locale(&config)?;
+    let unicode = load_unicode_map(&config)?;
+
+    let coverage = keymap::coverage::coverage(&keymap, &layer_names, chars, &locale, &unicode);
+    let mut missing = Vec::new();
+    for c in &coverage {
+        let (Some(m), Some(presses)) = (&c.best, c.presses()) else {
+            missing.push(c.character);
+            continue;
+        };
+        let mut description = format!("{:?}: {presses} key(s), {}", c.character, layer_names.get(m.position.layer));
+        if !m.modifiers.is_empty() {
+            description.push_str(&format!(" with {}", m.modifiers.join("+")));
+        }
+        println!("{description}");
+    }
+    println!();
+    println!("{}/{} characters can be typed", coverage.len() - missing.len(), coverage.len());
+    if !missing.is_empty() {
+        println!("missing: {}", missing.iter().collect::<String>());
+    }
+    Ok(())
+}
+
+// Applies `operation` to the QMK JSON keymap and writes the result to `output`. keymap.c is not
+// read, so that only the content of the JSON file is written back.
+pub fn edit(config: Config, operation: Operation, output: &Path) -> Result<(), &'static str> {
+    let mut layer_names = LayerNames::new(config.custom.layer_names.iter().map(|x| x.to_string()).collect());
+    let mut keymap = qmk::parser::keymap_from_file(&config.src_json, config.custom.num_rows, &layer_names)?;
+    // the layer constants of keymap.c which no longer match the layers of the JSON file
+    let reorder = |identifiers: Vec<String>| {
+        if !identifiers.is_empty() {
+            eprintln!("warning: {} changed number, reorder the layer enum of keymap.c the same way", identifiers.join(", "));
+        }
+    };
+    match operation {
+        Operation::SwapKeys(a, b) => edit::swap_keys(&mut keymap, a, b)?,
+        Operation::MoveLayer { layer, to } => reorder(edit::move_layer(&mut keymap, &mut layer_names, &layer, to)?),
+        Operation::DuplicateLayer { layer, name } => reorder(edit::duplicate_layer(&mut keymap, &mut layer_names, &layer, &name)?),
+        Operation::DeleteLayer(layer) => reorder(edit::delete_layer(&mut keymap, &mut layer_names, &layer)?),
+        Operation::RenameLayer { layer, name } => {
+            if let Some((old, new)) = edit::rename_layer(&mut keymap, &mut layer_names, &layer, &name)? {
+                eprintln!("warning: rename {old} to {new} in the layer enum of keymap.c");
+            }
+        }
+        Operation::ReplaceKey { from, to } => {
+            let from = qmk::parser::parse_keycode(&from, &layer_names);
+            let to = qmk::parser::parse_keycode(&to, &layer_names);
+            let count = edit::replace_key(&mut keymap, &from, &to);
+            println!("{count} key(s) replaced");
+        }
+        Operation::CopyLayer { from, layer, name } => {
+            let other = qmk::parser::keymap_from_file(&from, config.custom.num_rows, &layer_names)?;
+            // both keymaps use the layer names from the configuration
+            let other_names = layer_names.clone();
+            let missing = edit::copy_layer(&mut keymap, &mut layer_names, &other, &other_names, &layer, &name)?;
+            for layer in missing {
+                eprintln!("layer {layer} referenced by the copied layer does not exist");
+            }
+        }
+    }
+    layer_names.fill(keymap.layers.len());
+    println!("layers: {}", layer_names.names().join(", "));
+    qmk::parser::keymap_to_file(&keymap, output)
+}
+
+// Rewrites the QMK JSON keymap with its layers laid out as a grid, following the geometry of
+// `info_json` if set. With `check`, the file is left untouched and an error is returned if it is
+// not formatted.
+pub fn fmt(config: Config, info_json: Option<&Path>, check: bool) -> Result<(), &'static str> {
+    let layer_names = LayerNames::new(config.custom.layer_names.iter().map(|x| x.to_string()).collect());
+    let keymap = qmk::parser::keymap_from_file(&config.src_json, config.custom.num_rows, &layer_names)?;
+    let json = match info_json {
+        Some(info_json) => {
+            let grid = match qmk::format::Grid::from_info_json(info_json, &keymap.layout) {
+                Ok(grid) => grid,
+                Err(err) => match config.custom.aliases.get(&keymap.layout) {
+                    Some(alias) => qmk::format::Grid::from_info_json(info_json, alias)?,
+                    None => return Err(err),
+                },
+            };
+            qmk::parser::keymap_to_json_with_grid(&keymap, &grid)?
+        }
+        None => qmk::parser::keymap_to_json(&keymap)?,
+    };
+    let current = fs::read_to_string(&config.src_json).map_err(|_| "Unable to read file")?;
+    if current == json {
+        return Ok(());
+    }
+    if check {
+        eprintln!("{} is not formatted", config.src_json.display());
+        return Err("keymap is not formatted");
+    }
+    fs::write(&config.src_json, json).map_err(|_| "Unable to write file")
+}
+
+fn layout_keys(config: &Config, info_json: &Path, layout: &str) -> Result<Vec<qmk::info::InfoKey>, &'static str> {
+    match qmk::info::layout_keys(info_json, layout) {
+        Ok(keys) => Ok(keys),
+        Err(err) => match config.custom.aliases.get(layout) {
+            Some(alias) => qmk::info::layout_keys(info_json, alias),
+            None => Err(err),
+        },
+    }
+}
+
+// Converts the QMK JSON keymap to another layout macro, possibly of another keyboard, and writes
+// it to `output`. The keys which can't be placed on the new layout are reported on stderr.
+pub fn convert(config: Config, conversion: Conversion, output: &Path) -> Result<(), &'static str> {
+    let layer_names = LayerNames::new(config.custom.layer_names.iter().map(|x| x.to_string()).collect());
+    let mut keymap = qmk::parser::keymap_from_file(&config.src_json, config.custom.num_rows, &layer_names)?;
+    let to_keys = match &conversion.to_info {
+        Some(to_info) => Some(qmk::info::layout_keys(to_info, &conversion.layout)?),
+        None => None,
+    };
+    let mapping = match (&conversion.mapping, &conversion.from_info, &to_keys) {
+        (Some(mapping), _, _) => qmk::convert::mapping_from_file(mapping)?,
+        (None, Some(from_info), Some(to_keys)) => {
+            let from_keys = layout_keys(&config, from_info, &keymap.layout)?;
+            if from_keys.len() != keymap.num_rows * keymap.num_columns {
+                return Err("the source layout does not have the same number of keys as the keymap");
+            }
+            qmk::convert::geometry_mapping(&from_keys, to_keys)
+        }
+        _ => return Err("a mapping file or the info.json files of both keyboards are needed"),
+    };
+    let num_columns = keymap.num_columns;
+    let keyboard = conversion.keyboard.unwrap_or(keymap.keyboard.to_string());
+    let dropped = qmk::convert::convert(&mut keymap, &mapping, &keyboard, &conversion.layout)?;
+    for key in &dropped {
+        eprintln!(
+            "{}, row {}, column {}: {} dropped",
+            layer_names.get(key.layer),
+            key.index / num_columns + 1,
+            key.index % num_columns + 1,
+            qmk::parser::keycode(&key.key),
+        );
+    }
+    let grid = match &to_keys {
+        Some(to_keys) => qmk::format::Grid::from_info_keys(to_keys),
+        None => qmk::format::Grid::from_columns(mapping.len(), num_columns),
+    };
+    let json = qmk::parser::keymap_to_json_with_grid(&keymap, &grid)?;
+    fs::write(output, json).map_err(|_| "Unable to write file")
+}
+
+// Reads a keymap file, or `REVISION:FILE` to read the file from its git repository, with its own
+// layer names when its format has them, see `parse_keymap`
+fn load_keymap_source(config: &Config, source: &str) -> Result<(Keymap, LayerNames), &'static str> {
+    let layer_names = LayerNames::new(config.custom.layer_names.iter().map(|x| x.to_string()).collect());
+    let path = Path::new(source);
+    let data = if path.exists() {
+        fs::read_to_string(path).map_err(|_| "Unable to read file")?
+    } else {
+        let Some((revision, file)) = source.split_once(':') else {
+            return Err("Unable to read file");
+        };
+        git::show(revision, Path::new(file))?
+    };
+    parse_keymap(config, &data, layer_names)
+}
+
+fn position(keymap: &Keymap, index: usize) -> String {
+    format!("row {}, column {}", index / keymap.num_columns + 1, index % keymap.num_columns + 1)
+}
+
+// Describes `change`, `old` and `new` are the compared keymaps, the layers are named with the names
+// of the keymap they are in
+pub fn describe_change(change: &Change, old: (&Keymap, &LayerNames), new: (&Keymap, &LayerNames)) -> String {
+    let keycode = qmk::parser::keycode;
+    let ((old, old_names), (new, layer_names)) = (old, new);
+    match change {
+        Change::LayerAdded { layer } => format!("layer {} added", layer_names.get(*layer)),
+        Change::LayerRemoved { layer } => format!("layer {} removed", old_names.get(*layer)),
+        Change::LayerMoved { from, to } => format!("layer {} moved from position {} to {}", layer_names.get(*to), from + 1, to + 1),
+        Change::LayerRenamed { layer, old_name } => format!("layer {old_name} renamed to {}", layer_names.get(*layer)),
+        Change::KeyChanged { layer, index, old: old_key, new: new_key } => format!(
+            "{}, {}: {} -> {}",
+            layer_names.get(*layer),
+            position(new, *index),
+            keycode(old_key),
+            keycode(new_key),
+        ),
+        Change::HoldChanged { layer, index, old: old_key, new: new_key } => format!(
+            "{}, {}: hold changed, {} -> {}",
+            layer_names.get(*layer),
+            position(new, *index),
+            keycode(old_key),
+            keycode(new_key),
+        ),
+        Change::KeyMoved { layer, from, to, key } => format!(
+            "{}: {} moved from {} to {}",
+            layer_names.get(*layer),
+            keycode(key),
+            position(old, *from),
+            position(new, *to),
+        ),
+    }
+}
+
+// Prints the differences between the `old` and `new` keymaps, files or `REVISION:FILE`. Each
+// keymap is read with its own layer names when its format has them, so that renamed layers are
+// reported. With `visual`, the new keymap is also rendered with its changed keys highlighted.
+pub fn diff(config: Config, old: &str, new: &str, visual: Option<&Path>) -> Result<(), &'static str> {
+    let (old_keymap, old_names) = load_keymap_source(&config, old)?;
+    let (new_keymap, layer_names) = load_keymap_source(&config, new)?;
+    let changes = keymap::diff::diff(&old_keymap, &old_names, &new_keymap, &layer_names);
+    if changes.is_empty() {
+        println!("no changes");
+    }
+    for change in &changes {
+        println!("{}", describe_change(change, (&old_keymap, &old_names), (&new_keymap, &layer_names)));
+    }
+    if let Some(visual) = visual {
+        let highlighted: Vec<(usize, usize)> = changes.iter().filter_map(|c| c.position()).collect();
+        let mut labels = Labels::new();
+        labels.add_overrides(&config.custom.labels);
+        let yaml = new_keymap.to_yaml_highlighted(&layer_names, &labels, &highlighted);
+        fs::write(visual, yaml).map_err(|_| "Unable to write file")?;
+    }
+    Ok(())
+}
+
+// Prints the keymap changes made by each commit of `range` (`v1.0..HEAD`, ...) in the git
+// repository of the keymap file, which is read as in `diff`
+pub fn changelog(config: Config, range: &str) -> Result<(), &'static str> {
+    let load = |revision: &str| {
+        let data = git::show(revision, &config.src_json)?;
+        let layer_names = LayerNames::new(config.custom.layer_names.iter().map(|x| x.to_string()).collect());
+        parse_keymap(&config, &data, layer_names)
+    };
+    for commit in git::log(range, &config.src_json)? {
+        println!("{} {}", &commit.hash[..commit.hash.len().min(8)], commit.subject);
+        let (new, new_names) = match load(&commit.hash) {
+            Ok(keymap) => keymap,
+            Err(err) => {
+                println!("  {err}");
+                continue;
+            }
+        };
+        let Ok((old, old_names)) = load(&format!("{}^", commit.hash)) else {
+            println!("  keymap added");
+            continue;
+        };
+        let changes = keymap::diff::diff(&old, &old_names, &new, &new_names);
+        if changes.is_empty() {
+            println!("  no keymap changes");
+        }
+        for change in &changes {
+            println!("  - {}", describe_change(change, (&old, &old_names), (&new, &new_names)));
+        }
+    }
+    Ok(())
+}
+
+fn violation_location(violation: &Violation, keymap: &Keymap, layer_names: &LayerNames) -> String {
+    match (violation.layer, violation.index) {
+        (Some(layer), Some(index)) => format!("{}, {}", layer_names.get(layer), position(keymap, index)),
+        (Some(layer), None) => layer_names.get(layer),
+        _ => String::new(),
+    }
+}
+
+fn violations_to_json(violations: &[Violation], keymap: &Keymap, layer_names: &LayerNames) -> serde_json::Value {
+    let violations: Vec<serde_json::Value> = violations
+        .iter()
+        .map(|v| {
+            serde_json::json!({
+                "rule": v.rule,
+                "level": v.level.name(),
+                "layer": v.layer.map(|layer| layer_names.get(layer)),
+                "row": v.index.map(|index| index / keymap.num_columns + 1),
+                "column": v.index.map(|index| index % keymap.num_columns + 1),
+                "message": v.message,
+            })
+        })
+        .collect();
+    serde_json::Value::Array(violations)
+}
+
+// SARIF 2.1.0 log, the format used by CI code scanning tools
+fn violations_to_sarif(violations: &[Violation], keymap: &Keymap, layer_names: &LayerNames, file: &Path) -> serde_json::Value {
+    let rules: Vec<serde_json::Value> = keymap::lint::RULES
+        .iter()
+        .map(|(name, _, description)| serde_json::json!({"id": name, "shortDescription": {"text": description}}))
+        .collect();
+    let results: Vec<serde_json::Value> = violations
+        .iter()
+        .map(|v| {
+            serde_json::json!({
+                "ruleId": v.rule,
+                "level": v.level.name(),
+                "message": {"text": v.message},
+                "locations": [{
+                    "physicalLocation": {"artifactLocation": {"uri": file.to_string_lossy()}},
+                    "logicalLocations": [{"name": violation_location(v, keymap, layer_names)}],
+                }],
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": {"driver": {"name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION"), "rules": rules}},
+            "results": results,
+        }],
+    })
+}
+
+// Runs the lint rules over the keymap and prints the violations as `text`, `json` or `sarif`.
+// An error is returned when error level violations are found.
+pub fn lint(config: Config, lint_config: &LintConfig, format: &str) -> Result<(), &'static str> {
+    let (keymap, layer_names) = load_keymap(&config)?;
+    let violations = keymap::lint::lint(&keymap, &layer_names, lint_config, &keycode_database(&config));
+    match format {
+        "text" => {
+            for v in &violations {
+                let location = violation_location(v, &keymap, &layer_names);
+                println!("{}: {location}: {} [{}]", v.level.name(), v.message, v.rule);
+            }
+            println!("{} problem(s) found", violations.len());
+        }
+        "json" => println!("{:#}", violations_to_json(&violations, &keymap, &layer_names)),
+        "sarif" => println!("{:#}", violations_to_sarif(&violations, &keymap, &layer_names, &config.src_json)),
+        _ => return Err("unknown output format"),
+    }
+    if violations.iter().any(|v| v.level == Level::Error) {
+        return Err("lint errors found");
+    }
+    Ok(())
+}
+
+fn keycode_table(config: &Config, version: Option<&str>) -> Result<KeycodeTable, &'static str> {
+    match (&config.qmk_src_path, version) {
+        (Some(path), version) => KeycodeTable::from_qmk_src(path, version.unwrap_or(qmk::constants::VERSIONS[qmk::constants::VERSIONS.len() - 1])),
+        (None, None) => Ok(KeycodeTable::builtin()),
+        (None, Some(_)) => Err("the keycode spec versions are read from the QMK source tree"),
+    }
+}
+
+// Converts keycodes to the 16-bit values of the keycode spec `version` (the latest one if not
+// set) and back. Without the QMK source tree, only the basic keycodes have a name.
+pub fn keycodes(config: Config, version: Option<&str>, operation: numeric::Operation) -> Result<(), &'static str> {
+    let layer_names = LayerNames::new(config.custom.layer_names.iter().map(|x| x.to_string()).collect());
+    let table = keycode_table(&config, version)?;
+    match operation {
+        numeric::Operation::Encode(keycodes) => {
+            for keycode in keycodes {
+                match table.encode_str(&keycode, &layer_names) {
+                    Ok(value) => println!("{keycode}: 0x{value:04X}"),
+                    Err(err) => println!("{keycode}: {err}"),
+                }
+            }
+        }
+        numeric::Operation::Decode(values) => {
+            for value in values {
+                let number = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+                    Some(hex) => u16::from_str_radix(hex, 16),
+                    None => value.parse::<u16>(),
+                };
+                match number {
+                    Ok(number) => println!("{value}: {}", table.decode_str(number, &layer_names)),
+                    Err(_) => println!("{value}: not a 16-bit value"),
+                }
+            }
+        }
+        numeric::Operation::EncodeKeymap => {
+            let keymap = qmk::parser::keymap_from_file(&config.src_json, config.custom.num_rows, &layer_names)?;
+            let mut layers = Vec::new();
+            for (l, layer) in keymap.layers.iter().enumerate() {
+                let mut values = Vec::new();
+                for (index, key) in layer.iter().enumerate() {
+                    match table.encode(key, &layer_names) {
+                        Ok(value) => values.push(value),
+                        Err(err) => {
+                            eprintln!("{}, {}: {}: {err}", layer_names.get(l), position(&keymap, index), qmk::parser::keycode(key));
+                            values.push(0);
+                        }
+                    }
+                }
+                layers.push(values);
+            }
+            println!("{}", serde_json::json!({ "layers": layers }));
+        }
+    }
+    Ok(())
+}
+
+// Writes the keymap to `output` in the keymap format of another firmware: `zmk`, `kanata` or
+// `rmk`, or as a `kle` layout with the legends of each layer. The keys and features which can't be
+// converted are reported on stderr.
+pub fn export(mut config: Config, format: &str, output: &Path) -> Result<(), &'static str> {
+    let kle_keys = load_kle(&mut config)?;
+    let (keymap, layer_names) = load_keymap(&config)?;
+    let (data, unsupported) = match format {
+        "zmk" => zmk::writer::keymap_to_zmk(&keymap, &layer_names),
+        "kanata" => kanata::writer::keymap_to_kanata(&keymap, &layer_names),
+        "rmk" => rmk::writer::keymap_to_rmk(&keymap, &layer_names),
+        "kle" => {
+            let mut labels = Labels::new();
+            labels.add_overrides(&config.custom.labels);
+            let keys = kle_keys.unwrap_or_else(|| kle::writer::grid_keys(keymap.num_rows, keymap.num_columns));
+            kle::writer::keymap_to_kle(&keymap, &layer_names, &labels, &keys)
+        }
+        _ => return Err("unknown export format"),
+    };
+    for u in &unsupported {
+        let location = match (u.layer, u.index) {
+            (Some(layer), Some(index)) => format!("{}, {}: ", layer_names.get(layer), position(&keymap, index)),
+            (Some(layer), None) => format!("{}: ", layer_names.get(layer)),
+            (None, Some(index)) => format!("{}: ", position(&keymap, index)),
+            _ => String::new(),
+        };
+        eprintln!("warning: {location}{}", u.message);
+    }
+    fs::write(output, data).map_err(|_| "Unable to write file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatted_keymap_is_unchanged_by_fmt() {
+        let path = std::env::temp_dir().join(format!("qzmk-drawer-fmt-{}.json", std::process::id()));
+        let keymap = r#"{"keyboard": "planck/rev6", "keymap": "test", "layout": "LAYOUT_ortho_2x3", "version": 1,
+            "layers": [["KC_TAB", "KC_Q", "KC_W", "LCTL_T(KC_ESC)", "KC_A", "KC_S"], ["KC_TRNS", "KC_1", "MO(1)", "KC_NO", "KC_2", "KC_3"]]}"#;
+        fs::write(&path, keymap).unwrap();
+        let config = || Config::new(path.clone(), PathBuf::new(), 2);
+        assert!(fmt(config(), None, true).is_err());
+        fmt(config(), None, false).unwrap();
+        let formatted = fs::read_to_string(&path).unwrap();
+        fmt(config(), None, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), formatted);
+        assert!(fmt(config(), None, true).is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+
+    // Changes between two Kanata configs, which have their own layer names
+    fn kanata_changes(old: &str, new: &str) -> Vec<String> {
+        let config = Config::new(PathBuf::new(), PathBuf::new(), 1);
+        let write = |name: &str, layers: &str| {
+            let path = std::env::temp_dir().join(format!("qzmk-drawer-diff-{name}-{}.kbd", std::process::id()));
+            fs::write(&path, format!("(defsrc a b c)\n{layers}")).unwrap();
+            path
+        };
+        let (old_path, new_path) = (write("old", old), write("new", new));
+        let (old, old_names) = load_keymap_source(&config, old_path.to_str().unwrap()).unwrap();
+        let (new, new_names) = load_keymap_source(&config, new_path.to_str().unwrap()).unwrap();
+        fs::remove_file(&old_path).unwrap();
+        fs::remove_file(&new_path).unwrap();
+        keymap::diff::diff(&old, &old_names, &new, &new_names)
+            .iter()
+            .map(|change| describe_change(change, (&old, &old_names), (&new, &new_names)))
+            .collect()
+    }
+
+    #[test]
+    fn renamed_layers_are_reported() {
+        let changes = kanata_changes("(deflayer base a b c)\n(deflayer lower 1 2 3)", "(deflayer base a b c)\n(deflayer nav 1 2 3)");
+        assert_eq!(changes, vec!["layer lower renamed to nav"]);
+    }
+
+    #[test]
+    fn moved_layers_are_reported() {
+        let changes = kanata_changes(
+            "(deflayer base a b c)\n(deflayer lower 1 2 3)\n(deflayer raise 4 5 6)",
+            "(deflayer base a b c)\n(deflayer raise 4 5 6)\n(deflayer lower 1 2 3)",
+        );
+        assert_eq!(changes, vec!["layer lower moved from position 2 to 3"]);
+    }
 }
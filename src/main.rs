@@ -1,5 +1,12 @@
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+use qzmk_drawer::keymap::coverage;
+use qzmk_drawer::keymap::edit::Operation;
+use qzmk_drawer::keymap::lint::{self, Level, LintConfig};
+use qzmk_drawer::qmk::convert::Conversion;
+use qzmk_drawer::keymap::labels;
 use qzmk_drawer::qmk::constants;
+use qzmk_drawer::qmk::numeric;
 
 use clap::{Args, Parser, Subcommand};
 
@@ -20,18 +27,313 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     Draw(DrawArgs),
+    /// Find all the ways to type a character or keycode
+    Find(FindArgs),
+    /// Report which characters of a set can be typed
+    Coverage(CoverageArgs),
+    /// Edit a QMK JSON keymap: swap keys, move, duplicate or delete layers, ...
+    Edit(EditArgs),
+    /// Format a QMK JSON keymap with its layers laid out as grids
+    Fmt(FmtArgs),
+    /// Convert a QMK JSON keymap to another layout macro or keyboard
+    Convert(ConvertArgs),
+    /// Show the differences between two QMK JSON keymaps
+    Diff(DiffArgs),
+    /// Summarize the keymap changes of each commit of a git revision range
+    Changelog(ChangelogArgs),
+    /// Check the keymap for common QMK mistakes
+    Lint(LintArgs),
+    /// Convert keycodes to the numeric values used in VIA dumps and EEPROM images, and back
+    Keycodes(KeycodesArgs),
+    /// Export a keymap to the keymap format of another firmware
+    Export(ExportArgs),
     ParseQmkKeycodes(ParseQmkKeycodesArgs),
 }
 
 #[derive(Args)]
 struct DrawArgs {
-        /// source QMK JSON file
+        /// source keymap: QMK JSON, Vial .vil, VIA backup, ZMK .keymap, Kanata .kbd, RMK keyboard.toml or Keyberon .rs file
         #[arg(short, long, value_name = "FILE")]
         qmk_json: Option<PathBuf>,
 
         /// destination YAML file
         #[arg(short, long, value_name = "FILE")]
         output: Option<PathBuf>,
+
+        /// keymap.c with the features missing from the QMK JSON file (tap dances, ...)
+        #[arg(short, long, value_name = "FILE")]
+        keymap_c: Option<PathBuf>,
+
+        /// QMK source tree, used to get the keycode labels
+        #[arg(long, value_name = "DIR")]
+        qmk_src_path: Option<PathBuf>,
+
+        /// JSON file with keycode labels overriding the builtin ones
+        #[arg(short, long, value_name = "FILE")]
+        labels: Option<PathBuf>,
+
+        /// OS input language (`french`, `german`, `bepo`, `ergol`, ... or a keymap_extras header)
+        #[arg(long, value_name = "LOCALE")]
+        host_locale: Option<String>,
+
+        /// git revision to draw the keymap at, the keymap files are read from their repository
+        #[arg(short, long, value_name = "REVISION")]
+        revision: Option<String>,
+
+        /// JSON file to write the unknown keycode diagnostics to
+        #[arg(long, value_name = "FILE")]
+        diagnostics: Option<PathBuf>,
+
+        /// KLE JSON file with the physical layout of the keyboard, for handwired boards
+        #[arg(long, value_name = "FILE")]
+        kle: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct FindArgs {
+        /// character or keycode to look for
+        query: String,
+
+        /// source QMK JSON file
+        #[arg(short, long, value_name = "FILE")]
+        qmk_json: Option<PathBuf>,
+
+        /// keymap.c with the unicode map
+        #[arg(short, long, value_name = "FILE")]
+        keymap_c: Option<PathBuf>,
+
+        /// QMK source tree, used to get the keymap_extras headers
+        #[arg(long, value_name = "DIR")]
+        qmk_src_path: Option<PathBuf>,
+
+        /// OS input language (`french`, `german`, `bepo`, `ergol`, ... or a keymap_extras header)
+        #[arg(long, value_name = "LOCALE")]
+        host_locale: Option<String>,
+}
+
+#[derive(Args)]
+struct CoverageArgs {
+        /// source QMK JSON file
+        #[arg(short, long, value_name = "FILE")]
+        qmk_json: Option<PathBuf>,
+
+        /// keymap.c with the unicode map
+        #[arg(short, long, value_name = "FILE")]
+        keymap_c: Option<PathBuf>,
+
+        /// QMK source tree, used to get the keymap_extras headers
+        #[arg(long, value_name = "DIR")]
+        qmk_src_path: Option<PathBuf>,
+
+        /// OS input language (`french`, `german`, `bepo`, `ergol`, ... or a keymap_extras header)
+        #[arg(long, value_name = "LOCALE")]
+        host_locale: Option<String>,
+
+        /// predefined character set (`ascii`, `french`)
+        #[arg(long, value_name = "NAME")]
+        charset: Option<String>,
+
+        /// characters to check
+        #[arg(long, value_name = "CHARS")]
+        chars: Option<String>,
+
+        /// text file whose characters are checked
+        #[arg(long, value_name = "FILE")]
+        corpus: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct EditArgs {
+        /// source QMK JSON file
+        #[arg(short, long, value_name = "FILE")]
+        qmk_json: Option<PathBuf>,
+
+        /// destination QMK JSON file, the source file is overwritten if not set
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        #[command(subcommand)]
+        operation: EditOperation,
+}
+
+#[derive(Args)]
+struct FmtArgs {
+        /// QMK JSON file to format
+        #[arg(short, long, value_name = "FILE")]
+        qmk_json: Option<PathBuf>,
+
+        /// QMK info.json of the keyboard, used to get the rows and the split halves
+        #[arg(short, long, value_name = "FILE")]
+        info_json: Option<PathBuf>,
+
+        /// don't write the file, fail if it is not formatted
+        #[arg(long)]
+        check: bool,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+        /// source QMK JSON file
+        #[arg(short, long, value_name = "FILE")]
+        qmk_json: Option<PathBuf>,
+
+        /// destination QMK JSON file
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// target layout macro, `LAYOUT_planck_mit`, ...
+        #[arg(short, long, value_name = "LAYOUT")]
+        layout: String,
+
+        /// target keyboard, `preonic/rev3`, ... the source keyboard is used if not set
+        #[arg(long, value_name = "KEYBOARD")]
+        keyboard: Option<String>,
+
+        /// QMK info.json of the source keyboard
+        #[arg(long, value_name = "FILE")]
+        from_info: Option<PathBuf>,
+
+        /// QMK info.json of the target keyboard
+        #[arg(long, value_name = "FILE")]
+        to_info: Option<PathBuf>,
+
+        /// JSON array giving for each key of the target layout the index of the source key,
+        /// or null
+        #[arg(short, long, value_name = "FILE")]
+        mapping: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+        /// old keymap, a file in one of the formats `draw` reads or REVISION:FILE to read it from git
+        old: String,
+
+        /// new keymap, a file in one of the formats `draw` reads or REVISION:FILE to read it from git
+        new: String,
+
+        /// YAML file where the new keymap is drawn with the changed keys highlighted
+        #[arg(short, long, value_name = "FILE")]
+        visual: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ChangelogArgs {
+        /// git revision range, `v1.0..HEAD`, ...
+        range: String,
+
+        /// keymap file, in a git repository
+        #[arg(short, long, value_name = "FILE")]
+        qmk_json: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct LintArgs {
+        /// source QMK JSON file
+        #[arg(short, long, value_name = "FILE")]
+        qmk_json: Option<PathBuf>,
+
+        /// keymap.c with the features missing from the QMK JSON file (custom keycodes, ...)
+        #[arg(short, long, value_name = "FILE")]
+        keymap_c: Option<PathBuf>,
+
+        /// QMK source tree, used to get all the keycodes, only the builtin ones are known otherwise
+        #[arg(long, value_name = "DIR")]
+        qmk_src_path: Option<PathBuf>,
+
+        /// JSON file with the level of the rules: {"trans-on-base": "off", ...}
+        #[arg(short, long, value_name = "FILE")]
+        config: Option<PathBuf>,
+
+        /// disable a rule, can be repeated
+        #[arg(long, value_name = "RULE")]
+        allow: Vec<String>,
+
+        /// turn a rule into an error, can be repeated
+        #[arg(long, value_name = "RULE")]
+        deny: Vec<String>,
+
+        /// output format: `text`, `json` or `sarif`
+        #[arg(short, long, value_name = "FORMAT", default_value = "text")]
+        format: String,
+
+        /// list the rules and exit
+        #[arg(long)]
+        list_rules: bool,
+}
+
+#[derive(Args)]
+struct KeycodesArgs {
+        /// QMK source tree, used to get all the keycodes, only the basic ones are known otherwise
+        #[arg(long, value_name = "DIR")]
+        qmk_src_path: Option<PathBuf>,
+
+        /// keycode spec version (`0.0.1`, ...), the latest one if not set
+        #[arg(long, value_name = "VERSION")]
+        spec_version: Option<String>,
+
+        #[command(subcommand)]
+        operation: KeycodesOperation,
+}
+
+#[derive(Subcommand)]
+enum KeycodesOperation {
+    /// Print the values of keycodes: `KC_A`, `LT(_LOWER, KC_SPC)`, ...
+    Encode { keycodes: Vec<String> },
+    /// Print the keycodes of decimal or hexadecimal values
+    Decode { values: Vec<String> },
+    /// Print the layers of a QMK JSON keymap as values
+    EncodeKeymap {
+        /// source QMK JSON file
+        #[arg(short, long, value_name = "FILE")]
+        qmk_json: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct ExportArgs {
+        /// source keymap: QMK JSON, Vial .vil, VIA backup, ZMK .keymap, Kanata .kbd, RMK keyboard.toml or Keyberon .rs file
+        #[arg(short, long, value_name = "FILE")]
+        qmk_json: Option<PathBuf>,
+
+        /// keymap.c with the features missing from the QMK JSON file (tap dances, ...)
+        #[arg(short, long, value_name = "FILE")]
+        keymap_c: Option<PathBuf>,
+
+        /// destination file
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// target format: `zmk`, `kanata`, `rmk` or `kle`
+        #[arg(short, long, value_name = "FORMAT")]
+        format: String,
+
+        /// KLE JSON file with the physical layout of the keyboard, used by the `kle` format
+        #[arg(long, value_name = "FILE")]
+        kle: Option<PathBuf>,
+}
+
+// Layers are given by name or by index, keys by their index in the layer
+#[derive(Subcommand)]
+enum EditOperation {
+    /// Swap two keys on all the layers
+    SwapKeys { a: usize, b: usize },
+    /// Move a layer to another position
+    MoveLayer { layer: String, to: usize },
+    /// Add a copy of a layer after it
+    DuplicateLayer { layer: String, name: String },
+    /// Delete a layer and the keys activating it
+    DeleteLayer { layer: String },
+    /// Rename a layer and the keys activating it
+    RenameLayer { layer: String, name: String },
+    /// Replace a keycode everywhere in the keymap
+    Replace { from: String, to: String },
+    /// Copy a layer from another QMK JSON keymap
+    CopyLayer {
+        /// QMK JSON file to copy the layer from
+        from: PathBuf,
+        layer: String,
+        name: String,
+    },
 }
 
 #[derive(Args)]
@@ -42,22 +344,177 @@ struct ParseQmkKeycodesArgs {
         keycodes_filename: Option<PathBuf>,
 }
 
-fn draw(args: DrawArgs) -> Result<(), &'static str> {
-    const FILENAME: &str = "data/planck-ergol.json";
-    const DEST_FILENAME: &str = "planck-ergol.yaml";
-    let qmk_json = args.qmk_json.unwrap_or(PathBuf::from(FILENAME));
-    let output = args.output.unwrap_or(PathBuf::from(DEST_FILENAME));
+const FILENAME: &str = "data/planck-ergol.json";
 
+fn keyboard_config(qmk_json: PathBuf, output: PathBuf) -> Config {
     // hardcoded values for my keyboard, at some point they should come from
     // command parameters, or some extra json files
     let mut config = Config::new(qmk_json, output, 4);
     config.custom.aliases.insert( String::from("LAYOUT_planck_grid"), String::from("LAYOUT_ortho_4x12"));
     config.custom.layer_names = vec!["Qwerty", "ErgoL", "Dvorak", "Lower", "Raise", "Adjust", "NaVim", "Accents"];
+    config
+}
+
+fn draw(args: DrawArgs) -> Result<(), &'static str> {
+    const DEST_FILENAME: &str = "planck-ergol.yaml";
+    let qmk_json = args.qmk_json.unwrap_or(PathBuf::from(FILENAME));
+    let output = args.output.unwrap_or(PathBuf::from(DEST_FILENAME));
+
+    let mut config = keyboard_config(qmk_json, output);
+    config.keymap_c = args.keymap_c;
+    config.qmk_src_path = args.qmk_src_path;
+    config.host_locale = args.host_locale;
+    config.revision = args.revision;
+    config.diagnostics = args.diagnostics;
+    config.kle = args.kle;
+    if let Some(labels) = args.labels {
+        config.custom.labels = labels::overrides_from_file(&labels)?;
+    }
 
     qzmk_drawer::run(config)
 }
 
-fn qmk_keycodes_path(base_path: &PathBuf, keycode_type: &str, version: &str) -> PathBuf {
+fn find(args: FindArgs) -> Result<(), &'static str> {
+    let qmk_json = args.qmk_json.unwrap_or(PathBuf::from(FILENAME));
+
+    let mut config = keyboard_config(qmk_json, PathBuf::new());
+    config.keymap_c = args.keymap_c;
+    config.qmk_src_path = args.qmk_src_path;
+    config.host_locale = args.host_locale;
+
+    qzmk_drawer::find(config, &args.query)
+}
+
+fn coverage(args: CoverageArgs) -> Result<(), &'static str> {
+    let qmk_json = args.qmk_json.unwrap_or(PathBuf::from(FILENAME));
+
+    let mut chars = Vec::new();
+    if let Some(name) = args.charset {
+        chars.extend(coverage::charset(&name).ok_or("unknown character set")?);
+    }
+    if let Some(text) = args.chars {
+        chars.extend(coverage::text_chars(&text));
+    }
+    if let Some(corpus) = args.corpus {
+        let text = fs::read_to_string(corpus).map_err(|_| "Unable to read file")?;
+        chars.extend(coverage::text_chars(&text));
+    }
+    if chars.is_empty() {
+        chars = coverage::charset("ascii").unwrap();
+    }
+    let chars = coverage::text_chars(&chars.iter().collect::<String>());
+
+    let mut config = keyboard_config(qmk_json, PathBuf::new());
+    config.keymap_c = args.keymap_c;
+    config.qmk_src_path = args.qmk_src_path;
+    config.host_locale = args.host_locale;
+
+    qzmk_drawer::coverage(config, &chars)
+}
+
+fn edit(args: EditArgs) -> Result<(), &'static str> {
+    let qmk_json = args.qmk_json.unwrap_or(PathBuf::from(FILENAME));
+    let output = args.output.unwrap_or(qmk_json.clone());
+
+    let operation = match args.operation {
+        EditOperation::SwapKeys { a, b } => Operation::SwapKeys(a, b),
+        EditOperation::MoveLayer { layer, to } => Operation::MoveLayer { layer, to },
+        EditOperation::DuplicateLayer { layer, name } => Operation::DuplicateLayer { layer, name },
+        EditOperation::DeleteLayer { layer } => Operation::DeleteLayer(layer),
+        EditOperation::RenameLayer { layer, name } => Operation::RenameLayer { layer, name },
+        EditOperation::Replace { from, to } => Operation::ReplaceKey { from, to },
+        EditOperation::CopyLayer { from, layer, name } => Operation::CopyLayer { from, layer, name },
+    };
+
+    let config = keyboard_config(qmk_json, PathBuf::new());
+    qzmk_drawer::edit(config, operation, &output)
+}
+
+fn fmt(args: FmtArgs) -> Result<(), &'static str> {
+    let qmk_json = args.qmk_json.unwrap_or(PathBuf::from(FILENAME));
+
+    let config = keyboard_config(qmk_json, PathBuf::new());
+    qzmk_drawer::fmt(config, args.info_json.as_deref(), args.check)
+}
+
+fn convert(args: ConvertArgs) -> Result<(), &'static str> {
+    let qmk_json = args.qmk_json.unwrap_or(PathBuf::from(FILENAME));
+
+    let conversion = Conversion {
+        from_info: args.from_info,
+        to_info: args.to_info,
+        keyboard: args.keyboard,
+        layout: args.layout,
+        mapping: args.mapping,
+    };
+    let config = keyboard_config(qmk_json, PathBuf::new());
+    qzmk_drawer::convert(config, conversion, &args.output)
+}
+
+fn diff(args: DiffArgs) -> Result<(), &'static str> {
+    let config = keyboard_config(PathBuf::new(), PathBuf::new());
+    qzmk_drawer::diff(config, &args.old, &args.new, args.visual.as_deref())
+}
+
+fn changelog(args: ChangelogArgs) -> Result<(), &'static str> {
+    let qmk_json = args.qmk_json.unwrap_or(PathBuf::from(FILENAME));
+
+    let config = keyboard_config(qmk_json, PathBuf::new());
+    qzmk_drawer::changelog(config, &args.range)
+}
+
+fn lint(args: LintArgs) -> Result<(), &'static str> {
+    if args.list_rules {
+        for (name, level, description) in lint::RULES {
+            println!("{name} ({}): {description}", level.name());
+        }
+        return Ok(());
+    }
+    let qmk_json = args.qmk_json.unwrap_or(PathBuf::from(FILENAME));
+
+    let mut lint_config = match args.config {
+        Some(path) => LintConfig::from_json(&fs::read_to_string(path).map_err(|_| "Unable to read file")?)?,
+        None => LintConfig::new(),
+    };
+    for rule in args.allow {
+        lint_config.set(&rule, Level::Off)?;
+    }
+    for rule in args.deny {
+        lint_config.set(&rule, Level::Error)?;
+    }
+
+    let mut config = keyboard_config(qmk_json, PathBuf::new());
+    config.keymap_c = args.keymap_c;
+    config.qmk_src_path = args.qmk_src_path;
+    qzmk_drawer::lint(config, &lint_config, &args.format)
+}
+
+fn keycodes(args: KeycodesArgs) -> Result<(), &'static str> {
+    let mut qmk_json = PathBuf::new();
+    let operation = match args.operation {
+        KeycodesOperation::Encode { keycodes } => numeric::Operation::Encode(keycodes),
+        KeycodesOperation::Decode { values } => numeric::Operation::Decode(values),
+        KeycodesOperation::EncodeKeymap { qmk_json: path } => {
+            qmk_json = path.unwrap_or(PathBuf::from(FILENAME));
+            numeric::Operation::EncodeKeymap
+        }
+    };
+
+    let mut config = keyboard_config(qmk_json, PathBuf::new());
+    config.qmk_src_path = args.qmk_src_path;
+    qzmk_drawer::keycodes(config, args.spec_version.as_deref(), operation)
+}
+
+fn export(args: ExportArgs) -> Result<(), &'static str> {
+    let qmk_json = args.qmk_json.unwrap_or(PathBuf::from(FILENAME));
+
+    let mut config = keyboard_config(qmk_json, PathBuf::new());
+    config.keymap_c = args.keymap_c;
+    config.kle = args.kle;
+    qzmk_drawer::export(config, &args.format, &args.output)
+}
+
+fn qmk_keycodes_path(base_path: &Path, keycode_type: &str, version: &str) -> PathBuf {
     let keycodes_filename = format!("keycodes_{version}_{keycode_type}.hjson");
 
     base_path.join("data").join("constants").join("keycodes").join(keycodes_filename)
@@ -86,7 +543,7 @@ fn parse_qmk_keycodes(args: ParseQmkKeycodesArgs) -> Result<(), &'static str> {
     println!("{}", qmk_src_path.display());
 */
     //constants::parse(&qmk_src_path).map(|_| ())
-    let files = constants::gen_file_list(&PathBuf::from(QMK_SRC_PATH), &[ "0.0.6", "0.0.5", "0.0.4", "0.0.3", "0.0.2", "0.0.1" ]);
+    let files = constants::gen_file_list(&PathBuf::from(QMK_SRC_PATH), &constants::VERSIONS);
     for (key, value) in files.iter() {
         println!("{key}");
         for file in value {
@@ -94,7 +551,10 @@ fn parse_qmk_keycodes(args: ParseQmkKeycodesArgs) -> Result<(), &'static str> {
         }
 
     }
-    constants::parse_categories(&files);
+    for (category, keycodes) in constants::parse_categories(&files) {
+        print!("{category}: ");
+        keycodes.print();
+    }
     if let Ok(k) = constants::parse(&qmk_src_path) {
         k.print();
     }
@@ -105,16 +565,27 @@ fn parse_qmk_keycodes(args: ParseQmkKeycodesArgs) -> Result<(), &'static str> {
 fn main() -> Result<(), &'static str> {
 
     let cli = Cli::parse();
-     match cli.debug {
-        0 => println!("Debug mode is off"),
-        1 => println!("Debug mode is kind of on"),
-        2 => println!("Debug mode is on"),
-        _ => println!("Don't be crazy"),
+    // on stderr, the output of some commands is read by other tools
+    match cli.debug {
+        0 => (),
+        1 => eprintln!("Debug mode is kind of on"),
+        2 => eprintln!("Debug mode is on"),
+        _ => eprintln!("Don't be crazy"),
     }
 
     match cli.command{
         Some(cmd) => match cmd {
             Commands::Draw(args) => draw(args),
+            Commands::Find(args) => find(args),
+            Commands::Coverage(args) => coverage(args),
+            Commands::Edit(args) => edit(args),
+            Commands::Fmt(args) => fmt(args),
+            Commands::Convert(args) => convert(args),
+            Commands::Diff(args) => diff(args),
+            Commands::Changelog(args) => changelog(args),
+            Commands::Lint(args) => lint(args),
+            Commands::Keycodes(args) => keycodes(args),
+            Commands::Export(args) => export(args),
             Commands::ParseQmkKeycodes(args) => parse_qmk_keycodes(args),
             //_default => Err("unknown command"),
         }
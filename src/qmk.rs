@@ -1,2 +1,11 @@
 pub mod constants;
+pub mod convert;
+pub mod diagnostics;
+pub mod format;
+pub mod info;
+pub mod keymap_c;
+pub mod keycodes;
+pub mod keymap_extras;
+pub mod numeric;
 pub mod parser;
+pub mod vial;
@@ -2,7 +2,9 @@ use glob::glob;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+pub const VERSIONS: [&str; 6] = [ "0.0.1", "0.0.2", "0.0.3", "0.0.4", "0.0.5", "0.0.6" ];
 
 #[derive(Serialize, Deserialize)]
 struct Keycode {
@@ -58,6 +60,36 @@ impl KeycodesEnum {
         }
     }
 
+    pub fn get_labels(&self) -> Vec<(&str, &str)> {
+        match self {
+            KeycodesEnum::Ranges{..} => Vec::new(),
+            KeycodesEnum::Keycodes(keycodes) => keycodes.get_labels()
+        }
+    }
+
+    pub fn get_keycodes(&self) -> Vec<(&str, &str)> {
+        match self {
+            KeycodesEnum::Ranges{..} => Vec::new(),
+            KeycodesEnum::Keycodes(keycodes) => keycodes.get_keycodes()
+        }
+    }
+
+    // (value, keycode, aliases) of the keycodes
+    pub fn get_values(&self) -> Vec<(u16, &str, &[String])> {
+        match self {
+            KeycodesEnum::Ranges{..} => Vec::new(),
+            KeycodesEnum::Keycodes(keycodes) => keycodes.get_values()
+        }
+    }
+
+    // (define, first value, last value) of the ranges
+    pub fn get_ranges(&self) -> Vec<(&str, u16, u16)> {
+        match self {
+            KeycodesEnum::Ranges(ranges) => ranges.get_ranges(),
+            KeycodesEnum::Keycodes{..} => Vec::new(),
+        }
+    }
+
     pub fn print(&self) {
         match self {
             KeycodesEnum::Keycodes(k) => println!("keycodes, {} items", k.keycodes.len()),
@@ -85,7 +117,25 @@ impl KeycodesEnum {
     }
 }
 
+fn parse_hex(value: &str) -> Option<u16> {
+    u16::from_str_radix(value.trim().strip_prefix("0x")?, 16).ok()
+}
+
 impl Ranges {
+    // Ranges are written as `0x2000/0x1FFF`, the first value and the size minus one
+    pub fn get_ranges(&self) -> Vec<(&str, u16, u16)> {
+        let mut ranges = Vec::new();
+        for (range, value) in self.ranges.iter() {
+            let (RangeEnum::Detailed(Range { define: Some(define) }), Some((first, size))) = (value, range.split_once('/')) else {
+                continue;
+            };
+            if let (Some(first), Some(size)) = (parse_hex(first), parse_hex(size)) {
+                ranges.push((define.as_str(), first, first.saturating_add(size)));
+            }
+        }
+        ranges
+    }
+
     pub fn merge(&mut self, new: Ranges) {
         for (key, value) in new.ranges.into_iter() {
             match value {
@@ -108,8 +158,50 @@ impl Ranges {
 
 impl Keycodes {
     pub fn get_aliases(&self) -> Vec<&str> {
+        let mut aliases = Vec::new();
         for keycode in self.keycodes.values() {
+            if let KeycodeEnum::Detailed(k) = keycode {
+                aliases.extend(k.aliases.iter().map(|a| a.as_str()));
+            }
         }
+        aliases
+    }
+
+    // Returns (keycode, label) pairs for all keycodes with a label, their aliases get the same
+    // label as the keycode they alias.
+    pub fn get_labels(&self) -> Vec<(&str, &str)> {
+        let mut labels = Vec::new();
+        for keycode in self.keycodes.values() {
+            if let KeycodeEnum::Detailed(k) = keycode {
+                if let Some(label) = &k.label {
+                    labels.push((k.key.as_str(), label.as_str()));
+                    labels.extend(k.aliases.iter().map(|a| (a.as_str(), label.as_str())));
+                }
+            }
+        }
+        labels
+    }
+
+    // Returns (name, keycode) pairs for all keycodes and their aliases
+    pub fn get_keycodes(&self) -> Vec<(&str, &str)> {
+        let mut keycodes = Vec::new();
+        for keycode in self.keycodes.values() {
+            if let KeycodeEnum::Detailed(k) = keycode {
+                keycodes.push((k.key.as_str(), k.key.as_str()));
+                keycodes.extend(k.aliases.iter().map(|a| (a.as_str(), k.key.as_str())));
+            }
+        }
+        keycodes
+    }
+
+    pub fn get_values(&self) -> Vec<(u16, &str, &[String])> {
+        let mut values = Vec::new();
+        for (value, keycode) in self.keycodes.iter() {
+            if let (Some(value), KeycodeEnum::Detailed(k)) = (parse_hex(value), keycode) {
+                values.push((value, k.key.as_str(), k.aliases.as_slice()));
+            }
+        }
+        values
     }
 
     pub fn merge(&mut self, new: Keycodes) {
@@ -197,7 +289,7 @@ pub fn parse(file: &PathBuf) -> Result<KeycodesEnum, &'static str> {
     let j = serde_json::to_string(&ks).unwrap();
     println!("{}", j);
 */
-    if data.len() == 0 {
+    if data.is_empty() {
         println!("empty file");
     }
     match serde_hjson::from_str::<KeycodesEnum>(&data) {
@@ -232,7 +324,7 @@ fn append_val<T>(h: &mut HashMap<String, Vec<T>>, key: &str, val: T) {
     h.get_mut(key).unwrap().push(val);
 }
 
-pub fn gen_file_list(base_path: &PathBuf, versions: &[&str]) -> HashMap<String, Vec<PathBuf>> {
+pub fn gen_file_list(base_path: &Path, versions: &[&str]) -> HashMap<String, Vec<PathBuf>> {
     let mut files = HashMap::new();
     const PREFIX: &str = "keycodes_";
     let base_path = base_path.join("data").join("constants").join("keycodes");
@@ -243,7 +335,7 @@ pub fn gen_file_list(base_path: &PathBuf, versions: &[&str]) -> HashMap<String,
         let prefix = format!("{PREFIX}{version}_");
         let path_glob = base_path.join(format!("{prefix}*.hjson"));
         let glob_iter = glob(&path_glob.to_string_lossy());
-        if let Err(_) = glob_iter {
+        if glob_iter.is_err() {
             println!("continue");
             continue;
         }
@@ -251,20 +343,79 @@ pub fn gen_file_list(base_path: &PathBuf, versions: &[&str]) -> HashMap<String,
             if let Some(file_name) = entry.file_name() {
                 let category = String::from(file_name.to_string_lossy().strip_prefix(&prefix).expect("missing prefix, glob bug?"));
                 let category = category.strip_suffix(".hjson").expect("missing suffix, glob bug?");
-                append_val(&mut files, &category, entry);
+                append_val(&mut files, category, entry);
             }
         }
     }
     files
 }
 
-pub fn parse_categories(categories: &HashMap<String, Vec<PathBuf>>) {
-    for file_list in categories.values() {
+// The file lists are expected to be sorted from the oldest to the newest version, as each version
+// only contains the changes from the previous one.
+pub fn parse_categories(categories: &HashMap<String, Vec<PathBuf>>) -> HashMap<String, KeycodesEnum> {
+    let mut merged: HashMap<String, KeycodesEnum> = HashMap::new();
+    for (category, file_list) in categories.iter() {
         for file in file_list {
-            match parse(file) {
-                Ok(keycodes) => (),
+            let keycodes = match parse(file) {
+                Ok(keycodes) => keycodes,
                 Err(_) => continue,
+            };
+            match merged.get_mut(category) {
+                Some(k) => k.merge(keycodes),
+                None => { merged.insert(category.to_string(), keycodes); },
             }
         }
     }
+    merged
+}
+
+// Returns the keycode labels defined in the QMK source tree at `base_path`, including the ones
+// of the keycode aliases.
+pub fn labels(base_path: &Path) -> HashMap<String, String> {
+    let files = gen_file_list(base_path, &VERSIONS);
+    let categories = parse_categories(&files);
+    let mut labels = HashMap::new();
+    for keycodes in categories.values() {
+        for (key, label) in keycodes.get_labels() {
+            labels.insert(key.to_string(), label.to_string());
+        }
+    }
+    labels
+}
+
+// Returns all the keycode names defined in the QMK source tree at `base_path`, aliases included,
+// with the keycode they are a name of
+pub fn keycode_names(base_path: &Path) -> HashMap<String, String> {
+    let files = gen_file_list(base_path, &VERSIONS);
+    let categories = parse_categories(&files);
+    let mut names = HashMap::new();
+    for keycodes in categories.values() {
+        for (name, keycode) in keycodes.get_keycodes() {
+            names.insert(name.to_string(), keycode.to_string());
+        }
+    }
+    names
+}
+
+// (define, first value, last value)
+pub type SpecRange = (String, u16, u16);
+// (value, keycode, aliases)
+pub type SpecKeycode = (u16, String, Vec<String>);
+
+// Returns the ranges and the keycodes of the keycode spec `version` in the QMK source tree at
+// `base_path`
+pub fn spec(base_path: &Path, version: &str) -> Result<(Vec<SpecRange>, Vec<SpecKeycode>), &'static str> {
+    let idx = VERSIONS.iter().position(|v| *v == version).ok_or("unknown keycode spec version")?;
+    let files = gen_file_list(base_path, &VERSIONS[..=idx]);
+    let categories = parse_categories(&files);
+    let mut ranges = Vec::new();
+    let mut keycodes = Vec::new();
+    for category in categories.values() {
+        ranges.extend(category.get_ranges().into_iter().map(|(define, first, last)| (define.to_string(), first, last)));
+        keycodes.extend(category.get_values().into_iter().map(|(value, key, aliases)| (value, key.to_string(), aliases.to_vec())));
+    }
+    if keycodes.is_empty() {
+        return Err("no keycodes found in the QMK source tree");
+    }
+    Ok((ranges, keycodes))
 }
@@ -0,0 +1,156 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::keymap::{Key, Keymap};
+use crate::qmk::info::InfoKey;
+
+// Keys further than this from all the source keys are new positions
+const MAX_DISTANCE: f64 = 1.25;
+
+// Options of the `convert` subcommand, the mapping comes from `mapping` if set, otherwise from
+// the geometry of the info.json files
+pub struct Conversion {
+    pub from_info: Option<PathBuf>,
+    pub to_info: Option<PathBuf>,
+    // target keyboard, the keyboard of the keymap is kept if not set
+    pub keyboard: Option<String>,
+    pub layout: String,
+    pub mapping: Option<PathBuf>,
+}
+
+// A key of the source keymap which has no position in the target layout
+pub struct DroppedKey {
+    pub layer: usize,
+    pub index: usize,
+    pub key: Key,
+}
+
+// Key centers, with the left half relative to the left edge of the keyboard, the right half
+// relative to its right edge, and rows relative to the top or bottom edge. This way the keys keep
+// their place relative to the closest edge when the keyboards don't have the same size.
+fn anchored_centers(keys: &[InfoKey], top: bool) -> Vec<(bool, f64, f64)> {
+    let min_x = keys.iter().map(|k| k.x).fold(f64::INFINITY, f64::min);
+    let max_x = keys.iter().map(|k| k.x + k.w).fold(f64::NEG_INFINITY, f64::max);
+    let edge_y = match top {
+        true => keys.iter().map(|k| k.y).fold(f64::INFINITY, f64::min),
+        false => keys.iter().map(|k| k.y + k.h).fold(f64::NEG_INFINITY, f64::max),
+    };
+    let middle = (min_x + max_x) / 2.0;
+    keys.iter()
+        .map(|key| {
+            let (x, y) = key.center();
+            match x < middle {
+                true => (true, x - min_x, y - edge_y),
+                false => (false, x - max_x, y - edge_y),
+            }
+        })
+        .collect()
+}
+
+// Maps each key of the `to` layout to the closest key of the `from` layout. The rows are aligned
+// on the bottom edge (a number row is added above the existing rows) unless aligning them on the
+// top edge keeps more keys (thumb clusters of split keyboards below shorter rows).
+pub fn geometry_mapping(from: &[InfoKey], to: &[InfoKey]) -> Vec<Option<usize>> {
+    let bottom = anchored_mapping(from, to, false);
+    let top = anchored_mapping(from, to, true);
+    match top.iter().flatten().count() > bottom.iter().flatten().count() {
+        true => top,
+        false => bottom,
+    }
+}
+
+// Closest pairs are matched first, and each source key is used at most once
+fn anchored_mapping(from: &[InfoKey], to: &[InfoKey], top: bool) -> Vec<Option<usize>> {
+    let from_centers = anchored_centers(from, top);
+    let to_centers = anchored_centers(to, top);
+    let mut pairs = Vec::new();
+    for (dst, (dst_left, dst_x, dst_y)) in to_centers.iter().enumerate() {
+        for (src, (src_left, src_x, src_y)) in from_centers.iter().enumerate() {
+            let distance = ((dst_x - src_x).powi(2) + (dst_y - src_y).powi(2)).sqrt();
+            if dst_left == src_left && distance <= MAX_DISTANCE {
+                pairs.push((distance, dst, src));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut mapping = vec![None; to.len()];
+    let mut used = vec![false; from.len()];
+    for (_, dst, src) in pairs {
+        if mapping[dst].is_none() && !used[src] {
+            mapping[dst] = Some(src);
+            used[src] = true;
+        }
+    }
+    mapping
+}
+
+// Reads a mapping written by hand: a JSON array with, for each key of the target layout, the
+// index of the source key to use, or `null` for new positions
+pub fn mapping_from_file(path: &Path) -> Result<Vec<Option<usize>>, &'static str> {
+    let data = fs::read_to_string(path).map_err(|_| "Unable to read file")?;
+    serde_json::from_str(&data).map_err(|_| "Unable to parse file")
+}
+
+// Remaps the layers of `keymap` to a layout with `mapping.len()` keys. New positions are `KC_NO`
+// on the base layer and `KC_TRNS` on the other layers. Returns the keys which were not mapped to
+// a position of the new layout, `KC_NO` and `KC_TRNS` keys are not reported.
+pub fn convert(keymap: &mut Keymap, mapping: &[Option<usize>], keyboard: &str, layout: &str) -> Result<Vec<DroppedKey>, &'static str> {
+    let num_keys = keymap.num_rows * keymap.num_columns;
+    if mapping.iter().flatten().any(|src| *src >= num_keys) {
+        return Err("the mapping uses keys which are not in the keymap");
+    }
+    let mut dropped = Vec::new();
+    for (i, layer) in keymap.layers.iter_mut().enumerate() {
+        let fill = if i == 0 { Key::No } else { Key::Trans };
+        let keys: Vec<Key> = mapping.iter().map(|src| src.map_or(fill.clone(), |src| layer[src].clone())).collect();
+        for (index, key) in layer.iter().enumerate() {
+            if !mapping.contains(&Some(index)) && !matches!(key, Key::No | Key::Trans) {
+                dropped.push(DroppedKey { layer: i, index, key: key.clone() });
+            }
+        }
+        *layer = keys;
+    }
+    // the rows of the new layout are not known, they come from its info.json when it is written
+    keymap.num_rows = 1;
+    keymap.num_columns = mapping.len();
+    keymap.keyboard = keyboard.to_string();
+    keymap.layout = layout.to_string();
+    keymap.combos.iter_mut().for_each(|combo| combo.positions.clear());
+    Ok(dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::layer::LayerNames;
+    use crate::kle::writer::grid_keys;
+    use crate::qmk::parser;
+
+    #[test]
+    fn rows_are_aligned_on_the_bottom_edge() {
+        // a 2x2 grid to a 3x2 grid, the number row is new
+        let mapping = geometry_mapping(&grid_keys(2, 2), &grid_keys(3, 2));
+        assert_eq!(mapping, [None, None, Some(0), Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn split_halves_are_aligned_on_their_outer_edge() {
+        // the inner column of a 2x4 grid has no place on a 2x2 grid
+        let mapping = geometry_mapping(&grid_keys(2, 4), &grid_keys(2, 2));
+        assert_eq!(mapping, [Some(0), Some(3), Some(4), Some(7)]);
+    }
+
+    #[test]
+    fn keys_are_moved_following_the_mapping() {
+        let layer_names = LayerNames::new(vec![String::from("Base"), String::from("Lower")]);
+        let json = r#"{"keyboard": "old", "keymap": "test", "layout": "LAYOUT", "layers": [["KC_A", "KC_B", "KC_C"], ["KC_1", "KC_TRNS", "KC_3"]]}"#;
+        let mut keymap = parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        let dropped = convert(&mut keymap, &[Some(2), None, Some(0)], "new", "LAYOUT_split").unwrap();
+        let keycodes: Vec<Vec<String>> = keymap.layers.iter().map(|layer| layer.iter().map(parser::keycode).collect()).collect();
+        assert_eq!(keycodes, [["KC_C", "KC_NO", "KC_A"], ["KC_3", "KC_TRNS", "KC_1"]]);
+        // the transparent key of the lower layer is not reported
+        let dropped: Vec<(usize, usize, String)> = dropped.iter().map(|d| (d.layer, d.index, parser::keycode(&d.key))).collect();
+        assert_eq!(dropped, [(0, 1, String::from("KC_B"))]);
+        assert_eq!((keymap.keyboard.as_str(), keymap.layout.as_str(), keymap.num_columns), ("new", "LAYOUT_split", 3));
+        assert!(convert(&mut keymap, &[Some(3)], "new", "LAYOUT").is_err());
+    }
+}
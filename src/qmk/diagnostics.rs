@@ -0,0 +1,239 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::keymap::{Key, Keymap};
+use crate::qmk::{constants, keycodes};
+use crate::qmk::keymap_extras::US_SHIFTED;
+
+// Suggestions further than this edit distance from the unknown keycode are not kept
+const MAX_DISTANCE: usize = 2;
+const MAX_SUGGESTIONS: usize = 3;
+
+// Keycode names, aliases included, used to check the keycodes of a keymap
+pub struct KeycodeDatabase {
+    // name -> keycode it is a name of
+    names: HashMap<String, String>,
+    // whether all the QMK keycodes are known, otherwise only the basic `KC_` keycodes are checked
+    complete: bool,
+}
+
+impl KeycodeDatabase {
+    // Basic keycodes only
+    pub fn builtin() -> KeycodeDatabase {
+        let mut names = HashMap::new();
+        let mut add = |keycode: String, aliases: Vec<String>| {
+            for alias in aliases {
+                names.insert(alias, keycode.to_string());
+            }
+            names.insert(keycode.to_string(), keycode);
+        };
+        for (_, keycode, aliases) in keycodes::basic_keycodes() {
+            add(keycode, aliases);
+        }
+        for (shifted, _, _) in US_SHIFTED {
+            add(shifted.to_string(), Vec::new());
+        }
+        KeycodeDatabase {
+            names,
+            complete: false,
+        }
+    }
+
+    // All the keycodes defined in the QMK source tree at `base_path`
+    pub fn from_qmk_src(base_path: &Path) -> KeycodeDatabase {
+        let mut database = KeycodeDatabase::builtin();
+        let names = constants::keycode_names(base_path);
+        database.complete = !names.is_empty();
+        database.names.extend(names);
+        database
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    // Known names close to `keycode`: the same name in upper case or with the `KC_` prefix, or
+    // else the names within `MAX_DISTANCE` edits. Names are followed by the keycode they are an
+    // alias of, `KC_ENT (KC_ENTER)`, and only one name is kept per keycode.
+    pub fn suggestions(&self, keycode: &str) -> Vec<String> {
+        let upper = keycode.to_uppercase();
+        let prefixed = format!("KC_{upper}");
+        let mut candidates: Vec<(usize, &str)> = Vec::new();
+        for name in [&upper, &prefixed] {
+            if let Some((name, _)) = self.names.get_key_value(name.as_str()) {
+                candidates.push((0, name));
+            }
+        }
+        if candidates.is_empty() {
+            candidates = self
+                .names
+                .keys()
+                .map(|name| (edit_distance(&upper, name).min(edit_distance(&prefixed, name)), name.as_str()))
+                .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+                .collect();
+            candidates.sort();
+        }
+
+        let mut suggestions: Vec<String> = Vec::new();
+        let mut seen: Vec<&str> = Vec::new();
+        for (_, name) in candidates {
+            let keycode = self.names[name].as_str();
+            if seen.contains(&keycode) {
+                continue;
+            }
+            seen.push(keycode);
+            suggestions.push(match keycode == name {
+                true => name.to_string(),
+                false => format!("{name} ({keycode})"),
+            });
+            if suggestions.len() == MAX_SUGGESTIONS {
+                break;
+            }
+        }
+        suggestions
+    }
+}
+
+// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Problem {
+    // keycode which is not in the database
+    Unknown,
+    // keycode expression which the parser doesn't understand, drawn as is
+    Unparsed,
+}
+
+impl Problem {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Problem::Unknown => "unknown",
+            Problem::Unparsed => "unparsed",
+        }
+    }
+}
+
+// A suspicious keycode at position `index` of `layer`
+pub struct Diagnostic {
+    pub layer: usize,
+    pub index: usize,
+    pub keycode: String,
+    pub problem: Problem,
+    pub suggestions: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn message(&self) -> String {
+        let mut message = match self.problem {
+            Problem::Unknown => format!("unknown keycode {}", self.keycode),
+            Problem::Unparsed => format!("keycode {} could not be parsed", self.keycode),
+        };
+        if let Some((last, others)) = self.suggestions.split_last() {
+            match others.is_empty() {
+                true => message.push_str(&format!(", did you mean {last}?")),
+                false => message.push_str(&format!(", did you mean {} or {last}?", others.join(", "))),
+            }
+        }
+        message
+    }
+}
+
+// Keycodes of `key` to look up in the database, with the keycodes of the unparsed expressions
+// (`LCTL(LALT(KC_DEL))`, ...) as a second element
+fn keycodes(key: &Key, database: &KeycodeDatabase) -> (Vec<String>, bool) {
+    lazy_static! {
+        static ref KEYCODE: Regex = Regex::new(r"\bKC_\w+").unwrap();
+    }
+    match key {
+        Key::BasicKey(key) | Key::ModTap { key, .. } | Key::ModKey { key, .. } => (vec![format!("KC_{key}")], false),
+        Key::LayerTap { key, .. } if key.starts_with("KC_") => (vec![key.to_string()], false),
+        Key::SpecialKey(key) if database.complete => (vec![key.to_string()], false),
+        Key::Unknown(key) if key.contains('(') => (KEYCODE.find_iter(key).map(|m| m.as_str().to_string()).collect(), true),
+        Key::Unknown(key) => (vec![key.to_string()], false),
+        _ => (Vec::new(), false),
+    }
+}
+
+// Checks the keycodes of the keymap layers against `database`
+pub fn diagnose(keymap: &Keymap, database: &KeycodeDatabase) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for (layer, keys) in keymap.layers.iter().enumerate() {
+        for (index, key) in keys.iter().enumerate() {
+            let (keycodes, unparsed) = keycodes(key, database);
+            let unknown: Vec<&String> = keycodes.iter().filter(|k| !database.contains(k)).collect();
+            for keycode in &unknown {
+                diagnostics.push(Diagnostic {
+                    layer,
+                    index,
+                    keycode: keycode.to_string(),
+                    problem: Problem::Unknown,
+                    suggestions: database.suggestions(keycode),
+                });
+            }
+            if let (Key::Unknown(expression), true, true) = (key, unparsed, unknown.is_empty()) {
+                diagnostics.push(Diagnostic {
+                    layer,
+                    index,
+                    keycode: expression.to_string(),
+                    problem: Problem::Unparsed,
+                    suggestions: Vec::new(),
+                });
+            }
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::layer::LayerNames;
+    use crate::qmk::parser;
+
+    fn messages(keycodes: &[&str]) -> Vec<(usize, String)> {
+        let layer_names = LayerNames::new(vec![String::from("Base")]);
+        let layer: Vec<String> = keycodes.iter().map(|k| format!("\"{k}\"")).collect();
+        let json = format!(r#"{{"keyboard": "", "keymap": "test", "layout": "", "layers": [[{}]]}}"#, layer.join(", "));
+        let keymap = parser::keymap_from_str(&json, 1, &layer_names).unwrap();
+        diagnose(&keymap, &KeycodeDatabase::builtin()).iter().map(|d| (d.index, d.message())).collect()
+    }
+
+    #[test]
+    fn unknown_keycodes_get_suggestions() {
+        assert_eq!(messages(&["KC_A", "KC_SPCE", "kc_tab", "ENTER", "KC_XYZZY"]), [
+            (1, String::from("unknown keycode KC_SPCE, did you mean KC_SPACE, KC_BSPC (KC_BACKSPACE) or KC_SLCT (KC_SELECT)?")),
+            (2, String::from("unknown keycode kc_tab, did you mean KC_TAB?")),
+            (3, String::from("unknown keycode ENTER, did you mean KC_ENTER?")),
+            (4, String::from("unknown keycode KC_XYZZY")),
+        ]);
+    }
+
+    #[test]
+    fn unparsed_expressions_are_reported() {
+        assert_eq!(messages(&["LCTL(LALT(KC_DEL))", "LCTL(LALT(KC_DLE))"]), [
+            (0, String::from("keycode LCTL(LALT(KC_DEL)) could not be parsed")),
+            (1, String::from("unknown keycode KC_DLE, did you mean KC_DLR, KC_CLR (KC_CLEAR) or KC_D?")),
+        ]);
+    }
+
+    #[test]
+    fn edit_distance_counts_the_edits() {
+        assert_eq!(edit_distance("KC_SPCE", "KC_SPACE"), 1);
+        assert_eq!(edit_distance("KC_ESC", "KC_ECS"), 2);
+        assert_eq!(edit_distance("", "KC_A"), 4);
+    }
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+use crate::qmk::info::{self, InfoKey};
+
+// Indentation of the keycodes in the formatted keymap.json, the layers are in the `layers`
+// array of the top-level object
+const INDENT: &str = "            ";
+// Space added between the two halves of split keyboards
+const HALF_SEPARATOR: &str = "    ";
+
+// Physical arrangement of the keys of a layer: rows, split in halves, of key indexes
+pub struct Grid {
+    rows: Vec<Vec<Vec<usize>>>,
+}
+
+impl Grid {
+    // Rows of `num_columns` keys, without halves
+    pub fn from_columns(num_keys: usize, num_columns: usize) -> Grid {
+        let keys: Vec<usize> = (0..num_keys).collect();
+        Grid {
+            rows: keys.chunks(num_columns.max(1)).map(|row| vec![row.to_vec()]).collect(),
+        }
+    }
+
+    // Builds the grid from the geometry of `layout` in a QMK info.json file. A new row starts
+    // when a key is on the left of the previous one, and halves are separated by a gap of at
+    // least one key.
+    pub fn from_info_json(path: &Path, layout: &str) -> Result<Grid, &'static str> {
+        Ok(Grid::from_info_keys(&info::layout_keys(path, layout)?))
+    }
+
+    pub fn from_info_keys(keys: &[InfoKey]) -> Grid {
+        let mut rows: Vec<Vec<Vec<usize>>> = Vec::new();
+        let mut previous: Option<&InfoKey> = None;
+        for (idx, key) in keys.iter().enumerate() {
+            match previous {
+                Some(prev) if key.x > prev.x => {
+                    let row = rows.last_mut().unwrap();
+                    if key.x - (prev.x + prev.w) >= 1.0 {
+                        row.push(Vec::new());
+                    }
+                    row.last_mut().unwrap().push(idx);
+                }
+                _ => rows.push(vec![vec![idx]]),
+            }
+            previous = Some(key);
+        }
+        Grid { rows }
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn num_keys(&self) -> usize {
+        self.rows.iter().flatten().map(|half| half.len()).sum()
+    }
+}
+
+// Formats `layers` as the content of the keymap.json `layers` array, one line per row with the
+// columns aligned across all the rows and layers
+pub fn format_layers(layers: &[Vec<String>], grid: &Grid) -> String {
+    // width of each (half, column) cell, computed over all the rows and layers
+    let mut widths: Vec<Vec<usize>> = Vec::new();
+    for layer in layers {
+        for row in &grid.rows {
+            for (h, half) in row.iter().enumerate() {
+                if widths.len() <= h {
+                    widths.push(Vec::new());
+                }
+                for (c, idx) in half.iter().enumerate() {
+                    if widths[h].len() <= c {
+                        widths[h].push(0);
+                    }
+                    let width = layer.get(*idx).map_or(0, |k| k.chars().count() + 4);
+                    widths[h][c] = widths[h][c].max(width);
+                }
+            }
+        }
+    }
+
+    let mut formatted_layers = Vec::new();
+    for layer in layers {
+        let last = layer.len().saturating_sub(1);
+        let mut lines = Vec::new();
+        for row in &grid.rows {
+            let mut halves = Vec::new();
+            for (h, half) in row.iter().enumerate() {
+                let mut cells = String::new();
+                for (c, idx) in half.iter().enumerate() {
+                    let Some(keycode) = layer.get(*idx) else {
+                        continue;
+                    };
+                    let cell = format!("\"{keycode}\"{} ", if *idx == last { "" } else { "," });
+                    cells.push_str(&format!("{cell:<width$}", width = widths[h][c]));
+                }
+                // the halves keep their padding, so that the following ones are aligned too
+                cells.pop();
+                halves.push(cells);
+            }
+            lines.push(format!("{INDENT}{}", halves.join(HALF_SEPARATOR)).trim_end().to_string());
+        }
+        formatted_layers.push(format!("        [\n{}\n        ]", lines.join("\n")));
+    }
+    format!("[\n{}\n    ]", formatted_layers.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Offsets of the keycodes of a formatted row
+    fn cell_offsets(line: &str) -> Vec<usize> {
+        line.match_indices('"').step_by(2).map(|(offset, _)| offset).collect()
+    }
+
+    #[test]
+    fn columns_are_aligned_across_rows_and_layers() {
+        let layers: Vec<Vec<String>> = [
+            ["KC_TAB", "KC_Q", "KC_W", "LCTL_T(KC_ESC)", "KC_A", "KC_S"],
+            ["KC_TRNS", "LT(_LOWER, KC_1)", "KC_2", "KC_NO", "KC_3", "MO(_RAISE)"],
+        ]
+        .iter()
+        .map(|layer| layer.iter().map(|k| k.to_string()).collect())
+        .collect();
+        let formatted = format_layers(&layers, &Grid::from_columns(6, 3));
+        let rows: Vec<&str> = formatted.lines().filter(|line| line.starts_with(INDENT)).collect();
+        assert_eq!(rows.len(), 4);
+        for row in &rows {
+            assert_eq!(cell_offsets(row), cell_offsets(rows[0]), "{formatted}");
+        }
+    }
+
+    #[test]
+    fn halves_are_aligned_separately() {
+        let layers = vec![(0..8).map(|i| format!("KC_{}", "X".repeat(i))).collect::<Vec<String>>()];
+        let grid = Grid { rows: vec![vec![vec![0, 1], vec![2, 3]], vec![vec![4, 5], vec![6, 7]]] };
+        let formatted = format_layers(&layers, &grid);
+        let rows: Vec<&str> = formatted.lines().filter(|line| line.starts_with(INDENT)).collect();
+        assert_eq!(cell_offsets(rows[0]), cell_offsets(rows[1]), "{formatted}");
+        assert!(rows[0].contains(&format!(",{HALF_SEPARATOR}")));
+    }
+}
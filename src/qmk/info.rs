@@ -0,0 +1,71 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// A key of a QMK info.json layout, positions and sizes are in key units
+#[derive(Clone, Deserialize)]
+pub struct InfoKey {
+    pub x: f64,
+    pub y: f64,
+    #[serde(default = "default_size")]
+    pub w: f64,
+    #[serde(default = "default_size")]
+    pub h: f64,
+    // rotation in degrees, clockwise around (rx, ry)
+    #[serde(default)]
+    pub r: f64,
+    #[serde(default)]
+    pub rx: f64,
+    #[serde(default)]
+    pub ry: f64,
+}
+
+fn default_size() -> f64 {
+    1.0
+}
+
+impl InfoKey {
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+}
+
+#[derive(Deserialize)]
+struct InfoLayout {
+    layout: Vec<InfoKey>,
+}
+
+#[derive(Deserialize)]
+struct Info {
+    layouts: HashMap<String, InfoLayout>,
+}
+
+// Returns the keys of `layout` from the info.json file at `path`, in keymap order
+pub fn layout_keys(path: &Path, layout: &str) -> Result<Vec<InfoKey>, &'static str> {
+    let data = fs::read_to_string(path).map_err(|_| "Unable to read file")?;
+    let mut info: Info = serde_json::from_str(&data).map_err(|_| "Unable to parse file")?;
+    match info.layouts.remove(layout) {
+        Some(layout) => Ok(layout.layout),
+        None => Err("layout not found in info.json"),
+    }
+}
+
+// Writes `keys` as the `layout` of an info.json file, the sizes and rotations are only written
+// when they are not the default ones
+pub fn info_json(keys: &[InfoKey], layout: &str) -> String {
+    let keys: Vec<serde_json::Value> = keys
+        .iter()
+        .map(|key| {
+            let mut json = serde_json::json!({ "x": key.x, "y": key.y });
+            for (name, value, default) in [("w", key.w, 1.0), ("h", key.h, 1.0), ("r", key.r, 0.0), ("rx", key.rx, 0.0), ("ry", key.ry, 0.0)] {
+                if value != default {
+                    json[name] = serde_json::json!(value);
+                }
+            }
+            json
+        })
+        .collect();
+    let info = serde_json::json!({ "layouts": { layout: { "layout": keys } } });
+    format!("{info:#}\n")
+}
@@ -0,0 +1,160 @@
+use crate::qmk::constants::SpecKeycode;
+
+// Basic keycodes, as defined in data/constants/keycodes/keycodes_0.0.1_basic.hjson, used when the
+// QMK source tree is not available. The numbered keycodes (letters, F keys, ...) are generated by
+// `basic_keycodes`.
+const BASIC_KEYCODES: &[(u16, &str, &[&str])] = &[
+    (0x0000, "KC_NO", &["XXXXXXX"]),
+    (0x0001, "KC_TRANSPARENT", &["KC_TRNS", "_______"]),
+    (0x0028, "KC_ENTER", &["KC_ENT"]),
+    (0x0029, "KC_ESCAPE", &["KC_ESC"]),
+    (0x002A, "KC_BACKSPACE", &["KC_BSPC"]),
+    (0x002B, "KC_TAB", &[]),
+    (0x002C, "KC_SPACE", &["KC_SPC"]),
+    (0x002D, "KC_MINUS", &["KC_MINS"]),
+    (0x002E, "KC_EQUAL", &["KC_EQL"]),
+    (0x002F, "KC_LEFT_BRACKET", &["KC_LBRC"]),
+    (0x0030, "KC_RIGHT_BRACKET", &["KC_RBRC"]),
+    (0x0031, "KC_BACKSLASH", &["KC_BSLS"]),
+    (0x0032, "KC_NONUS_HASH", &["KC_NUHS"]),
+    (0x0033, "KC_SEMICOLON", &["KC_SCLN"]),
+    (0x0034, "KC_QUOTE", &["KC_QUOT"]),
+    (0x0035, "KC_GRAVE", &["KC_GRV"]),
+    (0x0036, "KC_COMMA", &["KC_COMM"]),
+    (0x0037, "KC_DOT", &[]),
+    (0x0038, "KC_SLASH", &["KC_SLSH"]),
+    (0x0039, "KC_CAPS_LOCK", &["KC_CAPS"]),
+    (0x0046, "KC_PRINT_SCREEN", &["KC_PSCR"]),
+    (0x0047, "KC_SCROLL_LOCK", &["KC_SCRL", "KC_BRMD"]),
+    (0x0048, "KC_PAUSE", &["KC_PAUS", "KC_BRK", "KC_BRMU"]),
+    (0x0049, "KC_INSERT", &["KC_INS"]),
+    (0x004A, "KC_HOME", &[]),
+    (0x004B, "KC_PAGE_UP", &["KC_PGUP"]),
+    (0x004C, "KC_DELETE", &["KC_DEL"]),
+    (0x004D, "KC_END", &[]),
+    (0x004E, "KC_PAGE_DOWN", &["KC_PGDN"]),
+    (0x004F, "KC_RIGHT", &["KC_RGHT"]),
+    (0x0050, "KC_LEFT", &[]),
+    (0x0051, "KC_DOWN", &[]),
+    (0x0052, "KC_UP", &[]),
+    (0x0053, "KC_NUM_LOCK", &["KC_NUM"]),
+    (0x0054, "KC_KP_SLASH", &["KC_PSLS"]),
+    (0x0055, "KC_KP_ASTERISK", &["KC_PAST"]),
+    (0x0056, "KC_KP_MINUS", &["KC_PMNS"]),
+    (0x0057, "KC_KP_PLUS", &["KC_PPLS"]),
+    (0x0058, "KC_KP_ENTER", &["KC_PENT"]),
+    (0x0063, "KC_KP_DOT", &["KC_PDOT"]),
+    (0x0064, "KC_NONUS_BACKSLASH", &["KC_NUBS"]),
+    (0x0065, "KC_APPLICATION", &["KC_APP"]),
+    (0x0066, "KC_KB_POWER", &[]),
+    (0x0067, "KC_KP_EQUAL", &["KC_PEQL"]),
+    (0x0074, "KC_EXECUTE", &["KC_EXEC"]),
+    (0x0075, "KC_HELP", &[]),
+    (0x0076, "KC_MENU", &[]),
+    (0x0077, "KC_SELECT", &["KC_SLCT"]),
+    (0x0078, "KC_STOP", &[]),
+    (0x0079, "KC_AGAIN", &["KC_AGIN"]),
+    (0x007A, "KC_UNDO", &[]),
+    (0x007B, "KC_CUT", &[]),
+    (0x007C, "KC_COPY", &[]),
+    (0x007D, "KC_PASTE", &["KC_PSTE"]),
+    (0x007E, "KC_FIND", &[]),
+    (0x007F, "KC_KB_MUTE", &[]),
+    (0x0080, "KC_KB_VOLUME_UP", &[]),
+    (0x0081, "KC_KB_VOLUME_DOWN", &[]),
+    (0x0082, "KC_LOCKING_CAPS_LOCK", &["KC_LCAP"]),
+    (0x0083, "KC_LOCKING_NUM_LOCK", &["KC_LNUM"]),
+    (0x0084, "KC_LOCKING_SCROLL_LOCK", &["KC_LSCR"]),
+    (0x0085, "KC_KP_COMMA", &["KC_PCMM"]),
+    (0x0086, "KC_KP_EQUAL_AS400", &[]),
+    (0x0099, "KC_ALTERNATE_ERASE", &["KC_ERAS"]),
+    (0x009A, "KC_SYSTEM_REQUEST", &["KC_SYRQ"]),
+    (0x009B, "KC_CANCEL", &["KC_CNCL"]),
+    (0x009C, "KC_CLEAR", &["KC_CLR"]),
+    (0x009D, "KC_PRIOR", &["KC_PRIR"]),
+    (0x009E, "KC_RETURN", &["KC_RETN"]),
+    (0x009F, "KC_SEPARATOR", &["KC_SEPR"]),
+    (0x00A0, "KC_OUT", &[]),
+    (0x00A1, "KC_OPER", &[]),
+    (0x00A2, "KC_CLEAR_AGAIN", &["KC_CLAG"]),
+    (0x00A3, "KC_CRSEL", &["KC_CRSL"]),
+    (0x00A4, "KC_EXSEL", &["KC_EXSL"]),
+    (0x00A5, "KC_SYSTEM_POWER", &["KC_PWR"]),
+    (0x00A6, "KC_SYSTEM_SLEEP", &["KC_SLEP"]),
+    (0x00A7, "KC_SYSTEM_WAKE", &["KC_WAKE"]),
+    (0x00A8, "KC_AUDIO_MUTE", &["KC_MUTE"]),
+    (0x00A9, "KC_AUDIO_VOL_UP", &["KC_VOLU"]),
+    (0x00AA, "KC_AUDIO_VOL_DOWN", &["KC_VOLD"]),
+    (0x00AB, "KC_MEDIA_NEXT_TRACK", &["KC_MNXT"]),
+    (0x00AC, "KC_MEDIA_PREV_TRACK", &["KC_MPRV"]),
+    (0x00AD, "KC_MEDIA_STOP", &["KC_MSTP"]),
+    (0x00AE, "KC_MEDIA_PLAY_PAUSE", &["KC_MPLY"]),
+    (0x00AF, "KC_MEDIA_SELECT", &["KC_MSEL"]),
+    (0x00B0, "KC_MEDIA_EJECT", &["KC_EJCT"]),
+    (0x00B1, "KC_MAIL", &[]),
+    (0x00B2, "KC_CALCULATOR", &["KC_CALC"]),
+    (0x00B3, "KC_MY_COMPUTER", &["KC_MYCM"]),
+    (0x00B4, "KC_WWW_SEARCH", &["KC_WSCH"]),
+    (0x00B5, "KC_WWW_HOME", &["KC_WHOM"]),
+    (0x00B6, "KC_WWW_BACK", &["KC_WBAK"]),
+    (0x00B7, "KC_WWW_FORWARD", &["KC_WFWD"]),
+    (0x00B8, "KC_WWW_STOP", &["KC_WSTP"]),
+    (0x00B9, "KC_WWW_REFRESH", &["KC_WREF"]),
+    (0x00BA, "KC_WWW_FAVORITES", &["KC_WFAV"]),
+    (0x00BB, "KC_MEDIA_FAST_FORWARD", &["KC_MFFD"]),
+    (0x00BC, "KC_MEDIA_REWIND", &["KC_MRWD"]),
+    (0x00BD, "KC_BRIGHTNESS_UP", &["KC_BRIU"]),
+    (0x00BE, "KC_BRIGHTNESS_DOWN", &["KC_BRID"]),
+    (0x00BF, "KC_CONTROL_PANEL", &["KC_CPNL"]),
+    (0x00C0, "KC_ASSISTANT", &["KC_ASST"]),
+    (0x00C1, "KC_MISSION_CONTROL", &["KC_MCTL"]),
+    (0x00C2, "KC_LAUNCHPAD", &["KC_LPAD"]),
+    (0x00CD, "KC_MS_UP", &["KC_MS_U"]),
+    (0x00CE, "KC_MS_DOWN", &["KC_MS_D"]),
+    (0x00CF, "KC_MS_LEFT", &["KC_MS_L"]),
+    (0x00D0, "KC_MS_RIGHT", &["KC_MS_R"]),
+    (0x00D9, "KC_MS_WH_UP", &["KC_WH_U"]),
+    (0x00DA, "KC_MS_WH_DOWN", &["KC_WH_D"]),
+    (0x00DB, "KC_MS_WH_LEFT", &["KC_WH_L"]),
+    (0x00DC, "KC_MS_WH_RIGHT", &["KC_WH_R"]),
+    (0x00E0, "KC_LEFT_CTRL", &["KC_LCTL"]),
+    (0x00E1, "KC_LEFT_SHIFT", &["KC_LSFT"]),
+    (0x00E2, "KC_LEFT_ALT", &["KC_LALT", "KC_LOPT"]),
+    (0x00E3, "KC_LEFT_GUI", &["KC_LGUI", "KC_LCMD", "KC_LWIN"]),
+    (0x00E4, "KC_RIGHT_CTRL", &["KC_RCTL"]),
+    (0x00E5, "KC_RIGHT_SHIFT", &["KC_RSFT"]),
+    (0x00E6, "KC_RIGHT_ALT", &["KC_RALT", "KC_ROPT", "KC_ALGR"]),
+    (0x00E7, "KC_RIGHT_GUI", &["KC_RGUI", "KC_RCMD", "KC_RWIN"]),
+];
+
+// Returns (value, keycode, aliases) for all the basic keycodes
+pub fn basic_keycodes() -> Vec<SpecKeycode> {
+    let mut keycodes = Vec::new();
+    let mut add = |value: u16, keycode: String, aliases: Vec<String>| keycodes.push((value, keycode, aliases));
+    for (i, c) in ('A'..='Z').enumerate() {
+        add(0x04 + i as u16, format!("KC_{c}"), Vec::new());
+    }
+    // KC_1 to KC_9 then KC_0, the same for the keypad
+    for i in 0..10u16 {
+        add(0x1E + i, format!("KC_{}", (i + 1) % 10), Vec::new());
+        add(0x59 + i, format!("KC_KP_{}", (i + 1) % 10), vec![format!("KC_P{}", (i + 1) % 10)]);
+    }
+    for i in 0..12u16 {
+        add(0x3A + i, format!("KC_F{}", i + 1), Vec::new());
+        add(0x68 + i, format!("KC_F{}", i + 13), Vec::new());
+    }
+    for i in 0..9u16 {
+        add(0x87 + i, format!("KC_INTERNATIONAL_{}", i + 1), vec![format!("KC_INT{}", i + 1)]);
+        add(0x90 + i, format!("KC_LANGUAGE_{}", i + 1), vec![format!("KC_LNG{}", i + 1)]);
+    }
+    for i in 0..8u16 {
+        add(0xD1 + i, format!("KC_MS_BTN{}", i + 1), vec![format!("KC_BTN{}", i + 1)]);
+    }
+    for i in 0..3u16 {
+        add(0xDD + i, format!("KC_MS_ACCEL{i}"), vec![format!("KC_ACL{i}")]);
+    }
+    for (value, keycode, aliases) in BASIC_KEYCODES {
+        add(*value, keycode.to_string(), aliases.iter().map(|a| a.to_string()).collect());
+    }
+    keycodes
+}
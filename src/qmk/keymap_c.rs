@@ -0,0 +1,550 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{Combo, CustomAction, CustomKeycode, Encoder, Key, KeyOverride, TapDance};
+use crate::qmk::parser;
+
+// Accented letters used by the unicode map names of the form `E_ACUTE_LOWER`
+const ACCENTS: &[(&str, &[(char, char)])] = &[
+    ("ACUTE", &[('a', 'á'), ('c', 'ć'), ('e', 'é'), ('i', 'í'), ('n', 'ń'), ('o', 'ó'), ('s', 'ś'), ('u', 'ú'), ('y', 'ý'), ('z', 'ź')]),
+    ("GRAVE", &[('a', 'à'), ('e', 'è'), ('i', 'ì'), ('o', 'ò'), ('u', 'ù')]),
+    ("CIRCUMFLEX", &[('a', 'â'), ('e', 'ê'), ('i', 'î'), ('o', 'ô'), ('u', 'û')]),
+    ("DIAERESIS", &[('a', 'ä'), ('e', 'ë'), ('i', 'ï'), ('o', 'ö'), ('u', 'ü'), ('y', 'ÿ')]),
+    ("CEDILLA", &[('c', 'ç')]),
+    ("TILDE", &[('a', 'ã'), ('n', 'ñ'), ('o', 'õ')]),
+];
+
+// Unicode map names which can't be guessed from their name
+const UNICODE_NAMES: &[(&str, char)] = &[
+    ("AE_LOWER", 'æ'),
+    ("AE_UPPER", 'Æ'),
+    ("OE_LOWER", 'œ'),
+    ("OE_UPPER", 'Œ'),
+    ("SHARP_S_LOWER", 'ß'),
+    ("SHARP_S_UPPER", 'ẞ'),
+    ("MU_LOWER", 'µ'),
+    ("MU_UPPER", 'Μ'),
+    ("EURO_SIGN", '€'),
+    ("EURO", '€'),
+    ("POUND_SIGN", '£'),
+    ("DEGREE", '°'),
+    ("SECTION", '§'),
+    ("COPYRIGHT", '©'),
+    ("REGISTERED", '®'),
+    ("ENDASH", '–'),
+    ("EMDASH", '—'),
+    ("THREEDOTS", '…'),
+    ("ELLIPSIS", '…'),
+    ("LEFT_GUILLEMET", '«'),
+    ("RIGHT_GUILLEMET", '»'),
+];
+
+// Returns the body of the C array initializer `name[...] = { ... };`, without the outer braces
+pub fn array_body<'a>(data: &'a str, name: &str) -> Option<&'a str> {
+    let decl = Regex::new(&format!(r"\b{}\s*(\[[^\]]*\]\s*)+=\s*\{{", regex::escape(name))).unwrap();
+    let start = decl.find(data)?.end();
+    let mut depth = 1;
+    for (i, c) in data[start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&data[start..start + i]);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+// Splits `s` on the commas which are not nested in parentheses or braces
+pub fn split_top_level(s: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in s.chars() {
+        match c {
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => (),
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        items.push(current.trim().to_string());
+    }
+    items
+}
+
+// Returns the entries of an array initializer body as (designator, value) pairs. Entries without
+// a `[NAME] =` designator are named after their index.
+pub fn array_entries(body: &str) -> Vec<(String, String)> {
+    lazy_static! {
+        static ref DESIGNATOR: Regex = Regex::new(r"(?s)^\[\s*([^\]]+?)\s*\]\s*=\s*(.*)$").unwrap();
+    }
+    let mut entries = Vec::new();
+    for (i, item) in split_top_level(body).into_iter().enumerate() {
+        match DESIGNATOR.captures(&item) {
+            Some(entry) => entries.push((entry[1].to_string(), entry[2].trim().to_string())),
+            None => entries.push((i.to_string(), item)),
+        }
+    }
+    entries
+}
+
+// Splits a macro call such as `ACTION_TAP_DANCE_DOUBLE(KC_ESC, KC_CAPS)` in its name and arguments
+pub fn macro_call(expr: &str) -> Option<(String, Vec<String>)> {
+    lazy_static! {
+        static ref CALL: Regex = Regex::new(r"(?s)^(\w+)\s*\((.*)\)$").unwrap();
+    }
+    let call = CALL.captures(expr.trim())?;
+    Some((call[1].to_string(), split_top_level(&call[2])))
+}
+
+// Parses `tap_dance_action_t tap_dance_actions[] = { [TD_ESC_CAPS] = ACTION_TAP_DANCE_DOUBLE(KC_ESC, KC_CAPS), ... };`
+// Tap dances using custom functions only get the function name, as their behaviour can't be
+// known without running them.
+pub fn tap_dances(data: &str, layer_names: &LayerNames) -> HashMap<String, TapDance> {
+    let mut tap_dances = HashMap::new();
+    let data = strip_comments(data);
+    let Some(body) = array_body(&data, "tap_dance_actions") else {
+        return tap_dances;
+    };
+    let key = |keycode: &str| Some(parser::parse_keycode(keycode, layer_names));
+    for (name, value) in array_entries(body) {
+        let Some((action, args)) = macro_call(&value) else {
+            continue;
+        };
+        let mut td = TapDance { tap: None, double_tap: None, hold: None, function: None };
+        match (action.as_str(), args.as_slice()) {
+            ("ACTION_TAP_DANCE_DOUBLE", [tap, double_tap]) => {
+                td.tap = key(tap);
+                td.double_tap = key(double_tap);
+            }
+            ("ACTION_TAP_DANCE_LAYER_MOVE", [tap, layer]) => {
+                td.tap = key(tap);
+                td.double_tap = key(&format!("TO({layer})"));
+            }
+            ("ACTION_TAP_DANCE_LAYER_TOGGLE", [tap, layer]) => {
+                td.tap = key(tap);
+                td.double_tap = key(&format!("TG({layer})"));
+            }
+            // not part of QMK, but defined in most keymaps using tap dances for tap/hold
+            ("ACTION_TAP_DANCE_TAP_HOLD", [tap, hold]) => {
+                td.tap = key(tap);
+                td.hold = key(hold);
+            }
+            ("ACTION_TAP_DANCE_FN" | "ACTION_TAP_DANCE_FN_ADVANCED", args) => {
+                td.function = args.iter().find(|a| *a != "NULL").cloned();
+            }
+            _ => td.function = Some(action),
+        }
+        tap_dances.insert(name, td);
+    }
+    tap_dances
+}
+
+// Returns the body of the C function `name`, without the outer braces
+pub fn function_body<'a>(data: &'a str, name: &str) -> Option<&'a str> {
+    let decl = Regex::new(&format!(r"\b{}\s*\([^)]*\)\s*\{{", regex::escape(name))).unwrap();
+    let start = decl.find(data)?.end();
+    let mut depth = 1;
+    for (i, c) in data[start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&data[start..start + i]);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+fn layer_name(layer: &str, layer_names: &LayerNames) -> String {
+    layer_names.pretty_name(layer).unwrap_or(layer.to_string())
+}
+
+// Returns the layers each combo is restricted to by `combo_should_trigger()`. Only the simple
+// cases are handled:
+//   case COMBO: return layer_state_is(_LAYER);
+//   case COMBO: if (layer_state_is(_LAYER)) return false;
+//   case COMBO: if (!layer_state_is(_LAYER)) { return false; }
+fn combo_layers(data: &str, layer_names: &LayerNames) -> HashMap<String, (Vec<String>, Vec<String>)> {
+    lazy_static! {
+        static ref CASE: Regex = Regex::new(r"case\s+(\w+)\s*:").unwrap();
+        static ref ONLY: Regex = Regex::new(r"return\s+layer_state_is\s*\(\s*(\w+)\s*\)|if\s*\(\s*!\s*layer_state_is\s*\(\s*(\w+)\s*\)\s*\)\s*\{?\s*return\s+false").unwrap();
+        static ref EXCLUDED: Regex = Regex::new(r"if\s*\(\s*layer_state_is\s*\(\s*(\w+)\s*\)\s*\)\s*\{?\s*return\s+false").unwrap();
+    }
+    let mut layers = HashMap::new();
+    let Some(body) = function_body(data, "combo_should_trigger") else {
+        return layers;
+    };
+    let cases: Vec<_> = CASE.captures_iter(body).map(|c| (c[1].to_string(), c.get(0).unwrap().end())).collect();
+    for (i, (name, start)) in cases.iter().enumerate() {
+        let end = cases.get(i + 1).map(|(_, next)| *next).unwrap_or(body.len());
+        let case = &body[*start..end];
+        let only = ONLY.captures_iter(case).map(|c| layer_name(c.get(1).or(c.get(2)).unwrap().as_str(), layer_names)).collect();
+        let excluded = EXCLUDED.captures_iter(case).map(|c| layer_name(&c[1], layer_names)).collect();
+        layers.insert(name.to_string(), (only, excluded));
+    }
+    layers
+}
+
+// Parses the combos from `combo_t key_combos[] = { [AB_ESC] = COMBO(ab_combo, KC_ESC), ... };`
+// and their key arrays `const uint16_t PROGMEM ab_combo[] = {KC_A, KC_B, COMBO_END};`.
+// `layers` are the names of all the keymap layers, used for the combos which are disabled on some
+// layers. Also returns the layer set with `COMBO_ONLY_FROM_LAYER` if any.
+pub fn combos(data: &str, layer_names: &LayerNames, layers: &[String]) -> (Vec<Combo>, Option<String>) {
+    lazy_static! {
+        static ref KEYS: Regex = Regex::new(r"uint16_t\s+(?:PROGMEM\s+)?(\w+)\s*\[[^\]]*\]\s*=\s*\{([^}]*)\}").unwrap();
+        static ref ONLY_FROM_LAYER: Regex = Regex::new(r"#define\s+COMBO_ONLY_FROM_LAYER\s+(\w+)").unwrap();
+    }
+    let data = strip_comments(data);
+    let only_from_layer = ONLY_FROM_LAYER.captures(&data).map(|c| c[1].to_string());
+    let mut combos = Vec::new();
+    let Some(body) = array_body(&data, "key_combos") else {
+        return (combos, only_from_layer);
+    };
+    let key_arrays: HashMap<String, Vec<String>> = KEYS
+        .captures_iter(&data)
+        .map(|c| (c[1].to_string(), split_top_level(&c[2]).into_iter().filter(|k| k != "COMBO_END").collect()))
+        .collect();
+    let restrictions = combo_layers(&data, layer_names);
+    for (name, value) in array_entries(body) {
+        let Some((action, args)) = macro_call(&value) else {
+            continue;
+        };
+        let (keys, result) = match (action.as_str(), args.as_slice()) {
+            ("COMBO", [keys, result]) => (keys, parser::parse_keycode(result, layer_names)),
+            ("COMBO_ACTION", [keys]) => (keys, Key::Unknown(name.to_string())),
+            _ => continue,
+        };
+        let Some(keys) = key_arrays.get(keys) else {
+            continue;
+        };
+        let combo_layers = match restrictions.get(&name) {
+            Some((only, _)) if !only.is_empty() => Some(only.clone()),
+            Some((_, excluded)) if !excluded.is_empty() => Some(layers.iter().filter(|l| !excluded.contains(l)).cloned().collect()),
+            _ => None,
+        };
+        combos.push(Combo {
+            name,
+            keys: keys.iter().map(|k| parser::parse_keycode(k, layer_names)).collect(),
+            result,
+            layers: combo_layers,
+            positions: Vec::new(),
+        });
+    }
+    (combos, only_from_layer)
+}
+
+// Returns what the `process_record_user()` code in `case` does, for the simple cases: sending a
+// string, changing the default layer or tapping a keycode.
+fn custom_action(case: &str, layer_names: &LayerNames) -> Option<CustomAction> {
+    lazy_static! {
+        static ref SEND_STRING: Regex = Regex::new(r#"SEND_STRING\s*\(\s*"((?:[^"\\]|\\.)*)""#).unwrap();
+        static ref DEFAULT_LAYER: Regex = Regex::new(r"(?:set_single_persistent_default_layer|set_single_default_layer|default_layer_set)\s*\(\s*(?:1UL\s*<<\s*)?\(?\s*(\w+)").unwrap();
+        static ref TAP_CODE: Regex = Regex::new(r"tap_code(?:16)?\s*\(\s*(.+?)\s*\)\s*;").unwrap();
+    }
+    if let Some(send) = SEND_STRING.captures(case) {
+        let text = send[1].replace("\\n", "\n").replace("\\\"", "\"").replace("\\\\", "\\");
+        return Some(CustomAction::SendString(text));
+    }
+    if let Some(layer) = DEFAULT_LAYER.captures(case) {
+        return Some(CustomAction::DefaultLayer(layer_name(&layer[1], layer_names)));
+    }
+    if let Some(tap) = TAP_CODE.captures(case) {
+        return Some(CustomAction::Tap(parser::parse_keycode(&tap[1], layer_names)));
+    }
+    None
+}
+
+// Parses the custom keycodes declared in an enum starting at `SAFE_RANGE`,
+// `enum custom_keycodes { QWERTY = SAFE_RANGE, ERGOL, DVORAK };`, and summarises what they do
+// from `process_record_user()` when possible.
+pub fn custom_keycodes(data: &str, layer_names: &LayerNames) -> HashMap<String, CustomKeycode> {
+    lazy_static! {
+        static ref ENUM: Regex = Regex::new(r"(?s)enum\s*\w*\s*\{(.*?)\}").unwrap();
+        static ref CASE: Regex = Regex::new(r"case\s+(\w+)\s*:").unwrap();
+    }
+    let data = strip_comments(data);
+    let mut keycodes = HashMap::new();
+    for e in ENUM.captures_iter(&data) {
+        if !["SAFE_RANGE", "QK_USER", "QK_KB"].iter().any(|start| e[1].contains(start)) {
+            continue;
+        }
+        for member in split_top_level(&e[1]) {
+            let name = member.split('=').next().unwrap_or("").trim().to_string();
+            if !name.is_empty() {
+                keycodes.insert(name.to_string(), CustomKeycode { name, action: None });
+            }
+        }
+    }
+    let Some(body) = function_body(&data, "process_record_user") else {
+        return keycodes;
+    };
+    let cases: Vec<_> = CASE.captures_iter(body).map(|c| (c[1].to_string(), c.get(0).unwrap().start(), c.get(0).unwrap().end())).collect();
+    // cases falling through to the next one share its action
+    let mut action = None;
+    for (i, (name, _, start)) in cases.iter().enumerate().rev() {
+        let end = cases.get(i + 1).map(|(_, next, _)| *next).unwrap_or(body.len());
+        let case = &body[*start..end];
+        if !case.trim().is_empty() {
+            action = custom_action(case, layer_names);
+        }
+        if let Some(keycode) = keycodes.get_mut(name) {
+            keycode.action = action.clone();
+        }
+    }
+    keycodes
+}
+
+// Returns the layers enabled in a layer mask such as `(1 << _LOWER) | (1 << _RAISE)`, `None` if
+// all the layers are enabled. `layers` are the names of all the keymap layers.
+fn mask_layers(mask: &str, layer_names: &LayerNames, layers: &[String]) -> Option<Vec<String>> {
+    lazy_static! {
+        static ref SHIFT: Regex = Regex::new(r"1U?L?\s*<<\s*(\w+)").unwrap();
+        static ref ALL: Regex = Regex::new(r"^~\s*0U?L?$").unwrap();
+    }
+    let mask = mask.trim();
+    if ALL.is_match(mask) {
+        return None;
+    }
+    let masked: Vec<String> = SHIFT
+        .captures_iter(mask)
+        .map(|c| match c[1].parse::<usize>() {
+            Ok(idx) => layer_names.get(idx),
+            Err(_) => layer_name(&c[1], layer_names),
+        })
+        .collect();
+    if mask.starts_with('~') {
+        return Some(layers.iter().filter(|l| !masked.contains(l)).cloned().collect());
+    }
+    Some(masked)
+}
+
+// Parses the key overrides defined with the `ko_make_*` helpers,
+// `const key_override_t delete_key_override = ko_make_basic(MOD_MASK_SHIFT, KC_BSPC, KC_DEL);`
+// When the `key_overrides` array is found, only the overrides it lists are returned.
+pub fn key_overrides(data: &str, layer_names: &LayerNames, layers: &[String]) -> Vec<KeyOverride> {
+    lazy_static! {
+        static ref KO: Regex = Regex::new(r"(?s)key_override_t\s+(\w+)\s*=\s*(ko_make_\w+\s*\(.*?\))\s*;").unwrap();
+        static ref LIST: Regex = Regex::new(r"(?s)key_override_t\s*\*\*?\s*key_overrides\b[^{]*\{(.*?)\}").unwrap();
+    }
+    let data = strip_comments(data);
+    let listed: Option<Vec<String>> = LIST.captures(&data).map(|list| {
+        split_top_level(&list[1]).iter().map(|item| item.trim_start_matches('&').trim().to_string()).collect()
+    });
+    let mut overrides = Vec::new();
+    for ko in KO.captures_iter(&data) {
+        let name = ko[1].to_string();
+        if listed.as_ref().is_some_and(|listed| !listed.contains(&name)) {
+            continue;
+        }
+        let Some((_, args)) = macro_call(&ko[2]) else {
+            continue;
+        };
+        if args.len() < 3 {
+            continue;
+        }
+        overrides.push(KeyOverride {
+            name,
+            modifiers: args[0].to_string(),
+            trigger: parser::parse_keycode(&args[1], layer_names),
+            replacement: parser::parse_keycode(&args[2], layer_names),
+            layers: args.get(3).and_then(|mask| mask_layers(mask, layer_names, layers)),
+        });
+    }
+    overrides
+}
+
+// Parses `encoder_map[][NUM_ENCODERS][NUM_DIRECTIONS] = { [_QWERTY] = { ENCODER_CCW_CW(KC_VOLD, KC_VOLU) }, ... };`
+// The returned encoders are indexed like `layers`, layers missing from the map have no encoders.
+pub fn encoders(data: &str, layer_names: &LayerNames, layers: &[String]) -> Vec<Vec<Encoder>> {
+    let mut encoders = vec![Vec::new(); layers.len()];
+    let data = strip_comments(data);
+    let Some(body) = array_body(&data, "encoder_map") else {
+        return encoders;
+    };
+    for (layer, value) in array_entries(body) {
+        let idx = match layer.parse::<usize>() {
+            Ok(idx) => Some(idx),
+            Err(_) => layers.iter().position(|l| *l == layer_name(&layer, layer_names)),
+        };
+        let Some(idx) = idx.filter(|idx| *idx < layers.len()) else {
+            continue;
+        };
+        let value = value.trim().trim_start_matches('{').trim_end_matches('}');
+        for encoder in split_top_level(value) {
+            let Some((_, args)) = macro_call(&encoder) else {
+                continue;
+            };
+            if let [ccw, cw] = args.as_slice() {
+                encoders[idx].push(Encoder {
+                    ccw: parser::parse_keycode(ccw, layer_names),
+                    cw: parser::parse_keycode(cw, layer_names),
+                });
+            }
+        }
+    }
+    encoders
+}
+
+// Removes `//` and `/* */` comments from C source code, leaving the string and character literals,
+// such as the URLs sent by `SEND_STRING("https://...")`, as they are
+pub fn strip_comments(data: &str) -> String {
+    let mut stripped = String::with_capacity(data.len());
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            ('"' | '\'', _) => {
+                stripped.push(c);
+                while let Some(next) = chars.next() {
+                    stripped.push(next);
+                    if next == '\\' {
+                        stripped.extend(chars.next());
+                    } else if next == c || next == '\n' {
+                        break;
+                    }
+                }
+            }
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+// Maps the names used in `UM()` and `UP()` keycodes to the characters they type
+pub struct UnicodeMap {
+    chars: HashMap<String, char>,
+}
+
+impl UnicodeMap {
+    pub fn new() -> UnicodeMap {
+        UnicodeMap {
+            chars: HashMap::new(),
+        }
+    }
+
+    // Parses `const uint32_t PROGMEM unicode_map[] = { [EURO_SIGN] = 0x20AC, ... };`
+    pub fn from_keymap_c(data: &str) -> UnicodeMap {
+        lazy_static! {
+            static ref ENTRY: Regex = Regex::new(r"\[\s*(\w+)\s*\]\s*=\s*(0[xX][0-9a-fA-F]+|\d+)").unwrap();
+        }
+        let mut map = UnicodeMap::new();
+        let data = strip_comments(data);
+        let Some(body) = array_body(&data, "unicode_map") else {
+            return map;
+        };
+        for entry in ENTRY.captures_iter(body) {
+            let value = match entry[2].strip_prefix("0x").or(entry[2].strip_prefix("0X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => entry[2].parse::<u32>().ok(),
+            };
+            if let Some(c) = value.and_then(char::from_u32) {
+                map.chars.insert(entry[1].to_string(), c);
+            }
+        }
+        map
+    }
+
+    pub fn from_file(path: &Path) -> Result<UnicodeMap, &'static str> {
+        match fs::read_to_string(path) {
+            Ok(data) => Ok(UnicodeMap::from_keymap_c(&data)),
+            Err(_) => Err("Unable to read file"),
+        }
+    }
+
+    // Returns the character for `name`, falling back to guessing it from common unicode map
+    // names when it's not in the keymap unicode map.
+    pub fn get(&self, name: &str) -> Option<char> {
+        if let Some(c) = self.chars.get(name) {
+            return Some(*c);
+        }
+        if let Some((_, c)) = UNICODE_NAMES.iter().find(|(n, _)| *n == name) {
+            return Some(*c);
+        }
+        let (name, upper) = match name.strip_suffix("_LOWER") {
+            Some(name) => (name, false),
+            None => (name.strip_suffix("_UPPER")?, true),
+        };
+        let (letter, accent) = name.split_once('_')?;
+        let letter = letter.to_lowercase().chars().next()?;
+        let (_, letters) = ACCENTS.iter().find(|(a, _)| *a == accent)?;
+        let (_, c) = letters.iter().find(|(l, _)| *l == letter)?;
+        if upper {
+            c.to_uppercase().next()
+        } else {
+            Some(*c)
+        }
+    }
+}
+
+impl Default for UnicodeMap {
+    fn default() -> Self {
+        UnicodeMap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_are_stripped() {
+        let data = "a, // one\nb, /* two\n three */ c /**/, d /* * / */\n";
+        assert_eq!(strip_comments(data), "a, \nb,  c , d \n");
+    }
+
+    #[test]
+    fn literals_are_kept() {
+        let data = r#"SEND_STRING("https://example.com /* x */"); // url
+c = '/'; d = '"'; e = "\"//"; /* '"' */ f"#;
+        assert_eq!(strip_comments(data), "SEND_STRING(\"https://example.com /* x */\"); \nc = '/'; d = '\"'; e = \"\\\"//\";  f");
+    }
+
+    #[test]
+    fn send_string_urls_are_kept() {
+        let data = r#"enum custom_keycodes { REPO = SAFE_RANGE };
+bool process_record_user(uint16_t keycode, keyrecord_t *record) {
+    switch (keycode) {
+    case REPO: // the repository
+        if (record->event.pressed) {
+            SEND_STRING("https://github.com/qmk/qmk_firmware");
+        }
+        return false;
+    }
+    return true;
+}"#;
+        let keycodes = custom_keycodes(data, &LayerNames::new(Vec::new()));
+        let action = keycodes["REPO"].action.clone();
+        assert_eq!(action, Some(CustomAction::SendString(String::from("https://github.com/qmk/qmk_firmware"))));
+    }
+}
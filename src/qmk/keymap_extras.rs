@@ -0,0 +1,276 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// Shifted US keycodes, as defined in quantum/keymap_extras/keymap_us.h
+// (keycode, unshifted keycode, output)
+pub const US_SHIFTED: &[(&str, &str, &str)] = &[
+    ("KC_TILD", "KC_GRV", "~"),
+    ("KC_EXLM", "KC_1", "!"),
+    ("KC_AT", "KC_2", "@"),
+    ("KC_HASH", "KC_3", "#"),
+    ("KC_DLR", "KC_4", "$"),
+    ("KC_PERC", "KC_5", "%"),
+    ("KC_CIRC", "KC_6", "^"),
+    ("KC_AMPR", "KC_7", "&"),
+    ("KC_ASTR", "KC_8", "*"),
+    ("KC_LPRN", "KC_9", "("),
+    ("KC_RPRN", "KC_0", ")"),
+    ("KC_UNDS", "KC_MINS", "_"),
+    ("KC_PLUS", "KC_EQL", "+"),
+    ("KC_LCBR", "KC_LBRC", "{"),
+    ("KC_RCBR", "KC_RBRC", "}"),
+    ("KC_PIPE", "KC_BSLS", "|"),
+    ("KC_COLN", "KC_SCLN", ":"),
+    ("KC_DQUO", "KC_QUOT", "\""),
+    ("KC_DQT", "KC_QUOT", "\""),
+    ("KC_LT", "KC_COMM", "<"),
+    ("KC_LABK", "KC_COMM", "<"),
+    ("KC_GT", "KC_DOT", ">"),
+    ("KC_RABK", "KC_DOT", ">"),
+    ("KC_QUES", "KC_SLSH", "?"),
+];
+
+// Unshifted US symbols, letters and digits are handled separately
+const US_SYMBOLS: &[(&str, &str)] = &[
+    ("KC_GRV", "`"),
+    ("KC_MINS", "-"),
+    ("KC_EQL", "="),
+    ("KC_LBRC", "["),
+    ("KC_RBRC", "]"),
+    ("KC_BSLS", "\\"),
+    ("KC_SCLN", ";"),
+    ("KC_QUOT", "'"),
+    ("KC_COMM", ","),
+    ("KC_DOT", "."),
+    ("KC_SLSH", "/"),
+    ("KC_SPC", " "),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Level {
+    Base,
+    Shift,
+    AltGr,
+    ShiftAltGr,
+}
+
+impl Level {
+    fn with_shift(self) -> Level {
+        match self {
+            Level::Base | Level::Shift => Level::Shift,
+            Level::AltGr | Level::ShiftAltGr => Level::ShiftAltGr,
+        }
+    }
+
+    fn with_altgr(self) -> Level {
+        match self {
+            Level::Base | Level::AltGr => Level::AltGr,
+            Level::Shift | Level::ShiftAltGr => Level::ShiftAltGr,
+        }
+    }
+
+    // Returns the QMK keycode sending `keycode` at this level, e.g. `S(KC_1)`
+    pub fn wrap(&self, keycode: &str) -> String {
+        match self {
+            Level::Base => keycode.to_string(),
+            Level::Shift => format!("S({keycode})"),
+            Level::AltGr => format!("ALGR({keycode})"),
+            Level::ShiftAltGr => format!("S(ALGR({keycode}))"),
+        }
+    }
+
+    // Modifiers which need to be held to reach this level
+    pub fn modifiers(&self) -> Vec<&'static str> {
+        match self {
+            Level::Base => Vec::new(),
+            Level::Shift => vec!["Shift"],
+            Level::AltGr => vec!["AltGr"],
+            Level::ShiftAltGr => vec!["Shift", "AltGr"],
+        }
+    }
+}
+
+// A keycode defined by a keymap_extras header, for example
+// `#define FR_EACU KC_2 // é`
+pub struct LocaleKey {
+    // name of the keycode in the header, `FR_EACU`
+    pub name: String,
+    // basic keycode sent by the keyboard, `KC_2`
+    pub keycode: String,
+    pub level: Level,
+    // what the OS types, as written in the header comment. Letters are uppercase, as on keycaps
+    pub output: String,
+    pub dead: bool,
+}
+
+pub struct Locale {
+    pub name: String,
+    pub keys: Vec<LocaleKey>,
+}
+
+impl Locale {
+    // The US layout is what QMK basic keycodes are named after, it has no keymap_extras header
+    // with character comments so it's built from the keycode names.
+    pub fn us() -> Locale {
+        let mut keys: Vec<LocaleKey> = Vec::new();
+        let mut add = |keycode: &str, level: Level, output: &str| {
+            // several aliases exist for some shifted keycodes (KC_DQUO/KC_DQT)
+            if keys.iter().any(|k| k.keycode == keycode && k.level == level) {
+                return;
+            }
+            keys.push(LocaleKey {
+                name: level.wrap(keycode),
+                keycode: keycode.to_string(),
+                level,
+                output: output.to_string(),
+                dead: false,
+            });
+        };
+        for c in 'A'..='Z' {
+            add(&format!("KC_{c}"), Level::Base, &c.to_string());
+        }
+        for c in '0'..='9' {
+            add(&format!("KC_{c}"), Level::Base, &c.to_string());
+        }
+        for (keycode, output) in US_SYMBOLS {
+            add(keycode, Level::Base, output);
+        }
+        for (_, keycode, output) in US_SHIFTED {
+            add(keycode, Level::Shift, output);
+        }
+        Locale {
+            name: String::from("us"),
+            keys,
+        }
+    }
+
+    pub fn from_header(name: &str, data: &str) -> Locale {
+        lazy_static! {
+            static ref DEFINE: Regex = Regex::new(r"^#define\s+(\w+)\s+(\S+)\s*//\s*(.*?)\s*$").unwrap();
+        }
+        let mut defined: HashMap<String, (String, Level)> = HashMap::new();
+        let mut keys = Vec::new();
+        for line in data.lines() {
+            let Some(define) = DEFINE.captures(line) else {
+                continue;
+            };
+            let Some((keycode, level)) = resolve(&define[2], &defined) else {
+                continue;
+            };
+            let (output, dead) = match define[3].strip_suffix("(dead)") {
+                Some(output) => (output.trim(), true),
+                None => (&define[3], false),
+            };
+            let output = match output {
+                "(backslash)" => "\\",
+                "(space)" => " ",
+                _ => output,
+            };
+            defined.insert(define[1].to_string(), (keycode.to_string(), level));
+            keys.push(LocaleKey {
+                name: define[1].to_string(),
+                keycode,
+                level,
+                output: output.to_string(),
+                dead,
+            });
+        }
+        Locale {
+            name: name.to_string(),
+            keys,
+        }
+    }
+
+    // `name` is either the name of a keymap_extras header (`french` for `keymap_french.h`), or
+    // the path to such a header. When `qmk_src_path` is not set, only the bundled headers are
+    // available.
+    pub fn load(name: &str, qmk_src_path: Option<&Path>) -> Result<Locale, &'static str> {
+        if name == "us" {
+            return Ok(Locale::us());
+        }
+        let path = Path::new(name);
+        if path.is_file() {
+            return match fs::read_to_string(path) {
+                Ok(data) => Ok(Locale::from_header(name, &data)),
+                Err(_) => Err("Unable to read file"),
+            };
+        }
+        if let Some(qmk_src_path) = qmk_src_path {
+            let path = qmk_src_path.join("quantum").join("keymap_extras").join(format!("keymap_{name}.h"));
+            return match fs::read_to_string(path) {
+                Ok(data) => Ok(Locale::from_header(name, &data)),
+                Err(_) => Err("Unable to read keymap_extras header"),
+            };
+        }
+        let data = match name {
+            "french" => include_str!("../../data/keymap_extras/keymap_french.h"),
+            "german" => include_str!("../../data/keymap_extras/keymap_german.h"),
+            "bepo" => include_str!("../../data/keymap_extras/keymap_bepo.h"),
+            "ergol" => include_str!("../../data/keymap_extras/keymap_ergol.h"),
+            _ => return Err("unknown host locale"),
+        };
+        Ok(Locale::from_header(name, data))
+    }
+
+    // Returns what the OS types when `keycode` is sent at `level`
+    pub fn output(&self, keycode: &str, level: Level) -> Option<&LocaleKey> {
+        self.keys.iter().find(|k| k.keycode == keycode && k.level == level)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LocaleKey> {
+        self.keys.iter().find(|k| k.name == name)
+    }
+}
+
+// Resolves the value of a keymap_extras define to a basic keycode and the level it's typed at.
+// `defined` contains the defines which were already parsed, as the shifted and AltGr keycodes are
+// defined in terms of the unshifted ones, e.g. `S(FR_AMPR)`.
+fn resolve(value: &str, defined: &HashMap<String, (String, Level)>) -> Option<(String, Level)> {
+    if let Some(inner) = value.strip_prefix("S(").and_then(|v| v.strip_suffix(')')) {
+        return resolve(inner, defined).map(|(keycode, level)| (keycode, level.with_shift()));
+    }
+    if let Some(inner) = value.strip_prefix("ALGR(").and_then(|v| v.strip_suffix(')')) {
+        return resolve(inner, defined).map(|(keycode, level)| (keycode, level.with_altgr()));
+    }
+    if value.starts_with("KC_") {
+        return Some((value.to_string(), Level::Base));
+    }
+    defined.get(value).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_locales_are_loaded() {
+        for name in ["french", "german", "bepo", "ergol"] {
+            assert!(!Locale::load(name, None).unwrap().keys.is_empty(), "{name}");
+        }
+        assert!(Locale::load("dvorak", None).is_err());
+    }
+
+    #[test]
+    fn bepo_keycodes_are_resolved() {
+        let bepo = Locale::load("bepo", None).unwrap();
+        let eacu = bepo.get("BP_EACU").unwrap();
+        assert_eq!((eacu.keycode.as_str(), eacu.level, eacu.output.as_str()), ("KC_W", Level::Base, "É"));
+        let one = bepo.get("BP_1").unwrap();
+        assert_eq!((one.keycode.as_str(), one.level, one.output.as_str()), ("KC_1", Level::Shift, "1"));
+        let bsls = bepo.output("KC_Z", Level::AltGr).unwrap();
+        assert_eq!((bsls.name.as_str(), bsls.output.as_str()), ("BP_BSLS", "\\"));
+        assert!(bepo.get("BP_DCIR").unwrap().dead);
+    }
+
+    #[test]
+    fn ergol_keycodes_are_resolved() {
+        let ergol = Locale::load("ergol", None).unwrap();
+        assert_eq!(ergol.output("KC_D", Level::Base).unwrap().output, "E");
+        assert_eq!(ergol.output("KC_C", Level::Shift).unwrap().output, "?");
+        let odk = ergol.get("ER_ODK").unwrap();
+        assert_eq!((odk.keycode.as_str(), odk.dead), ("KC_O", true));
+    }
+}
@@ -0,0 +1,461 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::keymap::Key;
+use crate::keymap::layer::LayerNames;
+use crate::qmk::keymap_c::macro_call;
+use crate::qmk::keymap_extras::US_SHIFTED;
+use crate::qmk::constants::{self, SpecKeycode, SpecRange};
+use crate::qmk::{keycodes, parser};
+
+// Ranges of the latest keycode spec, used when the QMK source tree is not available
+// (define, first value, last value)
+const RANGES: &[(&str, u16, u16)] = &[
+    ("QK_BASIC", 0x0000, 0x00FF),
+    ("QK_MODS", 0x0100, 0x1FFF),
+    ("QK_MOD_TAP", 0x2000, 0x3FFF),
+    ("QK_LAYER_TAP", 0x4000, 0x4FFF),
+    ("QK_LAYER_MOD", 0x5000, 0x51FF),
+    ("QK_TO", 0x5200, 0x521F),
+    ("QK_MOMENTARY", 0x5220, 0x523F),
+    ("QK_DEF_LAYER", 0x5240, 0x525F),
+    ("QK_TOGGLE_LAYER", 0x5260, 0x527F),
+    ("QK_ONE_SHOT_LAYER", 0x5280, 0x529F),
+    ("QK_ONE_SHOT_MOD", 0x52A0, 0x52BF),
+    ("QK_LAYER_TAP_TOGGLE", 0x52C0, 0x52DF),
+    ("QK_PERSISTENT_DEF_LAYER", 0x52E0, 0x52FF),
+    ("QK_SWAP_HANDS", 0x5600, 0x56FF),
+    ("QK_TAP_DANCE", 0x5700, 0x57FF),
+    ("QK_MAGIC", 0x7000, 0x70FF),
+    ("QK_MIDI", 0x7100, 0x71FF),
+    ("QK_SEQUENCER", 0x7200, 0x73FF),
+    ("QK_JOYSTICK", 0x7400, 0x743F),
+    ("QK_PROGRAMMABLE_BUTTON", 0x7440, 0x747F),
+    ("QK_AUDIO", 0x7480, 0x74BF),
+    ("QK_STENO", 0x74C0, 0x74FF),
+    ("QK_MACRO", 0x7700, 0x777F),
+    ("QK_LIGHTING", 0x7800, 0x78FF),
+    ("QK_QUANTUM", 0x7C00, 0x7DFF),
+    ("QK_KB", 0x7E00, 0x7E3F),
+    ("QK_USER", 0x7E40, 0x7FFF),
+    ("QK_UNICODE", 0x8000, 0xFFFF),
+    ("QK_UNICODEMAP", 0x8000, 0xBFFF),
+    ("QK_UNICODEMAP_PAIR", 0xC000, 0xFFFF),
+];
+
+// Layer keycodes taking a single layer argument: (range, function)
+const LAYER_RANGES: &[(&str, &str)] = &[
+    ("QK_TO", "TO"),
+    ("QK_MOMENTARY", "MO"),
+    ("QK_DEF_LAYER", "DF"),
+    ("QK_TOGGLE_LAYER", "TG"),
+    ("QK_ONE_SHOT_LAYER", "OSL"),
+    ("QK_LAYER_TAP_TOGGLE", "TT"),
+    ("QK_PERSISTENT_DEF_LAYER", "PDF"),
+];
+
+// Modifier functions, `LCTL(kc)`, `MEH(kc)`, ..., also used for the mod-taps `LCTL_T(kc)`.
+// (name, 5-bit modifier mask), the first name of a mask is the one used when decoding.
+const MODS: &[(&str, u16)] = &[
+    ("LCTL", 0x01), ("CTL", 0x01), ("C", 0x01),
+    ("LSFT", 0x02), ("SFT", 0x02), ("S", 0x02),
+    ("LCS", 0x03), ("C_S", 0x03),
+    ("LALT", 0x04), ("ALT", 0x04), ("A", 0x04), ("LOPT", 0x04), ("OPT", 0x04),
+    ("LCA", 0x05),
+    ("LSA", 0x06),
+    ("MEH", 0x07),
+    ("LGUI", 0x08), ("GUI", 0x08), ("G", 0x08), ("LCMD", 0x08), ("CMD", 0x08), ("LWIN", 0x08), ("WIN", 0x08),
+    ("LCG", 0x09),
+    ("SGUI", 0x0A), ("LSG", 0x0A), ("SCMD", 0x0A), ("SWIN", 0x0A),
+    ("LCSG", 0x0B),
+    ("LAG", 0x0C),
+    ("LCAG", 0x0D),
+    ("LSAG", 0x0E),
+    ("HYPR", 0x0F), ("ALL", 0x0F),
+    ("RCTL", 0x11),
+    ("RSFT", 0x12),
+    ("RCS", 0x13),
+    ("RALT", 0x14), ("ALGR", 0x14), ("ROPT", 0x14),
+    ("RCA", 0x15),
+    ("RSA", 0x16), ("SAGR", 0x16),
+    ("RGUI", 0x18), ("RCMD", 0x18), ("RWIN", 0x18),
+    ("RCG", 0x19),
+    ("RSG", 0x1A),
+    ("RCSG", 0x1B),
+    ("RAG", 0x1C),
+    ("RCAG", 0x1D),
+    ("RSAG", 0x1E),
+];
+
+// `MOD_xxx` flags of `OSM()`, `LM()` and `MT()`
+const MOD_FLAGS: &[(&str, u16)] = &[
+    ("MOD_LCTL", 0x01),
+    ("MOD_LSFT", 0x02),
+    ("MOD_LALT", 0x04),
+    ("MOD_LGUI", 0x08),
+    ("MOD_RCTL", 0x11),
+    ("MOD_RSFT", 0x12),
+    ("MOD_RALT", 0x14),
+    ("MOD_RGUI", 0x18),
+    ("MOD_MEH", 0x07),
+    ("MOD_HYPR", 0x0F),
+];
+
+fn mod_mask(name: &str) -> Option<u16> {
+    MODS.iter().find(|(n, _)| *n == name).map(|(_, mask)| *mask)
+}
+
+fn mod_name(mask: u16) -> Option<&'static str> {
+    MODS.iter().find(|(_, m)| *m == mask).map(|(name, _)| *name)
+}
+
+// `MOD_LCTL | MOD_LSFT`
+fn parse_mod_flags(flags: &str) -> Result<u16, &'static str> {
+    let mut mask = 0;
+    for flag in flags.split('|') {
+        let flag = flag.trim();
+        mask |= match MOD_FLAGS.iter().find(|(name, _)| *name == flag) {
+            Some((_, value)) => *value,
+            None => parse_number(flag).ok_or("unknown modifier flag")?,
+        };
+    }
+    Ok(mask)
+}
+
+fn mod_flags(mask: u16) -> String {
+    let prefix = match mask & 0x10 {
+        0 => "MOD_L",
+        _ => "MOD_R",
+    };
+    let flags: Vec<String> = ["CTL", "SFT", "ALT", "GUI"]
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| mask & (1 << i) != 0)
+        .map(|(_, m)| format!("{prefix}{m}"))
+        .collect();
+    match flags.is_empty() {
+        true => format!("0x{mask:02X}"),
+        false => flags.join(" | "),
+    }
+}
+
+// Decimal or `0x` hexadecimal number
+fn parse_number(s: &str) -> Option<u16> {
+    match s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => s.parse::<u16>().ok(),
+    }
+}
+
+// Converts between keycodes and the 16-bit values QMK stores in its keymaps, as defined by a
+// version of the keycode spec
+pub struct KeycodeTable {
+    // (define, first value, last value), smallest ranges first
+    ranges: Vec<SpecRange>,
+    // value -> keycode
+    keycodes: HashMap<u16, String>,
+    // keycode or alias -> value
+    values: HashMap<String, u16>,
+}
+
+impl KeycodeTable {
+    fn new(mut ranges: Vec<SpecRange>, keycodes: Vec<SpecKeycode>) -> KeycodeTable {
+        // the unicode map ranges are inside the unicode range, they have to be found first
+        ranges.sort_by_key(|(_, first, last)| (last - first, *first));
+        let mut table = KeycodeTable {
+            ranges,
+            keycodes: HashMap::new(),
+            values: HashMap::new(),
+        };
+        for (value, keycode, aliases) in keycodes {
+            for alias in aliases {
+                table.values.insert(alias, value);
+            }
+            table.values.insert(keycode.to_string(), value);
+            table.keycodes.insert(value, keycode);
+        }
+        table
+    }
+
+    // Basic keycodes and ranges of the latest spec only
+    pub fn builtin() -> KeycodeTable {
+        let ranges = RANGES.iter().map(|(define, first, last)| (define.to_string(), *first, *last)).collect();
+        KeycodeTable::new(ranges, keycodes::basic_keycodes())
+    }
+
+    // Keycode spec `version` of the QMK source tree at `base_path`
+    pub fn from_qmk_src(base_path: &Path, version: &str) -> Result<KeycodeTable, &'static str> {
+        let (ranges, keycodes) = constants::spec(base_path, version)?;
+        Ok(KeycodeTable::new(ranges, keycodes))
+    }
+
+    fn range(&self, define: &str) -> Result<(u16, u16), &'static str> {
+        self.ranges
+            .iter()
+            .find(|(d, _, _)| d == define)
+            .map(|(_, first, last)| (*first, *last))
+            .ok_or("keycode range not defined by this keycode spec")
+    }
+
+    // First value of the `define` range plus `offset`, checking that the result is in the range
+    fn in_range(&self, define: &str, offset: u16) -> Result<u16, &'static str> {
+        let (first, last) = self.range(define)?;
+        match offset <= last - first {
+            true => Ok(first + offset),
+            false => Err("keycode parameter out of range"),
+        }
+    }
+
+    // (keycode or alias, value) of all the named keycodes
+    pub fn names(&self) -> Vec<(&str, u16)> {
+        let mut names: Vec<(&str, u16)> = self.values.iter().map(|(name, value)| (name.as_str(), *value)).collect();
+        names.sort();
+        names
+    }
+
+    fn layer(&self, layer: &str, layer_names: &LayerNames, max: u16) -> Result<u16, &'static str> {
+        let idx = match parse_number(layer) {
+            Some(idx) => Some(idx),
+            None => layer_names.pretty_name(layer).and_then(|name| layer_names.index(&name)).map(|idx| idx as u16),
+        };
+        idx.filter(|idx| *idx <= max).ok_or("unknown layer or layer out of range")
+    }
+
+    // Basic keycode argument of `LT()`, `MT()`, ...
+    fn basic(&self, keycode: &str, layer_names: &LayerNames) -> Result<u16, &'static str> {
+        let value = self.encode_str(keycode, layer_names)?;
+        match value <= 0xFF {
+            true => Ok(value),
+            false => Err("keycode argument is not a basic keycode"),
+        }
+    }
+
+    // Encodes a QMK keycode expression: `KC_A`, `LCTL(LSFT(KC_A))`, `LT(_LOWER, KC_SPC)`, ...
+    pub fn encode_str(&self, keycode: &str, layer_names: &LayerNames) -> Result<u16, &'static str> {
+        lazy_static! {
+            static ref NUMBERED: Regex = Regex::new(r"^(QK_MACRO|MC|QK_KB|QK_USER)_(\d+)$").unwrap();
+        }
+        let keycode = keycode.trim();
+        if let Some(value) = self.values.get(keycode) {
+            return Ok(*value);
+        }
+        if let Some((_, unshifted, _)) = US_SHIFTED.iter().find(|(shifted, _, _)| *shifted == keycode) {
+            return Ok(0x0200 | self.encode_str(unshifted, layer_names)?);
+        }
+        if let Some(value) = parse_number(keycode) {
+            return Ok(value);
+        }
+        if let Some(numbered) = NUMBERED.captures(keycode) {
+            let define = match &numbered[1] {
+                "MC" => "QK_MACRO",
+                define => define,
+            };
+            return self.in_range(define, numbered[2].parse().map_err(|_| "keycode parameter out of range")?);
+        }
+
+        let (function, args) = macro_call(keycode).ok_or("unknown keycode")?;
+        let arg = |i: usize| args.get(i).map(|a| a.as_str()).ok_or("missing keycode argument");
+        let number = |i: usize| arg(i).and_then(|a| parse_number(a).ok_or("keycode argument is not a number"));
+        if let Some((define, _)) = LAYER_RANGES.iter().find(|(_, f)| *f == function) {
+            return self.in_range(define, self.layer(arg(0)?, layer_names, 0x1F)?);
+        }
+        match function.as_str() {
+            "LT" => {
+                let layer = self.layer(arg(0)?, layer_names, 0x0F)?;
+                self.in_range("QK_LAYER_TAP", layer << 8 | self.basic(arg(1)?, layer_names)?)
+            }
+            "LM" => {
+                let layer = self.layer(arg(0)?, layer_names, 0x0F)?;
+                self.in_range("QK_LAYER_MOD", layer << 5 | parse_mod_flags(arg(1)?)? & 0x1F)
+            }
+            "OSM" => self.in_range("QK_ONE_SHOT_MOD", parse_mod_flags(arg(0)?)? & 0x1F),
+            "MT" => {
+                let mods = parse_mod_flags(arg(0)?)? & 0x1F;
+                self.in_range("QK_MOD_TAP", mods << 8 | self.basic(arg(1)?, layer_names)?)
+            }
+            "TD" => self.in_range("QK_TAP_DANCE", number(0)?),
+            "UM" => self.in_range("QK_UNICODEMAP", number(0)?),
+            "UP" => {
+                let (lower, upper) = (number(0)?, number(1)?);
+                if lower > 0x7F || upper > 0x7F {
+                    return Err("keycode parameter out of range");
+                }
+                self.in_range("QK_UNICODEMAP_PAIR", upper << 7 | lower)
+            }
+            "UC" => self.in_range("QK_UNICODE", number(0)?),
+            "SH_T" => self.in_range("QK_SWAP_HANDS", self.basic(arg(0)?, layer_names)?),
+            _ => match (function.strip_suffix("_T").and_then(mod_mask), mod_mask(&function)) {
+                (Some(mods), _) => self.in_range("QK_MOD_TAP", mods << 8 | self.basic(arg(0)?, layer_names)?),
+                // modifiers can be nested, `LCTL(LSFT(KC_A))`
+                (None, Some(mods)) => {
+                    let value = self.encode_str(arg(0)?, layer_names)?;
+                    if value > 0x1FFF || (value > 0xFF && (value >> 8 & 0x10) != (mods & 0x10)) {
+                        return Err("modifiers can only be applied to basic keycodes");
+                    }
+                    Ok(value | mods << 8)
+                }
+                (None, None) => Err("unknown keycode"),
+            },
+        }
+    }
+
+    pub fn encode(&self, key: &Key, layer_names: &LayerNames) -> Result<u16, &'static str> {
+        self.encode_str(&parser::keycode(key), layer_names)
+    }
+
+    fn layer_id(layer: u16, layer_names: &LayerNames) -> String {
+        match layer_names.names().get(layer as usize) {
+            Some(name) => parser::layer_id(name),
+            None => layer.to_string(),
+        }
+    }
+
+    // Decodes `value` to a QMK keycode expression, values which are not defined by the spec are
+    // written in hexadecimal
+    pub fn decode_str(&self, value: u16, layer_names: &LayerNames) -> String {
+        if let Some(keycode) = self.keycodes.get(&value) {
+            return keycode.to_string();
+        }
+        let basic = |value: u16| self.decode_str(value & 0xFF, layer_names);
+        for (define, first, last) in &self.ranges {
+            if value < *first || value > *last {
+                continue;
+            }
+            let offset = value - first;
+            if let Some((_, function)) = LAYER_RANGES.iter().find(|(d, _)| d == define) {
+                return format!("{function}({})", Self::layer_id(offset, layer_names));
+            }
+            match define.as_str() {
+                "QK_MODS" => {
+                    let mods = value >> 8 & 0x1F;
+                    if mods & 0x0F == 0 {
+                        break;
+                    }
+                    if mods == 0x02 {
+                        let shifted = US_SHIFTED.iter().find(|(_, unshifted, _)| self.values.get(*unshifted) == Some(&(value & 0xFF)));
+                        if let Some((shifted, _, _)) = shifted {
+                            return shifted.to_string();
+                        }
+                    }
+                    return match mod_name(mods) {
+                        Some(name) => format!("{name}({})", basic(value)),
+                        // no function for this combination, nest the modifiers
+                        None => {
+                            let mut keycode = basic(value);
+                            let side = mods & 0x10;
+                            for bit in [0x08, 0x04, 0x02, 0x01] {
+                                if mods & bit != 0 {
+                                    keycode = format!("{}({keycode})", mod_name(side | bit).unwrap_or_default());
+                                }
+                            }
+                            keycode
+                        }
+                    };
+                }
+                "QK_MOD_TAP" => {
+                    let mods = value >> 8 & 0x1F;
+                    return match mod_name(mods) {
+                        Some(name) => format!("{name}_T({})", basic(value)),
+                        None => format!("MT({}, {})", mod_flags(mods), basic(value)),
+                    };
+                }
+                "QK_LAYER_TAP" => return format!("LT({}, {})", Self::layer_id(offset >> 8 & 0x0F, layer_names), basic(value)),
+                "QK_LAYER_MOD" => return format!("LM({}, {})", Self::layer_id(offset >> 5 & 0x0F, layer_names), mod_flags(offset & 0x1F)),
+                "QK_ONE_SHOT_MOD" => return format!("OSM({})", mod_flags(offset)),
+                "QK_SWAP_HANDS" if offset < 0xF0 => return format!("SH_T({})", basic(value)),
+                "QK_TAP_DANCE" => return format!("TD({offset})"),
+                "QK_MACRO" => return format!("QK_MACRO_{offset}"),
+                "QK_KB" => return format!("QK_KB_{offset}"),
+                "QK_USER" => return format!("QK_USER_{offset}"),
+                "QK_UNICODEMAP" => return format!("UM({offset})"),
+                "QK_UNICODEMAP_PAIR" => return format!("UP({}, {})", offset & 0x7F, offset >> 7 & 0x7F),
+                "QK_UNICODE" => return format!("UC(0x{offset:04X})"),
+                _ => break,
+            }
+        }
+        format!("0x{value:04X}")
+    }
+
+    pub fn decode(&self, value: u16, layer_names: &LayerNames) -> Key {
+        parser::parse_keycode(&self.decode_str(value, layer_names), layer_names)
+    }
+}
+
+// Operations of the `keycodes` subcommand
+pub enum Operation {
+    // keycode expressions
+    Encode(Vec<String>),
+    // decimal or hexadecimal values
+    Decode(Vec<String>),
+    EncodeKeymap,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer_names() -> LayerNames {
+        LayerNames::new(["Base", "Lower", "Raise", "Adjust"].iter().map(|name| name.to_string()).collect())
+    }
+
+    // The keycodes are encoded from the keys they are parsed to, as for the keymaps
+    fn encode(table: &KeycodeTable, keycode: &str, layer_names: &LayerNames) -> Result<u16, &'static str> {
+        table.encode(&parser::parse_keycode(keycode, layer_names), layer_names)
+    }
+
+    #[test]
+    fn named_keycodes_are_encoded_to_their_value() {
+        let table = KeycodeTable::builtin();
+        let layer_names = layer_names();
+        for (name, value) in table.names() {
+            assert_eq!(encode(&table, name, &layer_names), Ok(value), "{name}");
+        }
+    }
+
+    #[test]
+    fn all_values_round_trip() {
+        let table = KeycodeTable::builtin();
+        for layer_names in [layer_names(), LayerNames::new(Vec::new())] {
+            for value in 0..=u16::MAX {
+                let key = table.decode(value, &layer_names);
+                assert_eq!(table.encode(&key, &layer_names), Ok(value), "0x{value:04X} decoded as {}", parser::keycode(&key));
+            }
+        }
+    }
+
+    #[test]
+    fn keycode_expressions() {
+        let table = KeycodeTable::builtin();
+        let layer_names = layer_names();
+        for (keycode, value) in [
+            ("LCTL(LSFT(KC_A))", 0x0304),
+            ("KC_EXLM", 0x021E),
+            ("LT(_LOWER, KC_SPC)", 0x412C),
+            ("LT(3, KC_ENT)", 0x4328),
+            ("MO(_RAISE)", 0x5222),
+            ("TG(1)", 0x5261),
+            ("LSFT_T(KC_D)", 0x2207),
+            ("OSM(MOD_LSFT)", 0x52A2),
+            ("TD(2)", 0x5702),
+            ("QK_MACRO_1", 0x7701),
+            ("UM(5)", 0x8005),
+            ("UP(1, 2)", 0xC101),
+            ("UC(0x00E9)", 0x80E9),
+        ] {
+            assert_eq!(encode(&table, keycode, &layer_names), Ok(value), "{keycode}");
+        }
+        assert_eq!(table.decode_str(0x4328, &layer_names), "LT(_ADJUST, KC_ENTER)");
+        assert_eq!(table.decode_str(0x5261, &LayerNames::new(Vec::new())), "TG(1)");
+    }
+
+    #[test]
+    fn invalid_keycodes() {
+        let table = KeycodeTable::builtin();
+        let layer_names = layer_names();
+        assert!(encode(&table, "LT(16, KC_A)", &layer_names).is_err());
+        assert!(encode(&table, "LT(_LOWER, LCTL(KC_A))", &layer_names).is_err());
+        assert!(encode(&table, "UP(128, 0)", &layer_names).is_err());
+        assert!(encode(&table, "KC_NOPE", &layer_names).is_err());
+    }
+}
@@ -1,22 +1,97 @@
 use crate::keymap::layer::LayerNames;
-use crate::keymap::Key;
+use crate::keymap::{Combo, Encoder, Key, Macro, MacroStep};
 use crate::keymap::Keymap;
+use crate::qmk::format::{self, Grid};
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+// Combos are not part of the QMK JSON format, this is the format used when they are added to it
+#[derive(Serialize, Deserialize)]
+struct RawCombo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    keys: Vec<String>,
+    output: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    layers: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawEncoder {
+    ccw: String,
+    cw: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawMacroStep {
+    Text(String),
+    Action {
+        action: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        keycodes: Vec<String>,
+        #[serde(default, skip_serializing_if = "is_zero")]
+        duration: u64,
+    },
+}
+
+fn is_zero(duration: &u64) -> bool {
+    *duration == 0
+}
+
+impl RawMacroStep {
+    fn into_step(self) -> Option<MacroStep> {
+        match self {
+            RawMacroStep::Text(text) => Some(MacroStep::Text(text)),
+            RawMacroStep::Action { action, keycodes, duration } => match action.as_str() {
+                "tap" => Some(MacroStep::Tap(keycodes)),
+                "down" => Some(MacroStep::Down(keycodes)),
+                "up" => Some(MacroStep::Up(keycodes)),
+                "delay" => Some(MacroStep::Delay(duration)),
+                _ => None,
+            },
+        }
+    }
+
+    fn from_step(step: &MacroStep) -> RawMacroStep {
+        let action = |action: &str, keycodes: &[String], duration: u64| RawMacroStep::Action {
+            action: action.to_string(),
+            keycodes: keycodes.to_vec(),
+            duration,
+        };
+        match step {
+            MacroStep::Text(text) => RawMacroStep::Text(text.to_string()),
+            MacroStep::Tap(keycodes) => action("tap", keycodes, 0),
+            MacroStep::Down(keycodes) => action("down", keycodes, 0),
+            MacroStep::Up(keycodes) => action("up", keycodes, 0),
+            MacroStep::Delay(duration) => action("delay", &[], *duration),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 struct RawKeymap {
-    keymap: String,
     keyboard: String,
+    keymap: String,
     layout: String,
     layers: Vec<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    combos: Vec<RawCombo>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    macros: Vec<Vec<RawMacroStep>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    encoders: Vec<Vec<RawEncoder>>,
+    // `version`, `author`, `config`, `notes`, ...
+    #[serde(flatten)]
+    extra_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 impl RawKeymap {
-    fn to_keymap(self, num_rows: usize, layer_names: &LayerNames) -> Result<Keymap, &'static str> {
+    fn into_keymap(self, num_rows: usize, layer_names: &LayerNames) -> Result<Keymap, &'static str> {
         let mut layers: Vec<Vec<Key>> = Vec::new();
         let mut num_keys: usize = 0;
 
@@ -36,10 +111,32 @@ impl RawKeymap {
             }
             layers.push(keys);
         }
-        if num_keys % num_rows != 0 {
+        if !num_keys.is_multiple_of(num_rows) {
             panic!("number of keys ({num_keys}) must be dividable by the number of rows ({num_rows})");
         }
 
+        let mut combos = Vec::new();
+        for (i, combo) in self.combos.into_iter().enumerate() {
+            combos.push(Combo {
+                name: combo.name.unwrap_or(format!("combo{i}")),
+                keys: combo.keys.iter().map(|k| parse_keycode(k, layer_names)).collect(),
+                result: parse_keycode(&combo.output, layer_names),
+                layers: combo.layers.map(|layers| layers.iter().map(|l| layer_names.pretty_name(l).unwrap_or(l.to_string())).collect()),
+                positions: Vec::new(),
+            });
+        }
+
+        let macros = self.macros.into_iter().map(|steps| Macro {
+            steps: steps.into_iter().filter_map(|step| step.into_step()).collect(),
+        }).collect();
+
+        let encoders = self.encoders.iter().map(|layer| {
+            layer.iter().map(|encoder| Encoder {
+                ccw: parse_keycode(&encoder.ccw, layer_names),
+                cw: parse_keycode(&encoder.cw, layer_names),
+            }).collect()
+        }).collect();
+
         Ok(Keymap {
             keymap: self.keymap,
             keyboard: self.keyboard,
@@ -47,8 +144,47 @@ impl RawKeymap {
             num_columns: num_keys/num_rows,
             layout: self.layout,
             layers,
+            tap_dances: HashMap::new(),
+            combos,
+            macros,
+            custom_keycodes: HashMap::new(),
+            key_overrides: Vec::new(),
+            encoders,
+            extra_fields: self.extra_fields,
+            info_json: None,
+            source_keys: Vec::new(),
         })
     }
+
+    // Combos defined in keymap.c are written too if they were added to `keymap`
+    fn from_keymap(keymap: &Keymap) -> RawKeymap {
+        let keycodes = |keys: &[Key]| keys.iter().map(keycode).collect();
+        let combos = keymap.combos.iter().enumerate().map(|(i, combo)| RawCombo {
+            // default names given when the keymap is read, see `keymap_from_str`, are not written
+            name: Some(combo.name.to_string()).filter(|name| *name != format!("combo{i}")),
+            keys: keycodes(&combo.keys),
+            output: keycode(&combo.result),
+            layers: combo.layers.as_ref().map(|layers| layers.iter().map(|l| layer_id(l)).collect()),
+        }).collect();
+        let macros = keymap.macros.iter().map(|m| m.steps.iter().map(RawMacroStep::from_step).collect()).collect();
+        let mut encoders: Vec<Vec<RawEncoder>> = keymap.encoders.iter().map(|layer| {
+            layer.iter().map(|encoder| RawEncoder { ccw: keycode(&encoder.ccw), cw: keycode(&encoder.cw) }).collect()
+        }).collect();
+        if encoders.iter().all(|layer| layer.is_empty()) {
+            encoders.clear();
+        }
+
+        RawKeymap {
+            keyboard: keymap.keyboard.to_string(),
+            keymap: keymap.keymap.to_string(),
+            layout: keymap.layout.to_string(),
+            layers: keymap.layers.iter().map(|layer| keycodes(layer)).collect(),
+            combos,
+            macros,
+            encoders,
+            extra_fields: keymap.extra_fields.clone(),
+        }
+    }
 }
 
 pub fn keymap_from_file(filename: &PathBuf, num_rows: usize, layer_names: &LayerNames) -> Result<Keymap, &'static str> {
@@ -59,18 +195,89 @@ pub fn keymap_from_file(filename: &PathBuf, num_rows: usize, layer_names: &Layer
         Err(_) => return Err("Unable to read file"),
     };
 
-    match serde_json::from_str::<RawKeymap>(&data) {
-        Ok(raw_keymap) => raw_keymap.to_keymap(num_rows, layer_names),
+    keymap_from_str(&data, num_rows, layer_names)
+}
+
+pub fn keymap_from_str(data: &str, num_rows: usize, layer_names: &LayerNames) -> Result<Keymap, &'static str> {
+    match serde_json::from_str::<RawKeymap>(data) {
+        Ok(raw_keymap) => raw_keymap.into_keymap(num_rows, layer_names),
         // Err(_) => Err(format!("Unable to parse file {}", filename)),
         Err(_) => Err("Unable to parse file"),
     }
 }
 
+// Serializes `keymap` with its layers laid out in rows of `num_columns` keys
+pub fn keymap_to_json(keymap: &Keymap) -> Result<String, &'static str> {
+    let num_keys = keymap.num_rows * keymap.num_columns;
+    keymap_to_json_with_grid(keymap, &Grid::from_columns(num_keys, keymap.num_columns))
+}
+
+pub fn keymap_to_json_with_grid(keymap: &Keymap, grid: &Grid) -> Result<String, &'static str> {
+    if grid.num_keys() != keymap.num_rows * keymap.num_columns {
+        return Err("the layout does not have the same number of keys as the keymap");
+    }
+    let mut raw_keymap = RawKeymap::from_keymap(keymap);
+    // the layers are serialized separately, serde_json can only put each keycode on its own
+    // line or the whole layer on a single line
+    let layers = std::mem::take(&mut raw_keymap.layers);
+    let mut data = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut data, formatter);
+    if raw_keymap.serialize(&mut serializer).is_err() {
+        return Err("Unable to serialize keymap");
+    }
+    let json = String::from_utf8(data).map_err(|_| "Unable to serialize keymap")?;
+    let mut json = json.replacen("\n    \"layers\": []", &format!("\n    \"layers\": {}", format::format_layers(&layers, grid)), 1);
+    json.push('\n');
+    Ok(json)
+}
+
+// Writes `keymap` as a QMK keymap.json file which can be built with `qmk compile`
+pub fn keymap_to_file(keymap: &Keymap, filename: &Path) -> Result<(), &'static str> {
+    let json = keymap_to_json(keymap)?;
+    match fs::write(filename, json) {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Unable to write file"),
+    }
+}
+
+// Layer identifier used in keycodes, `Lower` -> `_LOWER`
+pub fn layer_id(layer: &str) -> String {
+    format!("_{}", layer.to_uppercase())
+}
+
+// Returns the canonical QMK keycode string of `key`, this is the reverse of `from_str`
+pub fn keycode(key: &Key) -> String {
+    match key {
+        Key::No => String::from("KC_NO"),
+        Key::Trans => String::from("KC_TRNS"),
+        Key::BasicKey(key) => format!("KC_{key}"),
+        Key::SpecialKey(key) => key.to_string(),
+        Key::Unicode(lower, None) => format!("UM({lower})"),
+        Key::Unicode(lower, Some(upper)) => format!("UP({lower}, {upper})"),
+        Key::LayerTap { key, index: Some(idx), .. } => format!("LT({idx}, {key})"),
+        Key::LayerTap { layer, key, index: None } => format!("LT({}, {key})", layer_id(layer)),
+        Key::ModTap { modifier, key } => format!("{modifier}_T(KC_{key})"),
+        Key::ModKey { modifier, key } => format!("{modifier}(KC_{key})"),
+        Key::LayerChange { action, index: Some(idx), .. } => format!("{action}({idx})"),
+        Key::LayerChange { action, layer, index: None } => format!("{action}({})", layer_id(layer)),
+        Key::TapDance(name) => format!("TD({name})"),
+        Key::Macro(idx) => format!("QK_MACRO_{idx}"),
+        Key::Custom(name) => name.to_string(),
+        Key::Unknown(key) => key.to_string(),
+    }
+}
+
 fn parse_kc(key_str: &str) -> Option<Key> {
-    match key_str.strip_prefix("KC_") {
-        Some(key) => Some(Key::BasicKey(key.to_string())),
-        None => None,
+    key_str.strip_prefix("KC_").map(|key| Key::BasicKey(key.to_string()))
+}
+
+fn parse_macro(key_str: &str) -> Option<Key> {
+    lazy_static! {
+        static ref MACRO: Regex = Regex::new(r"^(QK_MACRO_|MC_)(\d+)$").unwrap();
     }
+    let m = MACRO.captures(key_str)?;
+    m[2].parse::<usize>().ok().map(Key::Macro)
 }
 
 fn parse_special(key_str: &str) -> Option<Key> {
@@ -99,15 +306,25 @@ fn parse_unicode(key_str: &str) -> Option<Key> {
     None
 }
 
+// Layer argument of a layer keycode, by name, `_LOWER`, or by number, `3`. Layers referred to by
+// number are named with `layer_names` too, `layer3` if they have no name.
+fn layer_argument(layer: &str, layer_names: &LayerNames) -> Option<(String, Option<usize>)> {
+    match layer.parse::<usize>() {
+        Ok(idx) => Some((layer_names.get(idx), Some(idx))),
+        Err(_) => layer_names.pretty_name(layer).map(|name| (name, None)),
+    }
+}
+
 fn parse_layertap(key_str: &str, layer_names: &LayerNames) -> Option<Key> {
     lazy_static! {
         static ref LT: Regex = Regex::new(r"^LT\(([\w_]+), *([\w_]+)\)$").unwrap();
     }
     if let Some(lt) = LT.captures(key_str) {
-        if let Some(layer) = layer_names.pretty_name(&lt[1]) {
+        if let Some((layer, index)) = layer_argument(&lt[1], layer_names) {
             return Some(Key::LayerTap {
-                layer: layer.to_string(),
+                layer,
                 key: lt[2].to_string(),
+                index,
             });
         }
     }
@@ -129,19 +346,27 @@ fn parse_modtap(key_str: &str) -> Option<Key> {
 
 fn parse_layer_change(key_str: &str, layer_names: &LayerNames) -> Option<Key> {
     lazy_static! {
-        static ref LAYER_CHANGE: Regex = Regex::new(r"^(MO|PDF)\(([A-Z_]+)\)$").unwrap();
+        static ref LAYER_CHANGE: Regex = Regex::new(r"^(MO|PDF|DF|TG|TO|TT|OSL)\(([A-Z_]+|\d+)\)$").unwrap();
     }
     if let Some(lc) = LAYER_CHANGE.captures(key_str) {
-        if let Some(layer) = layer_names.pretty_name(&lc[2]) {
+        if let Some((layer, index)) = layer_argument(&lc[2], layer_names) {
             return Some(Key::LayerChange {
-                _action: lc[1].to_string(),
-                layer: layer.to_string(),
+                action: lc[1].to_string(),
+                layer,
+                index,
             });
         }
     }
     None
 }
 
+fn parse_tapdance(key_str: &str) -> Option<Key> {
+    lazy_static! {
+        static ref TD: Regex = Regex::new(r"^TD\(([\w_]+)\)$").unwrap();
+    }
+    TD.captures(key_str).map(|td| Key::TapDance(td[1].to_string()))
+}
+
 // This method needs work:
 // - the parsed modifier needs to be validated against a list of known modifiers
 //   https://github.com/qmk/qmk_firmware/blob/master/docs/feature_advanced_keycodes.modifier
@@ -160,6 +385,14 @@ fn parse_modkey(key_str: &str) -> Option<Key> {
     None
 }
 
+// Parses a single QMK keycode, for the keycodes found outside of the keymap layers
+pub fn parse_keycode(key_str: &str, layer_names: &LayerNames) -> Key {
+    match from_str(key_str, layer_names) {
+        Some(key) => key,
+        None => Key::Unknown(key_str.to_string()),
+    }
+}
+
 fn from_str(key_str: &str, layer_names: &LayerNames) -> Option<Key> {
     if key_str == "KC_TRNS" {
         return Some(Key::Trans);
@@ -170,6 +403,9 @@ fn from_str(key_str: &str, layer_names: &LayerNames) -> Option<Key> {
     if let Some(key) = parse_kc(key_str) {
         return Some(key);
     }
+    if let Some(key) = parse_macro(key_str) {
+        return Some(key);
+    }
     if let Some(key) = parse_special(key_str) {
         return Some(key);
     }
@@ -185,11 +421,13 @@ fn from_str(key_str: &str, layer_names: &LayerNames) -> Option<Key> {
     if let Some(key) = parse_layer_change(key_str, layer_names) {
         return Some(key);
     }
+    if let Some(key) = parse_tapdance(key_str) {
+        return Some(key);
+    }
     // Keep this last as this is prone to false positives
     if let Some(key) = parse_modkey(key_str) {
         return Some(key);
     }
-    println!("unknown key {key_str}");
     Some(Key::Unknown(key_str.to_string()))
     //None
 }
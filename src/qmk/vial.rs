@@ -0,0 +1,319 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{Combo, Encoder, Key, Keymap, Macro, MacroStep, TapDance};
+use crate::qmk::numeric::KeycodeTable;
+use crate::qmk::parser;
+
+// Vial .vil file, the layers are in the matrix order: layer, row, column
+#[derive(Deserialize)]
+struct RawVil {
+    layout: Vec<Vec<Vec<Value>>>,
+    #[serde(default)]
+    encoder_layout: Vec<Vec<Vec<Value>>>,
+    // [["text", "abc"], ["tap", "KC_A", "KC_B"], ["delay", 100], ...] for each macro
+    #[serde(default, rename = "macro")]
+    macros: Vec<Vec<Vec<Value>>>,
+    // [on tap, on hold, on double tap, on tap + hold, tapping term]
+    #[serde(default)]
+    tap_dance: Vec<Vec<Value>>,
+    // [key 1, key 2, key 3, key 4, output]
+    #[serde(default)]
+    combo: Vec<Vec<Value>>,
+}
+
+// VIA backup, the layers are flat arrays in the matrix order
+#[derive(Deserialize)]
+struct RawVia {
+    #[serde(default)]
+    name: String,
+    layers: Vec<Vec<Value>>,
+    #[serde(default)]
+    encoders: Vec<Vec<Vec<Value>>>,
+    // "Hello{KC_ENT}", see `via_macro`
+    #[serde(default)]
+    macros: Vec<String>,
+}
+
+// Whether `data` is a Vial .vil file or a VIA backup rather than a QMK JSON keymap
+pub fn is_vial(data: &str) -> bool {
+    let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(data) else {
+        return false;
+    };
+    fields.contains_key("vial_protocol") || fields.contains_key("vendorProductId") || (fields.contains_key("layout") && fields.contains_key("uid"))
+}
+
+// Converts a Vial or VIA keycode to its QMK name. Numbers, and the hexadecimal values VIA writes
+// for the keycodes it doesn't know, are decoded with `table`. Layers are referenced by index.
+fn keycode(value: &Value, layer_names: &LayerNames, table: &KeycodeTable) -> String {
+    lazy_static! {
+        static ref MACRO: Regex = Regex::new(r"^(?:M|MACRO)\(?(\d+)\)?$").unwrap();
+        static ref USER: Regex = Regex::new(r"^USER(\d+)$").unwrap();
+        static ref LAYER: Regex = Regex::new(r"\b(LT|LM|MO|TG|TO|TT|OSL|DF|PDF)\((\d+)").unwrap();
+    }
+    let keycode = match value {
+        Value::Number(n) => match n.as_u64().and_then(|n| u16::try_from(n).ok()) {
+            Some(n) => table.decode_str(n, layer_names),
+            None => String::from("KC_NO"),
+        },
+        Value::String(s) if s.starts_with("0x") => match u16::from_str_radix(&s[2..], 16) {
+            Ok(n) => table.decode_str(n, layer_names),
+            Err(_) => s.to_string(),
+        },
+        Value::String(s) => s.to_string(),
+        _ => String::from("KC_NO"),
+    };
+    if let Some(m) = MACRO.captures(&keycode) {
+        return format!("QK_MACRO_{}", &m[1]);
+    }
+    if let Some(m) = USER.captures(&keycode) {
+        return format!("QK_USER_{}", m[1].parse::<usize>().unwrap_or_default());
+    }
+    LAYER
+        .replace_all(&keycode, |c: &regex::Captures| {
+            let layer = c[2].parse::<usize>().ok().and_then(|idx| layer_names.names().get(idx));
+            match layer {
+                Some(name) => format!("{}({}", &c[1], parser::layer_id(name)),
+                None => c[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+fn key(value: &Value, layer_names: &LayerNames, table: &KeycodeTable) -> Key {
+    parser::parse_keycode(&keycode(value, layer_names, table), layer_names)
+}
+
+// `KC_NO` actions are not set
+fn optional_key(value: Option<&Value>, layer_names: &LayerNames, table: &KeycodeTable) -> Option<Key> {
+    value.map(|v| key(v, layer_names, table)).filter(|k| *k != Key::No)
+}
+
+fn encoders(raw: &[Vec<Vec<Value>>], layer_names: &LayerNames, table: &KeycodeTable) -> Vec<Vec<Encoder>> {
+    raw.iter()
+        .map(|layer| {
+            layer
+                .iter()
+                .filter(|e| e.len() == 2)
+                .map(|e| Encoder { ccw: key(&e[0], layer_names, table), cw: key(&e[1], layer_names, table) })
+                .collect()
+        })
+        .collect()
+}
+
+fn vil_macro(actions: &[Vec<Value>], layer_names: &LayerNames, table: &KeycodeTable) -> Macro {
+    let mut steps = Vec::new();
+    for action in actions {
+        let Some((Value::String(kind), args)) = action.split_first() else {
+            continue;
+        };
+        let keycodes = || args.iter().map(|a| keycode(a, layer_names, table)).collect();
+        match kind.as_str() {
+            "text" => steps.push(MacroStep::Text(args.iter().filter_map(|a| a.as_str()).collect())),
+            "tap" => steps.push(MacroStep::Tap(keycodes())),
+            "down" => steps.push(MacroStep::Down(keycodes())),
+            "up" => steps.push(MacroStep::Up(keycodes())),
+            "delay" => steps.push(MacroStep::Delay(args.first().and_then(|a| a.as_u64()).unwrap_or_default())),
+            _ => (),
+        }
+    }
+    Macro { steps }
+}
+
+// VIA macros are text with `{KC_A}` or `{KC_LCTL,KC_C}` taps, `{+KC_LSFT}` presses,
+// `{-KC_LSFT}` releases and `{100}` delays
+fn via_macro(text: &str) -> Macro {
+    lazy_static! {
+        static ref TAG: Regex = Regex::new(r"\{([+-]?)([^}]*)\}").unwrap();
+    }
+    let mut steps = Vec::new();
+    let mut last = 0;
+    for tag in TAG.captures_iter(text) {
+        let all = tag.get(0).unwrap();
+        if all.start() > last {
+            steps.push(MacroStep::Text(text[last..all.start()].to_string()));
+        }
+        last = all.end();
+        let keycodes: Vec<String> = tag[2].split(',').map(|k| k.trim().to_string()).filter(|k| !k.is_empty()).collect();
+        match (&tag[1], tag[2].trim().parse::<u64>()) {
+            ("+", _) => steps.push(MacroStep::Down(keycodes)),
+            ("-", _) => steps.push(MacroStep::Up(keycodes)),
+            (_, Ok(delay)) => steps.push(MacroStep::Delay(delay)),
+            _ => steps.push(MacroStep::Tap(keycodes)),
+        }
+    }
+    if last < text.len() {
+        steps.push(MacroStep::Text(text[last..].to_string()));
+    }
+    Macro { steps }
+}
+
+fn keymap(layers: Vec<Vec<Key>>, num_rows: usize, name: &str) -> Result<Keymap, &'static str> {
+    let num_keys = layers.first().map_or(0, |layer| layer.len());
+    if num_keys == 0 || layers.iter().any(|layer| layer.len() != num_keys) {
+        return Err("inconsistent layer lengths");
+    }
+    let num_rows = if num_rows > 0 && num_keys.is_multiple_of(num_rows) { num_rows } else { 1 };
+    Ok(Keymap {
+        keymap: name.to_string(),
+        // no QMK keyboard, the keymap is drawn as an ortho layout
+        keyboard: String::new(),
+        layout: String::new(),
+        num_rows,
+        num_columns: num_keys / num_rows,
+        layers,
+        tap_dances: HashMap::new(),
+        combos: Vec::new(),
+        macros: Vec::new(),
+        custom_keycodes: HashMap::new(),
+        key_overrides: Vec::new(),
+        encoders: Vec::new(),
+        extra_fields: serde_json::Map::new(),
+        info_json: None,
+        source_keys: Vec::new(),
+    })
+}
+
+// The .vil matrix positions without a key (-1) are kept as `KC_NO` so that the keys stay in
+// their row and column
+fn keymap_from_vil(raw: RawVil, layer_names: &LayerNames, table: &KeycodeTable) -> Result<Keymap, &'static str> {
+    let num_columns = raw.layout.iter().flatten().map(|row| row.len()).max().unwrap_or(0);
+    let num_rows = raw.layout.first().map_or(0, |layer| layer.len());
+    let layers: Vec<Vec<Key>> = raw
+        .layout
+        .iter()
+        .map(|layer| {
+            layer
+                .iter()
+                .flat_map(|row| (0..num_columns).map(move |c| row.get(c)))
+                .map(|value| match value {
+                    Some(Value::Number(n)) if n.as_i64() == Some(-1) => Key::No,
+                    Some(value) => key(value, layer_names, table),
+                    None => Key::No,
+                })
+                .collect()
+        })
+        .collect();
+    let mut keymap = keymap(layers, num_rows, "vial")?;
+    keymap.encoders = encoders(&raw.encoder_layout, layer_names, table);
+    keymap.macros = raw.macros.iter().map(|m| vil_macro(m, layer_names, table)).collect();
+    for (i, td) in raw.tap_dance.iter().enumerate() {
+        let tap_dance = TapDance {
+            tap: optional_key(td.first(), layer_names, table),
+            hold: optional_key(td.get(1), layer_names, table),
+            double_tap: optional_key(td.get(2), layer_names, table),
+            function: None,
+        };
+        if tap_dance.tap.is_some() || tap_dance.hold.is_some() || tap_dance.double_tap.is_some() {
+            keymap.tap_dances.insert(i.to_string(), tap_dance);
+        }
+    }
+    for (i, combo) in raw.combo.iter().enumerate() {
+        let Some((output, keys)) = combo.split_last() else {
+            continue;
+        };
+        let Some(result) = optional_key(Some(output), layer_names, table) else {
+            continue;
+        };
+        keymap.combos.push(Combo {
+            name: format!("combo{i}"),
+            keys: keys.iter().filter_map(|k| optional_key(Some(k), layer_names, table)).collect(),
+            result,
+            layers: None,
+            positions: Vec::new(),
+        });
+    }
+    Ok(keymap)
+}
+
+// VIA backups don't have the matrix size, the layers are split in `num_rows` rows
+fn keymap_from_via(raw: RawVia, num_rows: usize, layer_names: &LayerNames, table: &KeycodeTable) -> Result<Keymap, &'static str> {
+    let layers = raw
+        .layers
+        .iter()
+        .map(|layer| layer.iter().map(|value| key(value, layer_names, table)).collect())
+        .collect();
+    let mut keymap = keymap(layers, num_rows, &raw.name)?;
+    keymap.encoders = encoders(&raw.encoders, layer_names, table);
+    keymap.macros = raw.macros.iter().map(|m| via_macro(m)).collect();
+    Ok(keymap)
+}
+
+// Reads a Vial .vil file or a VIA backup, numeric keycodes are decoded with `table`
+pub fn keymap_from_str(data: &str, num_rows: usize, layer_names: &LayerNames, table: &KeycodeTable) -> Result<Keymap, &'static str> {
+    let value: Value = serde_json::from_str(data).map_err(|_| "Unable to parse file")?;
+    if value.get("layout").is_some() {
+        let raw: RawVil = serde_json::from_value(value).map_err(|_| "Unable to parse Vial file")?;
+        return keymap_from_vil(raw, layer_names, table);
+    }
+    let raw: RawVia = serde_json::from_value(value).map_err(|_| "Unable to parse VIA backup")?;
+    keymap_from_via(raw, num_rows, layer_names, table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer_names() -> LayerNames {
+        LayerNames::new(["Base", "Lower"].iter().map(|name| name.to_string()).collect())
+    }
+
+    #[test]
+    fn vil_files_are_parsed() {
+        let data = r#"{
+            "version": 1, "uid": 1234, "vial_protocol": 6,
+            "layout": [
+                [["KC_A", "LT(1,KC_B)", -1], ["M0", "TD(0)", "LSFT_T(KC_C)"]],
+                [["KC_TRNS", "MO(1)", -1], ["KC_NO", "USER01", 4]]
+            ],
+            "encoder_layout": [[["KC_VOLD", "KC_VOLU"]], [["KC_TRNS", "KC_TRNS"]]],
+            "macro": [[["text", "hi"], ["tap", "KC_ENT"], ["delay", 100]]],
+            "tap_dance": [["KC_ESC", "KC_NO", "KC_CAPS", "KC_NO", 200]],
+            "combo": [["KC_A", "KC_C", "KC_NO", "KC_NO", "KC_TAB"]]
+        }"#;
+        assert!(is_vial(data));
+        let layer_names = layer_names();
+        let keymap = keymap_from_str(data, 0, &layer_names, &KeycodeTable::builtin()).unwrap();
+        assert_eq!((keymap.num_rows, keymap.num_columns), (2, 3));
+        assert_eq!(parser::keycode(&keymap.layers[0][0]), "KC_A");
+        assert_eq!(parser::keycode(&keymap.layers[0][1]), "LT(_LOWER, KC_B)");
+        assert_eq!(keymap.layers[0][2], Key::No);
+        assert_eq!(keymap.layers[0][3], Key::Macro(0));
+        assert_eq!(keymap.layers[0][4], Key::TapDance(String::from("0")));
+        assert_eq!(parser::keycode(&keymap.layers[1][1]), "MO(_LOWER)");
+        assert_eq!(parser::keycode(&keymap.layers[1][4]), "QK_USER_1");
+        // numbers are decoded, 4 is KC_A
+        assert_eq!(parser::keycode(&keymap.layers[1][5]), "KC_A");
+        assert_eq!(parser::keycode(&keymap.encoders[0][0].cw), "KC_VOLU");
+        let steps = vec![MacroStep::Text(String::from("hi")), MacroStep::Tap(vec![String::from("KC_ENT")]), MacroStep::Delay(100)];
+        assert_eq!(keymap.macros, vec![Macro { steps }]);
+        let tap_dance = &keymap.tap_dances["0"];
+        assert_eq!(tap_dance.tap.as_ref().map(parser::keycode).as_deref(), Some("KC_ESC"));
+        assert_eq!(tap_dance.hold, None);
+        assert_eq!(keymap.combos.len(), 1);
+        assert_eq!(keymap.combos[0].keys.len(), 2);
+        assert_eq!(parser::keycode(&keymap.combos[0].result), "KC_TAB");
+    }
+
+    #[test]
+    fn via_keycodes_round_trip() {
+        let table = KeycodeTable::builtin();
+        let layer_names = layer_names();
+        // KC_A, LT(1, KC_B), MO(1), LCTL(KC_C), KC_TRNS, KC_NO
+        let values: Vec<u16> = vec![0x0004, 0x4105, 0x5221, 0x0106, 0x0001, 0x0000];
+        let data = serde_json::json!({
+            "name": "test",
+            "vendorProductId": 1,
+            "layers": [values.iter().map(|v| format!("0x{v:04x}")).collect::<Vec<_>>(), values],
+        });
+        let keymap = keymap_from_str(&data.to_string(), 2, &layer_names, &table).unwrap();
+        assert_eq!((keymap.num_rows, keymap.num_columns), (2, 3));
+        for layer in &keymap.layers {
+            let encoded: Vec<u16> = layer.iter().map(|key| table.encode(key, &layer_names).unwrap()).collect();
+            assert_eq!(encoded, values);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use crate::qmk::keycodes;
+use crate::qmk::keymap_extras::US_SHIFTED;
+
+// (QMK keycode, RMK names), the first RMK name is the one written in keyboard.toml files.
+// Letters, digits, function keys and keypad digits are generated by `keycodes`.
+const KEYCODES: &[(&str, &[&str])] = &[
+    ("KC_ENT", &["Enter"]),
+    ("KC_ESC", &["Escape"]),
+    ("KC_BSPC", &["Backspace"]),
+    ("KC_TAB", &["Tab"]),
+    ("KC_SPC", &["Space"]),
+    ("KC_MINS", &["Minus"]),
+    ("KC_EQL", &["Equal"]),
+    ("KC_LBRC", &["LeftBracket"]),
+    ("KC_RBRC", &["RightBracket"]),
+    ("KC_BSLS", &["Backslash"]),
+    ("KC_NUHS", &["NonusHash"]),
+    ("KC_SCLN", &["Semicolon"]),
+    ("KC_QUOT", &["Quote"]),
+    ("KC_GRV", &["Grave"]),
+    ("KC_COMM", &["Comma"]),
+    ("KC_DOT", &["Dot"]),
+    ("KC_SLSH", &["Slash"]),
+    ("KC_CAPS", &["CapsLock"]),
+    ("KC_PSCR", &["PrintScreen"]),
+    ("KC_SCRL", &["ScrollLock"]),
+    ("KC_PAUS", &["Pause"]),
+    ("KC_INS", &["Insert"]),
+    ("KC_HOME", &["Home"]),
+    ("KC_PGUP", &["PageUp"]),
+    ("KC_DEL", &["Delete"]),
+    ("KC_END", &["End"]),
+    ("KC_PGDN", &["PageDown"]),
+    ("KC_RGHT", &["Right"]),
+    ("KC_LEFT", &["Left"]),
+    ("KC_DOWN", &["Down"]),
+    ("KC_UP", &["Up"]),
+    ("KC_NUM", &["NumLock"]),
+    ("KC_PSLS", &["KpSlash"]),
+    ("KC_PAST", &["KpAsterisk"]),
+    ("KC_PMNS", &["KpMinus"]),
+    ("KC_PPLS", &["KpPlus"]),
+    ("KC_PENT", &["KpEnter"]),
+    ("KC_PDOT", &["KpDot"]),
+    ("KC_PEQL", &["KpEqual"]),
+    ("KC_NUBS", &["NonusBackslash"]),
+    ("KC_APP", &["Application"]),
+    ("KC_EXEC", &["Execute"]),
+    ("KC_HELP", &["Help"]),
+    ("KC_MENU", &["Menu"]),
+    ("KC_SLCT", &["Select"]),
+    ("KC_STOP", &["Stop"]),
+    ("KC_AGIN", &["Again"]),
+    ("KC_UNDO", &["Undo"]),
+    ("KC_CUT", &["Cut"]),
+    ("KC_COPY", &["Copy"]),
+    ("KC_PSTE", &["Paste"]),
+    ("KC_FIND", &["Find"]),
+    ("KC_MUTE", &["AudioMute", "KbMute"]),
+    ("KC_VOLU", &["AudioVolUp", "KbVolumeUp"]),
+    ("KC_VOLD", &["AudioVolDown", "KbVolumeDown"]),
+    ("KC_MNXT", &["MediaNextTrack"]),
+    ("KC_MPRV", &["MediaPrevTrack"]),
+    ("KC_MSTP", &["MediaStop"]),
+    ("KC_MPLY", &["MediaPlayPause"]),
+    ("KC_MFFD", &["MediaFastForward"]),
+    ("KC_MRWD", &["MediaRewind"]),
+    ("KC_BRIU", &["BrightnessUp"]),
+    ("KC_BRID", &["BrightnessDown"]),
+    ("KC_MAIL", &["Mail"]),
+    ("KC_CALC", &["Calculator"]),
+    ("KC_MYCM", &["MyComputer"]),
+    ("KC_WSCH", &["WwwSearch"]),
+    ("KC_WHOM", &["WwwHome"]),
+    ("KC_WBAK", &["WwwBack"]),
+    ("KC_WFWD", &["WwwForward"]),
+    ("KC_WSTP", &["WwwStop"]),
+    ("KC_WREF", &["WwwRefresh"]),
+    ("KC_WFAV", &["WwwFavorites"]),
+    ("KC_MS_U", &["MouseUp"]),
+    ("KC_MS_D", &["MouseDown"]),
+    ("KC_MS_L", &["MouseLeft"]),
+    ("KC_MS_R", &["MouseRight"]),
+    ("KC_WH_U", &["MouseWheelUp"]),
+    ("KC_WH_D", &["MouseWheelDown"]),
+    ("KC_WH_L", &["MouseWheelLeft"]),
+    ("KC_WH_R", &["MouseWheelRight"]),
+    ("KC_LCTL", &["LCtrl"]),
+    ("KC_LSFT", &["LShift"]),
+    ("KC_LALT", &["LAlt"]),
+    ("KC_LGUI", &["LGui"]),
+    ("KC_RCTL", &["RCtrl"]),
+    ("KC_RSFT", &["RShift"]),
+    ("KC_RALT", &["RAlt"]),
+    ("KC_RGUI", &["RGui"]),
+    ("QK_BOOT", &["Bootloader"]),
+    ("QK_RBT", &["Reboot"]),
+];
+
+// QMK modifiers, their RMK names are the ones of the modifier keys
+pub const MODIFIERS: &[&str] = &["LCTL", "LSFT", "LALT", "LGUI", "RCTL", "RSFT", "RALT", "RGUI"];
+
+// QMK modifier aliases, `SFT_T()` is `LSFT_T()` and `S()` is `LSFT()`
+const MOD_ALIASES: &[(&str, &str)] = &[
+    ("C", "LCTL"),
+    ("CTL", "LCTL"),
+    ("S", "LSFT"),
+    ("SFT", "LSFT"),
+    ("A", "LALT"),
+    ("ALT", "LALT"),
+    ("LOPT", "LALT"),
+    ("G", "LGUI"),
+    ("GUI", "LGUI"),
+    ("LCMD", "LGUI"),
+    ("LWIN", "LGUI"),
+    ("ROPT", "RALT"),
+    ("ALGR", "RALT"),
+    ("RCMD", "RGUI"),
+    ("RWIN", "RGUI"),
+];
+
+// (QMK keycode, RMK names) for all the known keycodes
+fn keycodes() -> Vec<(String, Vec<String>)> {
+    let mut keycodes: Vec<(String, Vec<String>)> = Vec::new();
+    for c in 'A'..='Z' {
+        keycodes.push((format!("KC_{c}"), vec![c.to_string()]));
+    }
+    for n in 0..10 {
+        keycodes.push((format!("KC_{n}"), vec![format!("Kc{n}")]));
+        keycodes.push((format!("KC_P{n}"), vec![format!("Kp{n}")]));
+    }
+    for n in 1..=24 {
+        keycodes.push((format!("KC_F{n}"), vec![format!("F{n}")]));
+    }
+    for (qmk, rmk) in KEYCODES {
+        keycodes.push((qmk.to_string(), rmk.iter().map(|name| name.to_string()).collect()));
+    }
+    keycodes
+}
+
+lazy_static! {
+    // uppercase RMK name -> QMK keycode, RMK names are case insensitive
+    static ref TO_QMK: HashMap<String, String> = keycodes()
+        .into_iter()
+        .flat_map(|(qmk, rmk)| rmk.into_iter().map(move |name| (name.to_uppercase(), qmk.to_string())))
+        .collect();
+    // QMK basic keycode alias -> keycode of the table, `KC_ENTER` -> `KC_ENT`
+    static ref QMK_ALIASES: HashMap<String, String> = {
+        let values: HashMap<String, u16> = keycodes::basic_keycodes()
+            .into_iter()
+            .flat_map(|(value, name, aliases)| aliases.into_iter().chain([name]).map(move |alias| (alias, value)))
+            .collect();
+        let mut aliases = HashMap::new();
+        for (qmk, _) in keycodes() {
+            let Some(value) = values.get(&qmk) else {
+                continue;
+            };
+            for (alias, _) in values.iter().filter(|(_, v)| *v == value) {
+                aliases.insert(alias.to_string(), qmk.to_string());
+            }
+        }
+        aliases
+    };
+    // QMK keycode -> RMK name
+    static ref TO_RMK: HashMap<String, String> = keycodes()
+        .into_iter()
+        .filter_map(|(qmk, rmk)| rmk.into_iter().next().map(|name| (qmk, name)))
+        .collect();
+}
+
+fn canonical_modifier(qmk: &str) -> &str {
+    MOD_ALIASES.iter().find(|(alias, _)| *alias == qmk).map_or(qmk, |(_, modifier)| modifier)
+}
+
+// QMK keycode of an RMK key name, `Space` -> `KC_SPC`
+pub fn to_qmk(rmk: &str) -> Option<String> {
+    TO_QMK.get(&rmk.to_uppercase()).map(|qmk| qmk.to_string())
+}
+
+// RMK key name of a QMK keycode without modifiers, aliases included, `KC_SPACE` -> `Space`
+pub fn to_rmk(qmk: &str) -> Option<String> {
+    let keycode = QMK_ALIASES.get(qmk).map_or(qmk, |k| k.as_str());
+    TO_RMK.get(keycode).map(|name| name.to_string())
+}
+
+// RMK action of a QMK keycode, the modified keys are written with `WM`, `LCTL(KC_C)` ->
+// `WM(C, LCtrl)` and `KC_EXLM` -> `WM(Kc1, LShift)`
+pub fn to_rmk_action(qmk: &str) -> Option<String> {
+    let mut keycode = qmk;
+    let mut modifiers = Vec::new();
+    while let Some((modifier, key)) = keycode.strip_suffix(')').and_then(|k| k.split_once('(')) {
+        modifiers.push(modifier_to_rmk(modifier)?);
+        keycode = key.trim();
+    }
+    if let Some((_, unshifted, _)) = US_SHIFTED.iter().find(|(shifted, _, _)| *shifted == keycode) {
+        modifiers.push(String::from("LShift"));
+        keycode = unshifted;
+    }
+    let key = to_rmk(keycode)?;
+    if modifiers.is_empty() {
+        return Some(key);
+    }
+    Some(format!("WM({key}, {})", modifiers.join(" | ")))
+}
+
+// QMK modifiers of an RMK modifier combination, `LCtrl | LShift` -> [`LCTL`, `LSFT`], none for
+// an empty string
+pub fn modifiers_to_qmk(rmk: &str) -> Option<Vec<String>> {
+    rmk.split('|')
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| {
+            let modifier = to_qmk(name.trim())?.strip_prefix("KC_")?.to_string();
+            MODIFIERS.contains(&modifier.as_str()).then_some(modifier)
+        })
+        .collect()
+}
+
+// RMK name of a QMK modifier, `SFT` -> `LShift`
+pub fn modifier_to_rmk(qmk: &str) -> Option<String> {
+    let modifier = canonical_modifier(qmk);
+    MODIFIERS.contains(&modifier).then(|| to_rmk(&format!("KC_{modifier}")))?
+}
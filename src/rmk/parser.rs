@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use toml::Value;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{Combo, Key, Keymap};
+use crate::qmk::keymap_extras::US_SHIFTED;
+use crate::qmk::parser;
+use crate::rmk::keycodes;
+
+// Actions of a `[[layer]]` keys string, `LT(1, Space)` is a single action
+fn split_actions(line: &str) -> Vec<String> {
+    let mut actions = Vec::new();
+    let mut action = String::new();
+    let mut depth = 0;
+    for c in line.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if c.is_whitespace() && depth == 0 => {
+                if !action.is_empty() {
+                    actions.push(std::mem::take(&mut action));
+                }
+                continue;
+            }
+            _ => (),
+        }
+        action.push(c);
+    }
+    if !action.is_empty() {
+        actions.push(action);
+    }
+    actions
+}
+
+struct Context<'a> {
+    layer_names: &'a LayerNames,
+    num_layers: usize,
+}
+
+impl Context<'_> {
+    fn layer(&self, layer: &str) -> Option<String> {
+        let idx: usize = layer.trim().parse().ok()?;
+        (idx < self.num_layers).then(|| self.layer_names.get(idx))
+    }
+
+    // QMK keycode of an RMK key, modifiers included, `WM(C, LCtrl)` -> `LCTL(KC_C)`
+    fn keycode(&self, key: &str, modifiers: &str) -> Option<String> {
+        let mut keycode = keycodes::to_qmk(key.trim())?;
+        for modifier in keycodes::modifiers_to_qmk(modifiers)?.iter().rev() {
+            // shifted symbols have their own keycode
+            let shifted = US_SHIFTED.iter().find(|(_, unshifted, _)| *unshifted == keycode);
+            keycode = match shifted {
+                Some((shifted, _, _)) if modifier == "LSFT" => shifted.to_string(),
+                _ => format!("{modifier}({keycode})"),
+            };
+        }
+        Some(keycode)
+    }
+
+    // Key of an RMK action, `MO(1)`, `LT(1, Space)`, `MT(A, LShift)`, ...
+    fn action(&self, action: &str) -> Option<Key> {
+        lazy_static! {
+            static ref ACTION: Regex = Regex::new(r"^(\w+)\((.*)\)$").unwrap();
+        }
+        let Some(captures) = ACTION.captures(action) else {
+            return match action {
+                "_" | "__" => Some(Key::Trans),
+                _ if action.eq_ignore_ascii_case("trans") || action.eq_ignore_ascii_case("transparent") => Some(Key::Trans),
+                _ if action.eq_ignore_ascii_case("no") => Some(Key::No),
+                _ => Some(parser::parse_keycode(&self.keycode(action, "")?, self.layer_names)),
+            };
+        };
+        let args: Vec<&str> = captures[2].split(',').map(|a| a.trim()).collect();
+        let key = match (&captures[1].to_uppercase()[..], args.as_slice()) {
+            (action @ ("MO" | "TG" | "TO" | "DF" | "OSL" | "TT"), [layer]) => Key::LayerChange {
+                action: action.to_string(),
+                layer: self.layer(layer)?,
+                index: layer.parse().ok(),
+            },
+            ("LT", [layer, key]) => Key::LayerTap {
+                layer: self.layer(layer)?,
+                key: self.keycode(key, "")?,
+                index: layer.parse().ok(),
+            },
+            ("MT" | "HRM", [key, modifiers]) => match keycodes::modifiers_to_qmk(modifiers)?.as_slice() {
+                [modifier] => Key::ModTap {
+                    modifier: modifier.to_string(),
+                    key: self.keycode(key, "")?.strip_prefix("KC_")?.to_string(),
+                },
+                _ => return None,
+            },
+            ("WM", [key, modifiers]) => parser::parse_keycode(&self.keycode(key, modifiers)?, self.layer_names),
+            ("SHIFTED", [key]) => parser::parse_keycode(&self.keycode(key, "LShift")?, self.layer_names),
+            ("OSM", [modifiers]) => match keycodes::modifiers_to_qmk(modifiers)?.as_slice() {
+                [modifier] => parser::parse_keycode(&format!("OSM(MOD_{modifier})"), self.layer_names),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    // Actions without a QMK equivalent are kept as custom keys named after the action
+    fn key(&self, action: &str) -> Key {
+        self.action(action).unwrap_or_else(|| Key::Custom(action.to_string()))
+    }
+}
+
+// Actions of a layer, per row
+type LayerRows = Vec<Vec<String>>;
+
+// Rows of actions of the layers, from the `[[layer]]` tables or from the `keymap` of `[layout]`.
+// The layer names are the ones of the `[[layer]]` tables.
+fn layer_rows(config: &Value) -> Result<(Vec<LayerRows>, Vec<String>), &'static str> {
+    if let Some(tables) = config.get("layer").and_then(|l| l.as_array()) {
+        let mut layers = Vec::new();
+        let mut names = Vec::new();
+        for (i, table) in tables.iter().enumerate() {
+            let keys = table.get("keys").and_then(|k| k.as_str()).ok_or("layer without keys")?;
+            layers.push(keys.lines().map(split_actions).filter(|row| !row.is_empty()).collect());
+            names.push(table.get("name").and_then(|n| n.as_str()).map_or(format!("layer{i}"), |n| n.to_string()));
+        }
+        return Ok((layers, names));
+    }
+    let keymap = config.get("layout").and_then(|l| l.get("keymap")).and_then(|k| k.as_array()).ok_or("no keymap in [layout]")?;
+    let strings = |value: &Value| -> Option<Vec<String>> {
+        value.as_array()?.iter().map(|action| action.as_str().map(|a| a.trim().to_string())).collect()
+    };
+    let layers = keymap
+        .iter()
+        .map(|layer| layer.as_array().and_then(|rows| rows.iter().map(strings).collect()))
+        .collect::<Option<Vec<LayerRows>>>()
+        .ok_or("keymap layers must be arrays of rows of strings")?;
+    Ok((layers, Vec::new()))
+}
+
+// Reads the keymap of an RMK keyboard.toml. The layers are split in rows following the rows of
+// the file, shorter rows being padded with `KC_NO`. Layers named in `[[layer]]` tables keep their
+// names, the others are named after their number, `layer0`, `layer1`, ...
+pub fn keymap_from_str(data: &str) -> Result<(Keymap, LayerNames), &'static str> {
+    let config: Value = toml::from_str(data).map_err(|_| "invalid TOML")?;
+    let (layer_rows, names) = layer_rows(&config)?;
+    if layer_rows.is_empty() {
+        return Err("no layers in the keymap");
+    }
+    let mut layer_names = LayerNames::new(names);
+    layer_names.fill(layer_rows.len());
+    let num_rows = layer_rows.iter().map(|rows| rows.len()).max().unwrap_or(1);
+    let num_columns = layer_rows.iter().flatten().map(|row| row.len()).max().unwrap_or(1);
+    let context = Context {
+        layer_names: &layer_names,
+        num_layers: layer_rows.len(),
+    };
+    let layers: Vec<Vec<Key>> = layer_rows
+        .iter()
+        .map(|rows| {
+            let mut keys = vec![Key::No; num_rows * num_columns];
+            for (r, row) in rows.iter().enumerate() {
+                for (c, action) in row.iter().enumerate() {
+                    keys[r * num_columns + c] = context.key(action);
+                }
+            }
+            keys
+        })
+        .collect();
+
+    // combos are matched on their keys, their positions are found when the keymap is loaded
+    let mut combos = Vec::new();
+    let combo_configs = config.get("behavior").and_then(|b| b.get("combo")).and_then(|c| c.get("combos")).and_then(|c| c.as_array());
+    for (i, combo) in combo_configs.into_iter().flatten().enumerate() {
+        let keys: Vec<Key> = match combo.get("actions").and_then(|a| a.as_array()) {
+            Some(actions) => actions.iter().filter_map(|a| a.as_str()).map(|a| context.key(a.trim())).collect(),
+            None => continue,
+        };
+        let Some(output) = combo.get("output").and_then(|o| o.as_str()) else {
+            continue;
+        };
+        let layer = combo.get("layer").and_then(|l| l.as_integer()).and_then(|l| context.layer(&l.to_string()));
+        combos.push(Combo {
+            name: format!("combo{i}"),
+            keys,
+            result: context.key(output.trim()),
+            layers: layer.map(|l| vec![l]),
+            positions: Vec::new(),
+        });
+    }
+
+    let keymap = Keymap {
+        keymap: String::from("rmk"),
+        // no QMK keyboard, the keymap is drawn as an ortho layout
+        keyboard: String::new(),
+        layout: String::new(),
+        num_rows,
+        num_columns,
+        layers,
+        tap_dances: HashMap::new(),
+        combos,
+        macros: Vec::new(),
+        custom_keycodes: HashMap::new(),
+        key_overrides: Vec::new(),
+        encoders: Vec::new(),
+        extra_fields: serde_json::Map::new(),
+        info_json: None,
+        source_keys: Vec::new(),
+    };
+    Ok((keymap, layer_names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keycodes(keymap: &Keymap) -> Vec<Vec<String>> {
+        keymap.layers.iter().map(|layer| layer.iter().map(parser::keycode).collect()).collect()
+    }
+
+    #[test]
+    fn layer_tables_are_parsed() {
+        let config = r#"
+[[layer]]
+name = "Base"
+keys = """
+A  MT(S, LCtrl)  LT(1, Space)
+MO(1)  WM(C, LCtrl | LShift)
+"""
+
+[[layer]]
+keys = """
+_  No  User0
+OSM(LShift)  SHIFTED(Kc1)  TO(0)
+"""
+
+[behavior.combo]
+combos = [
+  { actions = ["A", "MO(1)"], output = "Escape", layer = 0 },
+]
+"#;
+        let (keymap, layer_names) = keymap_from_str(config).unwrap();
+        assert_eq!(layer_names.names(), &[String::from("Base"), String::from("layer1")]);
+        assert_eq!((keymap.num_rows, keymap.num_columns), (2, 3));
+        let keycodes = keycodes(&keymap);
+        // the second row is shorter than the first one
+        assert_eq!(keycodes[0], ["KC_A", "LCTL_T(KC_S)", "LT(1, KC_SPC)", "MO(1)", "LCTL(LSFT(KC_C))", "KC_NO"]);
+        assert_eq!(keycodes[1], ["KC_TRNS", "KC_NO", "User0", "OSM(MOD_LSFT)", "KC_EXLM", "TO(0)"]);
+        assert_eq!(keymap.combos.len(), 1);
+        assert_eq!(keymap.combos[0].keys, [keymap.layers[0][0].clone(), keymap.layers[0][3].clone()]);
+        assert_eq!(parser::keycode(&keymap.combos[0].result), "KC_ESC");
+        assert_eq!(keymap.combos[0].layers, Some(vec![String::from("Base")]));
+    }
+
+    #[test]
+    fn layout_keymaps_are_parsed() {
+        let config = r#"
+[layout]
+rows = 1
+cols = 2
+layers = 2
+keymap = [
+  [["A", "MO(1)"]],
+  [["Left", "__"]],
+]
+"#;
+        let (keymap, layer_names) = keymap_from_str(config).unwrap();
+        assert_eq!(layer_names.names(), &[String::from("layer0"), String::from("layer1")]);
+        assert_eq!(keycodes(&keymap), [["KC_A", "MO(1)"], ["KC_LEFT", "KC_TRNS"]]);
+    }
+}
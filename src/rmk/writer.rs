@@ -0,0 +1,211 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{CustomAction, Key, Keymap, Unsupported};
+use crate::qmk::parser;
+use crate::rmk::keycodes;
+
+struct Writer<'a> {
+    keymap: &'a Keymap,
+    layer_names: &'a LayerNames,
+    unsupported: Vec<Unsupported>,
+}
+
+impl Writer<'_> {
+    // RMK layers are referred to by their index, the one of the QMK keycode when it has one
+    fn layer(&self, name: &str, index: Option<usize>) -> Option<usize> {
+        index.or_else(|| self.layer_names.index(name)).filter(|idx| *idx < self.keymap.layers.len())
+    }
+
+    // RMK action of the key, or None if it has no equivalent
+    fn action(&self, key: &Key) -> Option<String> {
+        lazy_static! {
+            static ref OSM: Regex = Regex::new(r"^OSM\(MOD_(\w+)\)$").unwrap();
+        }
+        let action = match key {
+            Key::No => String::from("No"),
+            Key::Trans => String::from("_"),
+            Key::BasicKey(key) => keycodes::to_rmk_action(&format!("KC_{key}"))?,
+            Key::SpecialKey(key) => keycodes::to_rmk_action(key)?,
+            Key::ModKey { modifier, key } => keycodes::to_rmk_action(&format!("{modifier}(KC_{key})"))?,
+            Key::ModTap { modifier, key } => {
+                format!("MT({}, {})", keycodes::to_rmk(&format!("KC_{key}"))?, keycodes::modifier_to_rmk(modifier)?)
+            }
+            Key::LayerTap { layer, key, index } => format!("LT({}, {})", self.layer(layer, *index)?, keycodes::to_rmk(key)?),
+            Key::LayerChange { action, layer, index } => match action.as_str() {
+                "MO" | "TG" | "TO" | "DF" | "OSL" | "TT" => format!("{action}({})", self.layer(layer, *index)?),
+                "PDF" => format!("DF({})", self.layer(layer, *index)?),
+                _ => return None,
+            },
+            // actions read from a keyboard.toml
+            Key::Custom(name) if !self.keymap.custom_keycodes.contains_key(name) => name.to_string(),
+            Key::Custom(name) => match self.keymap.custom_keycodes.get(name).and_then(|c| c.action.as_ref()) {
+                Some(CustomAction::Tap(key)) => self.action(key)?,
+                Some(CustomAction::DefaultLayer(layer)) => format!("DF({})", self.layer(layer, None)?),
+                _ => return None,
+            },
+            Key::Unknown(keycode) => match OSM.captures(keycode) {
+                Some(osm) => format!("OSM({})", keycodes::modifier_to_rmk(&osm[1])?),
+                None => keycodes::to_rmk_action(keycode)?,
+            },
+            Key::Unicode(..) | Key::TapDance(_) | Key::Macro(_) => return None,
+        };
+        Some(action)
+    }
+
+    fn report(&mut self, layer: Option<usize>, index: Option<usize>, message: String) {
+        self.unsupported.push(Unsupported { layer, index, message });
+    }
+
+    fn key_action(&mut self, layer: usize, index: usize, key: &Key) -> String {
+        if let Some(action) = self.action(key) {
+            return action;
+        }
+        let message = match key {
+            Key::Unicode(..) => format!("unicode keycode {} is not supported by RMK", parser::keycode(key)),
+            _ => format!("{} has no RMK equivalent", parser::keycode(key)),
+        };
+        self.report(Some(layer), Some(index), message);
+        String::from("No")
+    }
+
+    // Rows of the layer as TOML arrays, with the columns aligned
+    fn layer_rows(&mut self, layer: usize) -> String {
+        let keymap = self.keymap;
+        let actions: Vec<String> = keymap.layers[layer]
+            .iter()
+            .enumerate()
+            .map(|(index, key)| format!("\"{}\",", self.key_action(layer, index, key)))
+            .collect();
+        let columns = keymap.num_columns.max(1);
+        let mut widths = vec![0; columns];
+        for (i, action) in actions.iter().enumerate() {
+            widths[i % columns] = widths[i % columns].max(action.chars().count());
+        }
+        let rows: Vec<String> = actions
+            .chunks(columns)
+            .map(|row| {
+                let cells: Vec<String> = row.iter().enumerate().map(|(c, a)| format!("{a:<width$}", width = widths[c])).collect();
+                format!("    [{}],", cells.join(" ").trim_end().trim_end_matches(','))
+            })
+            .collect();
+        rows.join("\n")
+    }
+
+    fn combos(&mut self) -> String {
+        let keymap = self.keymap;
+        let mut combos = String::new();
+        for combo in &keymap.combos {
+            let actions: Option<Vec<String>> = combo.keys.iter().map(|k| self.action(k).map(|a| format!("\"{a}\""))).collect();
+            let (Some(actions), Some(output)) = (actions, self.action(&combo.result)) else {
+                self.report(None, None, format!("combo {}: its keys or result have no RMK equivalent", combo.name));
+                continue;
+            };
+            // RMK combos are active on a single layer, or on all of them
+            let layers: Vec<Option<usize>> = match &combo.layers {
+                Some(layers) => layers.iter().filter_map(|l| self.layer(l, None)).map(Some).collect(),
+                None => vec![None],
+            };
+            if layers.is_empty() {
+                self.report(None, None, format!("combo {}: its layers are not in the keymap", combo.name));
+            }
+            for layer in layers {
+                let layer = layer.map_or(String::new(), |l| format!(", layer = {l}"));
+                combos.push_str(&format!("  {{ actions = [{}], output = \"{output}\"{layer} }},\n", actions.join(", ")));
+            }
+        }
+        if combos.is_empty() {
+            return combos;
+        }
+        format!("\n[behavior.combo]\ncombos = [\n{combos}]\n")
+    }
+}
+
+// Writes the keymap as the `[layout]` of an RMK keyboard.toml, with the keys and features which
+// RMK doesn't support returned separately. The rows and columns are the ones of the keymap, they
+// have to match the matrix of the keyboard.
+pub fn keymap_to_rmk(keymap: &Keymap, layer_names: &LayerNames) -> (String, Vec<Unsupported>) {
+    let mut writer = Writer {
+        keymap,
+        layer_names,
+        unsupported: Vec::new(),
+    };
+    let mut rmk = String::new();
+    if !keymap.keyboard.is_empty() {
+        rmk.push_str(&format!("# Converted from the QMK keymap {}:{}\n\n", keymap.keyboard, keymap.keymap));
+    }
+    rmk.push_str("[layout]\n");
+    rmk.push_str(&format!("rows = {}\n", keymap.num_rows));
+    rmk.push_str(&format!("cols = {}\n", keymap.num_columns));
+    rmk.push_str(&format!("layers = {}\n", keymap.layers.len()));
+    rmk.push_str("keymap = [\n");
+    for layer in 0..keymap.layers.len() {
+        rmk.push_str(&format!("  # {}\n  [\n{}\n  ],\n", layer_names.get(layer), writer.layer_rows(layer)));
+    }
+    rmk.push_str("]\n");
+    let combos = writer.combos();
+    rmk.push_str(&combos);
+    for feature in [
+        (!keymap.key_overrides.is_empty(), "key overrides"),
+        (keymap.encoders.iter().any(|e| !e.is_empty()), "encoders"),
+    ] {
+        if feature.0 {
+            writer.report(None, None, format!("{} are not exported", feature.1));
+        }
+    }
+    (rmk, writer.unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rmk::parser::keymap_from_str;
+
+    fn keycodes(keymap: &Keymap) -> Vec<Vec<String>> {
+        keymap.layers.iter().map(|layer| layer.iter().map(parser::keycode).collect()).collect()
+    }
+
+    #[test]
+    fn keyboard_tomls_round_trip() {
+        let config = r#"[layout]
+rows = 2
+cols = 3
+layers = 2
+keymap = [
+  # layer0
+  [
+    ["A",     "MT(S, LCtrl)", "LT(1, Space)"],
+    ["MO(1)", "WM(C, LCtrl)", "User0"],
+  ],
+  # layer1
+  [
+    ["_",           "No",              "Left"],
+    ["OSM(LShift)", "WM(Kc1, LShift)", "TO(0)"],
+  ],
+]
+
+[behavior.combo]
+combos = [
+  { actions = ["A", "MO(1)"], output = "Escape", layer = 0 },
+]
+"#;
+        let (mut keymap, layer_names) = keymap_from_str(config).unwrap();
+        keymap.locate_combos(0);
+        let (rmk, unsupported) = keymap_to_rmk(&keymap, &layer_names);
+        assert!(unsupported.is_empty(), "{:?}", unsupported.iter().map(|u| &u.message).collect::<Vec<_>>());
+        assert_eq!(rmk, config);
+        let (imported, _) = keymap_from_str(&rmk).unwrap();
+        assert_eq!(keycodes(&imported), keycodes(&keymap));
+    }
+
+    #[test]
+    fn unsupported_keys_are_reported() {
+        let layer_names = LayerNames::new(vec![String::from("Base")]);
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "", "layers": [["TD(0)", "KC_A"]]}"#;
+        let keymap = parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        let (rmk, unsupported) = keymap_to_rmk(&keymap, &layer_names);
+        assert!(rmk.contains("    [\"No\", \"A\"],\n"), "{rmk}");
+        assert_eq!(unsupported.len(), 1);
+        assert_eq!(unsupported[0].message, "TD(0) has no RMK equivalent");
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use lazy_static::lazy_static;
+use crate::qmk::keycodes;
+use crate::qmk::keymap_extras::US_SHIFTED;
+
+// (QMK keycode, ZMK names), the first ZMK name is the one written in ZMK keymaps. Letters,
+// digits, function keys and keypad digits are generated by `keycodes`.
+const KEYCODES: &[(&str, &[&str])] = &[
+    ("KC_ENT", &["RET", "ENTER", "RETURN"]),
+    ("KC_ESC", &["ESC", "ESCAPE"]),
+    ("KC_BSPC", &["BSPC", "BACKSPACE"]),
+    ("KC_TAB", &["TAB"]),
+    ("KC_SPC", &["SPACE", "SPC"]),
+    ("KC_MINS", &["MINUS"]),
+    ("KC_EQL", &["EQUAL"]),
+    ("KC_LBRC", &["LBKT", "LEFT_BRACKET"]),
+    ("KC_RBRC", &["RBKT", "RIGHT_BRACKET"]),
+    ("KC_BSLS", &["BSLH", "BACKSLASH"]),
+    ("KC_NUHS", &["NUHS", "NON_US_HASH"]),
+    ("KC_SCLN", &["SEMI", "SEMICOLON", "SCLN"]),
+    ("KC_QUOT", &["SQT", "APOS", "APOSTROPHE", "SINGLE_QUOTE"]),
+    ("KC_GRV", &["GRAVE"]),
+    ("KC_COMM", &["COMMA"]),
+    ("KC_DOT", &["DOT", "PERIOD"]),
+    ("KC_SLSH", &["FSLH", "SLASH"]),
+    ("KC_CAPS", &["CAPS", "CAPSLOCK", "CLCK"]),
+    ("KC_PSCR", &["PSCRN", "PRINTSCREEN"]),
+    ("KC_SCRL", &["SLCK", "SCROLLLOCK"]),
+    ("KC_PAUS", &["PAUSE_BREAK"]),
+    ("KC_INS", &["INS", "INSERT"]),
+    ("KC_HOME", &["HOME"]),
+    ("KC_PGUP", &["PG_UP", "PAGE_UP"]),
+    ("KC_DEL", &["DEL", "DELETE"]),
+    ("KC_END", &["END"]),
+    ("KC_PGDN", &["PG_DN", "PAGE_DOWN"]),
+    ("KC_RGHT", &["RIGHT", "RARW"]),
+    ("KC_LEFT", &["LEFT", "LARW"]),
+    ("KC_DOWN", &["DOWN", "DARW"]),
+    ("KC_UP", &["UP", "UARW"]),
+    ("KC_NUM", &["KP_NUM", "KP_NUMLOCK", "KP_NLCK"]),
+    ("KC_PSLS", &["KP_SLASH", "KP_DIVIDE"]),
+    ("KC_PAST", &["KP_ASTERISK", "KP_MULTIPLY"]),
+    ("KC_PMNS", &["KP_MINUS", "KP_SUBTRACT"]),
+    ("KC_PPLS", &["KP_PLUS"]),
+    ("KC_PENT", &["KP_ENTER"]),
+    ("KC_PDOT", &["KP_DOT"]),
+    ("KC_PEQL", &["KP_EQUAL"]),
+    ("KC_NUBS", &["NUBS", "NON_US_BSLH", "NON_US_BACKSLASH"]),
+    ("KC_APP", &["K_APP", "K_APPLICATION", "K_CONTEXT_MENU"]),
+    ("KC_UNDO", &["K_UNDO", "UNDO"]),
+    ("KC_CUT", &["K_CUT", "CUT"]),
+    ("KC_COPY", &["K_COPY", "COPY"]),
+    ("KC_PSTE", &["K_PASTE", "PASTE"]),
+    ("KC_FIND", &["K_FIND", "FIND"]),
+    ("KC_MUTE", &["C_MUTE", "K_MUTE"]),
+    ("KC_VOLU", &["C_VOL_UP", "C_VOLUME_UP", "K_VOL_UP"]),
+    ("KC_VOLD", &["C_VOL_DN", "C_VOLUME_DOWN", "K_VOL_DN"]),
+    ("KC_MNXT", &["C_NEXT", "C_NEXT_TRACK"]),
+    ("KC_MPRV", &["C_PREV", "C_PREVIOUS"]),
+    ("KC_MSTP", &["C_STOP"]),
+    ("KC_MPLY", &["C_PP", "C_PLAY_PAUSE"]),
+    ("KC_EJCT", &["C_EJECT"]),
+    ("KC_MFFD", &["C_FF", "C_FAST_FORWARD"]),
+    ("KC_MRWD", &["C_RW", "C_REWIND"]),
+    ("KC_BRIU", &["C_BRI_UP", "C_BRI_INC"]),
+    ("KC_BRID", &["C_BRI_DN", "C_BRI_DEC"]),
+    ("KC_CALC", &["C_AL_CALC", "C_AL_CALCULATOR"]),
+    ("KC_MAIL", &["C_AL_MAIL", "C_AL_EMAIL"]),
+    ("KC_WSCH", &["C_AC_SEARCH"]),
+    ("KC_WHOM", &["C_AC_HOME"]),
+    ("KC_WBAK", &["C_AC_BACK"]),
+    ("KC_WFWD", &["C_AC_FORWARD"]),
+    ("KC_WSTP", &["C_AC_STOP"]),
+    ("KC_WREF", &["C_AC_REFRESH"]),
+    ("KC_WFAV", &["C_AC_BOOKMARKS"]),
+    ("KC_PWR", &["C_PWR", "C_POWER"]),
+    ("KC_SLEP", &["C_SLEEP"]),
+    ("KC_LCTL", &["LCTRL", "LCTL", "LEFT_CONTROL"]),
+    ("KC_LSFT", &["LSHFT", "LSHIFT", "LEFT_SHIFT"]),
+    ("KC_LALT", &["LALT", "LEFT_ALT"]),
+    ("KC_LGUI", &["LGUI", "LWIN", "LCMD", "LMETA", "LEFT_GUI"]),
+    ("KC_RCTL", &["RCTRL", "RCTL", "RIGHT_CONTROL"]),
+    ("KC_RSFT", &["RSHFT", "RSHIFT", "RIGHT_SHIFT"]),
+    ("KC_RALT", &["RALT", "RIGHT_ALT"]),
+    ("KC_RGUI", &["RGUI", "RWIN", "RCMD", "RMETA", "RIGHT_GUI"]),
+    // shifted symbols, ZMK has names for them
+    ("KC_TILD", &["TILDE"]),
+    ("KC_EXLM", &["EXCL", "EXCLAMATION"]),
+    ("KC_AT", &["AT", "AT_SIGN"]),
+    ("KC_HASH", &["HASH", "POUND"]),
+    ("KC_DLR", &["DLLR", "DOLLAR"]),
+    ("KC_PERC", &["PRCNT", "PERCENT"]),
+    ("KC_CIRC", &["CARET"]),
+    ("KC_AMPR", &["AMPS", "AMPERSAND"]),
+    ("KC_ASTR", &["ASTRK", "STAR", "ASTERISK"]),
+    ("KC_LPRN", &["LPAR", "LEFT_PARENTHESIS"]),
+    ("KC_RPRN", &["RPAR", "RIGHT_PARENTHESIS"]),
+    ("KC_UNDS", &["UNDER", "UNDERSCORE"]),
+    ("KC_PLUS", &["PLUS"]),
+    ("KC_LCBR", &["LBRC", "LEFT_BRACE"]),
+    ("KC_RCBR", &["RBRC", "RIGHT_BRACE"]),
+    ("KC_PIPE", &["PIPE"]),
+    ("KC_COLN", &["COLON"]),
+    ("KC_DQUO", &["DQT", "DOUBLE_QUOTES"]),
+    ("KC_LT", &["LT", "LESS_THAN"]),
+    ("KC_GT", &["GT", "GREATER_THAN"]),
+    ("KC_QUES", &["QMARK", "QUESTION"]),
+];
+
+// (QMK modifier, ZMK modifier function), `LSFT(KC_A)` is `LS(A)` in ZMK
+pub const MOD_FUNCTIONS: &[(&str, &str)] = &[
+    ("LCTL", "LC"),
+    ("LSFT", "LS"),
+    ("LALT", "LA"),
+    ("LGUI", "LG"),
+    ("RCTL", "RC"),
+    ("RSFT", "RS"),
+    ("RALT", "RA"),
+    ("RGUI", "RG"),
+];
+
+// (QMK keycode, ZMK names) for all the known keycodes
+fn keycodes() -> Vec<(String, Vec<String>)> {
+    let mut keycodes: Vec<(String, Vec<String>)> = Vec::new();
+    for c in 'A'..='Z' {
+        keycodes.push((format!("KC_{c}"), vec![c.to_string()]));
+    }
+    for n in 0..10 {
+        keycodes.push((format!("KC_{n}"), vec![format!("N{n}"), format!("NUMBER_{n}")]));
+        keycodes.push((format!("KC_P{n}"), vec![format!("KP_N{n}"), format!("KP_NUMBER_{n}")]));
+    }
+    for n in 1..=24 {
+        keycodes.push((format!("KC_F{n}"), vec![format!("F{n}")]));
+    }
+    for n in 1..=9 {
+        keycodes.push((format!("KC_INT{n}"), vec![format!("INT{n}")]));
+        keycodes.push((format!("KC_LNG{n}"), vec![format!("LANG{n}")]));
+    }
+    for (qmk, zmk) in KEYCODES {
+        keycodes.push((qmk.to_string(), zmk.iter().map(|name| name.to_string()).collect()));
+    }
+    keycodes
+}
+
+lazy_static! {
+    // ZMK name -> QMK keycode
+    static ref TO_QMK: HashMap<String, String> = keycodes()
+        .into_iter()
+        .flat_map(|(qmk, zmk)| zmk.into_iter().map(move |name| (name, qmk.to_string())))
+        .collect();
+    // QMK basic keycode alias -> canonical keycode, `KC_ENTER` -> `KC_ENT`
+    static ref QMK_ALIASES: HashMap<String, String> = {
+        let values: HashMap<String, u16> = keycodes::basic_keycodes()
+            .into_iter()
+            .flat_map(|(value, name, aliases)| aliases.into_iter().chain([name]).map(move |alias| (alias, value)))
+            .collect();
+        let mut aliases = HashMap::new();
+        for (qmk, _) in keycodes() {
+            let Some(value) = values.get(&qmk) else {
+                continue;
+            };
+            for (alias, _) in values.iter().filter(|(_, v)| *v == value) {
+                aliases.insert(alias.to_string(), qmk.to_string());
+            }
+        }
+        // shifted keycodes typing the same character, `KC_DQT` -> `KC_DQUO`
+        for (alias, _, output) in US_SHIFTED {
+            let keycode = US_SHIFTED.iter().find(|(k, _, o)| o == output && KEYCODES.iter().any(|(qmk, _)| qmk == k));
+            if let Some((keycode, _, _)) = keycode {
+                aliases.insert(alias.to_string(), keycode.to_string());
+            }
+        }
+        aliases
+    };
+    // QMK keycode -> ZMK name
+    static ref TO_ZMK: HashMap<String, String> = keycodes()
+        .into_iter()
+        .filter_map(|(qmk, zmk)| zmk.into_iter().next().map(|name| (qmk, name)))
+        .collect();
+}
+
+// QMK keycode of the ZMK key name, `SPACE` -> `KC_SPC`
+pub fn to_qmk(zmk: &str) -> Option<String> {
+    TO_QMK.get(zmk).map(|qmk| qmk.to_string())
+}
+
+// ZMK key name of the QMK keycode, aliases included, `KC_SPACE` -> `SPACE`
+pub fn to_zmk(qmk: &str) -> Option<String> {
+    let canonical = QMK_ALIASES.get(qmk).map_or(qmk, |c| c.as_str());
+    TO_ZMK.get(canonical).map(|zmk| zmk.to_string())
+}
+
+// QMK modifier of a ZMK modifier key name, `LSHIFT` -> `LSFT`
+pub fn modifier_to_qmk(zmk: &str) -> Option<String> {
+    let qmk = to_qmk(zmk)?;
+    let modifier = qmk.strip_prefix("KC_")?;
+    MOD_FUNCTIONS.iter().any(|(m, _)| *m == modifier).then(|| modifier.to_string())
+}
+
+// QMK modifier aliases, `SFT_T()` is `LSFT_T()` and `S()` is `LSFT()`
+const MOD_ALIASES: &[(&str, &str)] = &[
+    ("C", "LCTL"),
+    ("CTL", "LCTL"),
+    ("S", "LSFT"),
+    ("SFT", "LSFT"),
+    ("A", "LALT"),
+    ("ALT", "LALT"),
+    ("LOPT", "LALT"),
+    ("G", "LGUI"),
+    ("GUI", "LGUI"),
+    ("LCMD", "LGUI"),
+    ("LWIN", "LGUI"),
+    ("ROPT", "RALT"),
+    ("ALGR", "RALT"),
+    ("RCMD", "RGUI"),
+    ("RWIN", "RGUI"),
+];
+
+fn canonical_modifier(qmk: &str) -> &str {
+    MOD_ALIASES.iter().find(|(alias, _)| *alias == qmk).map_or(qmk, |(_, modifier)| modifier)
+}
+
+// ZMK modifier key name of a QMK modifier, `SFT` -> `LSHFT`
+pub fn modifier_to_zmk(qmk: &str) -> Option<String> {
+    let modifier = canonical_modifier(qmk);
+    MOD_FUNCTIONS.iter().any(|(m, _)| *m == modifier).then(|| to_zmk(&format!("KC_{modifier}")))?
+}
+
+// ZMK modifier function of a QMK modifier, `LSFT` -> `LS`
+pub fn mod_function(qmk: &str) -> Option<&'static str> {
+    let modifier = canonical_modifier(qmk);
+    MOD_FUNCTIONS.iter().find(|(m, _)| *m == modifier).map(|(_, f)| *f)
+}
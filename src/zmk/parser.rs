@@ -0,0 +1,516 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{Combo, Encoder, Key, Keymap, Macro, MacroStep, TapDance};
+use crate::qmk::keymap_c::strip_comments;
+use crate::qmk::parser;
+use crate::zmk::keycodes::{self, MOD_FUNCTIONS};
+
+// Devicetree node, `label: name { property = value; child { ... }; };`
+struct Node {
+    name: String,
+    label: Option<String>,
+    properties: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(header: &str) -> Node {
+        let (label, name) = match header.split_once(':') {
+            Some((label, name)) => (Some(label.trim().to_string()), name.trim()),
+            None => (None, header.trim()),
+        };
+        Node {
+            name: name.to_string(),
+            label,
+            properties: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn property(&self, name: &str) -> Option<&str> {
+        self.properties.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    // String property without its quotes
+    fn string(&self, name: &str) -> Option<&str> {
+        self.property(name).map(|value| value.trim_matches('"'))
+    }
+
+    // Nodes with the `compatible` property, this node and its descendants
+    fn find_compatible<'a>(&'a self, compatible: &str, nodes: &mut Vec<&'a Node>) {
+        if self.string("compatible") == Some(compatible) {
+            nodes.push(self);
+        }
+        for child in &self.children {
+            child.find_compatible(compatible, nodes);
+        }
+    }
+
+    fn compatible(&self, compatible: &str) -> Vec<&Node> {
+        let mut nodes = Vec::new();
+        self.find_compatible(compatible, &mut nodes);
+        nodes
+    }
+}
+
+// Parses the devicetree nodes of `data`, preprocessor directives must already be removed.
+// The top-level nodes (`/ { ... };`, `&label { ... };`) are children of the returned node.
+fn parse_devicetree(data: &str) -> Node {
+    let mut stack = vec![Node::new("")];
+    let mut statement = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    for c in data.chars() {
+        match c {
+            '"' => {
+                in_string = !in_string;
+                statement.push(c);
+            }
+            _ if in_string => statement.push(c),
+            '<' | '(' | '[' => {
+                depth += 1;
+                statement.push(c);
+            }
+            '>' | ')' | ']' => {
+                depth -= 1;
+                statement.push(c);
+            }
+            '{' if depth == 0 => {
+                stack.push(Node::new(&statement));
+                statement.clear();
+            }
+            '}' if depth == 0 => {
+                if stack.len() > 1 {
+                    let node = stack.pop().unwrap();
+                    stack.last_mut().unwrap().children.push(node);
+                }
+                statement.clear();
+            }
+            ';' if depth == 0 => {
+                let property = statement.trim();
+                if !property.is_empty() {
+                    let (name, value) = property.split_once('=').unwrap_or((property, ""));
+                    stack.last_mut().unwrap().properties.push((name.trim().to_string(), value.trim().to_string()));
+                }
+                statement.clear();
+            }
+            _ => statement.push(c),
+        }
+    }
+    while stack.len() > 1 {
+        let node = stack.pop().unwrap();
+        stack.last_mut().unwrap().children.push(node);
+    }
+    stack.pop().unwrap()
+}
+
+// Object-like `#define NAME VALUE`, used for the layer numbers
+fn defines(data: &str) -> HashMap<String, String> {
+    lazy_static! {
+        static ref DEFINE: Regex = Regex::new(r"(?m)^\s*#\s*define\s+(\w+)[ \t]+(.+?)\s*$").unwrap();
+    }
+    DEFINE.captures_iter(data).map(|c| (c[1].to_string(), c[2].to_string())).collect()
+}
+
+fn remove_directives(data: &str) -> String {
+    lazy_static! {
+        static ref DIRECTIVE: Regex = Regex::new(r"(?m)^\s*#\s*(?:define|undef|include|if|ifdef|ifndef|elif|else|endif|pragma)\b.*$").unwrap();
+    }
+    DIRECTIVE.replace_all(data, "").to_string()
+}
+
+// Replaces the defined names by their value
+fn expand(value: &str, defines: &HashMap<String, String>) -> String {
+    lazy_static! {
+        static ref NAME: Regex = Regex::new(r"[A-Za-z_]\w*").unwrap();
+    }
+    let mut value = value.to_string();
+    // defines referring to other defines
+    for _ in 0..4 {
+        let expanded = NAME.replace_all(&value, |c: &regex::Captures| defines.get(&c[0]).cloned().unwrap_or(c[0].to_string())).to_string();
+        if expanded == value {
+            break;
+        }
+        value = expanded;
+    }
+    value
+}
+
+// Splits a `<&kp A &mt LSHIFT B>, <&trans>` property into the bindings and their parameters,
+// `[["&kp", "A"], ["&mt", "LSHIFT", "B"], ["&trans"]]`
+fn bindings(value: &str) -> Vec<Vec<String>> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut depth = 0;
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 && (c.is_whitespace() || matches!(c, '<' | '>' | ',')) {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+        } else if !c.is_whitespace() {
+            token.push(c);
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    let mut bindings: Vec<Vec<String>> = Vec::new();
+    for token in tokens {
+        match bindings.last_mut() {
+            Some(binding) if !token.starts_with('&') => binding.push(token),
+            _ => bindings.push(vec![token]),
+        }
+    }
+    bindings
+}
+
+// Number of bindings on each line of a `bindings` property
+fn bindings_per_line(value: &str) -> Vec<usize> {
+    value.lines().map(|line| line.matches('&').count()).filter(|count| *count > 0).collect()
+}
+
+// Custom behaviors defined in the keymap, by label
+enum Behavior {
+    // hold and tap behaviors, `<&kp>, <&kp>`
+    HoldTap(String, String),
+    TapDance,
+    Macro(usize),
+}
+
+struct Context<'a> {
+    layer_names: &'a LayerNames,
+    behaviors: HashMap<String, Behavior>,
+}
+
+impl Context<'_> {
+    // `LT()`, `MO()`, ... layer argument from a ZMK layer number
+    fn layer(&self, param: &str) -> Option<String> {
+        let name = match param.parse::<usize>() {
+            Ok(idx) => self.layer_names.names().get(idx)?.to_string(),
+            Err(_) => self.layer_names.pretty_name(param)?,
+        };
+        Some(parser::layer_id(&name))
+    }
+
+    // QMK keycode of a ZMK key parameter, `LS(A)` -> `LSFT(KC_A)`
+    fn kp(&self, param: &str) -> String {
+        lazy_static! {
+            static ref FUNCTION: Regex = Regex::new(r"^(\w+)\((.+)\)$").unwrap();
+        }
+        if let Some(f) = FUNCTION.captures(param) {
+            if let Some((qmk, _)) = MOD_FUNCTIONS.iter().find(|(_, zmk)| *zmk == &f[1]) {
+                return format!("{qmk}({})", self.kp(&f[2]));
+            }
+        }
+        keycodes::to_qmk(param).unwrap_or(param.to_string())
+    }
+
+    fn mod_tap(&self, modifier: &str, key: &str) -> Option<String> {
+        Some(format!("{}_T({})", keycodes::modifier_to_qmk(modifier)?, self.kp(key)))
+    }
+
+    // QMK keycode of the binding, for the behaviors which have a QMK equivalent
+    fn keycode(&self, behavior: &str, params: &[String]) -> Option<String> {
+        let keycode = match (behavior, params) {
+            ("kp", [key]) => self.kp(key),
+            ("mt", [modifier, key]) => self.mod_tap(modifier, key)?,
+            ("lt", [layer, key]) => format!("LT({}, {})", self.layer(layer)?, self.kp(key)),
+            ("mo", [layer]) => format!("MO({})", self.layer(layer)?),
+            ("tog", [layer]) => format!("TG({})", self.layer(layer)?),
+            ("to", [layer]) => format!("TO({})", self.layer(layer)?),
+            ("sl", [layer]) => format!("OSL({})", self.layer(layer)?),
+            ("sk", [modifier]) => format!("OSM(MOD_{})", keycodes::modifier_to_qmk(modifier)?),
+            ("trans", []) => String::from("KC_TRNS"),
+            ("none", []) => String::from("KC_NO"),
+            ("bootloader", []) => String::from("QK_BOOT"),
+            ("sys_reset", []) => String::from("QK_RBT"),
+            ("caps_word", []) => String::from("CW_TOGG"),
+            ("key_repeat", []) => String::from("QK_REP"),
+            ("gresc", []) => String::from("QK_GESC"),
+            (_, [hold, tap]) => match self.behaviors.get(behavior)? {
+                Behavior::HoldTap(hold_behavior, tap_behavior) => match (hold_behavior.as_str(), tap_behavior.as_str()) {
+                    ("kp", "kp") => self.mod_tap(hold, tap)?,
+                    ("mo", "kp") => format!("LT({}, {})", self.layer(hold)?, self.kp(tap)),
+                    _ => return None,
+                },
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(keycode)
+    }
+
+    // Bindings without a QMK equivalent are kept as custom keys named after the binding
+    fn key(&self, binding: &[String]) -> Key {
+        let Some((behavior, params)) = binding.split_first() else {
+            return Key::No;
+        };
+        let name = behavior.trim_start_matches('&');
+        if let Some(keycode) = self.keycode(name, params) {
+            return parser::parse_keycode(&keycode, self.layer_names);
+        }
+        match (self.behaviors.get(name), params.is_empty()) {
+            (Some(Behavior::TapDance), true) => Key::TapDance(name.to_string()),
+            (Some(Behavior::Macro(idx)), true) => Key::Macro(*idx),
+            _ => Key::Custom(binding.join(" ")),
+        }
+    }
+
+    // `&macro_press`, `&macro_release` and `&macro_tap` switch the mode of the following bindings
+    fn macro_steps(&self, bindings: &[Vec<String>]) -> Macro {
+        let mut steps = Vec::new();
+        let mut mode = "tap";
+        for binding in bindings {
+            match binding.first().map(|b| b.as_str()) {
+                Some("&macro_press") => mode = "down",
+                Some("&macro_release") => mode = "up",
+                Some("&macro_tap") => mode = "tap",
+                Some("&macro_wait_time") => {
+                    if let Some(delay) = binding.get(1).and_then(|d| d.parse::<u64>().ok()) {
+                        steps.push(MacroStep::Delay(delay));
+                    }
+                }
+                Some("&kp") if binding.len() == 2 => {
+                    let keycodes = vec![self.kp(&binding[1])];
+                    steps.push(match mode {
+                        "down" => MacroStep::Down(keycodes),
+                        "up" => MacroStep::Up(keycodes),
+                        _ => MacroStep::Tap(keycodes),
+                    });
+                }
+                _ => (),
+            }
+        }
+        Macro { steps }
+    }
+}
+
+// Layer name from its `display-name`, or else from its node name without the `_layer` suffix
+fn layer_name(node: &Node) -> String {
+    lazy_static! {
+        static ref WORD: Regex = Regex::new(r"^\w+$").unwrap();
+    }
+    match node.string("display-name").filter(|name| WORD.is_match(name)) {
+        Some(name) => name.to_string(),
+        None => node.name.strip_suffix("_layer").unwrap_or(&node.name).to_string(),
+    }
+}
+
+// Reads a ZMK .keymap file. The layer names come from the file, the layers are split in rows
+// following the lines of the `bindings`, or in `num_rows` rows if the lines have different
+// lengths.
+pub fn keymap_from_str(data: &str, num_rows: usize) -> Result<(Keymap, LayerNames), &'static str> {
+    let data = strip_comments(data);
+    let defines = defines(&data);
+    let root = parse_devicetree(&remove_directives(&data));
+    let keymap_node = *root.compatible("zmk,keymap").first().ok_or("no zmk,keymap node")?;
+    let layer_nodes: Vec<&Node> = keymap_node.children.iter().filter(|n| n.property("bindings").is_some()).collect();
+    let layer_names = LayerNames::new(layer_nodes.iter().map(|n| layer_name(n)).collect());
+
+    let mut context = Context {
+        layer_names: &layer_names,
+        behaviors: HashMap::new(),
+    };
+    let label = |node: &Node| node.label.clone().unwrap_or(node.name.to_string());
+    for node in root.compatible("zmk,behavior-hold-tap") {
+        let behaviors = bindings(node.property("bindings").unwrap_or_default());
+        if let [hold, tap] = behaviors.as_slice() {
+            let name = |b: &[String]| b.first().map_or(String::new(), |b| b.trim_start_matches('&').to_string());
+            context.behaviors.insert(label(node), Behavior::HoldTap(name(hold), name(tap)));
+        }
+    }
+    let macro_nodes = root.compatible("zmk,behavior-macro");
+    for (idx, node) in macro_nodes.iter().enumerate() {
+        context.behaviors.insert(label(node), Behavior::Macro(idx));
+    }
+    let tap_dance_nodes = root.compatible("zmk,behavior-tap-dance");
+    for node in &tap_dance_nodes {
+        context.behaviors.insert(label(node), Behavior::TapDance);
+    }
+    let property = |node: &Node, name: &str| expand(node.property(name).unwrap_or_default(), &defines);
+
+    let layers: Vec<Vec<Key>> = layer_nodes
+        .iter()
+        .map(|node| bindings(&property(node, "bindings")).iter().map(|b| context.key(b)).collect())
+        .collect();
+    let num_keys = layers.first().map_or(0, |layer| layer.len());
+    if num_keys == 0 || layers.iter().any(|layer| layer.len() != num_keys) {
+        return Err("inconsistent layer lengths");
+    }
+    let lines = bindings_per_line(layer_nodes[0].property("bindings").unwrap_or_default());
+    let num_rows = match lines.first() {
+        Some(first) if lines.len() > 1 && lines.iter().all(|count| count == first) => lines.len(),
+        _ if num_rows > 0 && num_keys.is_multiple_of(num_rows) => num_rows,
+        _ => 1,
+    };
+
+    let encoders: Vec<Vec<Encoder>> = layer_nodes
+        .iter()
+        .map(|node| {
+            bindings(&property(node, "sensor-bindings"))
+                .iter()
+                .filter(|b| b.len() == 3)
+                .map(|b| Encoder {
+                    cw: parser::parse_keycode(&context.kp(&b[1]), &layer_names),
+                    ccw: parser::parse_keycode(&context.kp(&b[2]), &layer_names),
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut tap_dances = HashMap::new();
+    for node in &tap_dance_nodes {
+        let keys: Vec<Key> = bindings(&property(node, "bindings")).iter().map(|b| context.key(b)).collect();
+        tap_dances.insert(label(node), TapDance {
+            tap: keys.first().cloned(),
+            double_tap: keys.get(1).cloned(),
+            hold: None,
+            function: None,
+        });
+    }
+
+    let mut combos = Vec::new();
+    for node in root.compatible("zmk,combos").iter().flat_map(|n| n.children.iter()) {
+        let positions: Vec<usize> = property(node, "key-positions")
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|p| p.parse().ok())
+            .collect();
+        let Some(result) = bindings(&property(node, "bindings")).first().map(|b| context.key(b)) else {
+            continue;
+        };
+        let combo_layers: Vec<String> = property(node, "layers")
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|l| l.parse::<usize>().ok())
+            .map(|l| layer_names.get(l))
+            .collect();
+        combos.push(Combo {
+            name: node.name.to_string(),
+            keys: positions.iter().filter_map(|p| layers[0].get(*p).cloned()).collect(),
+            result,
+            layers: if combo_layers.is_empty() { None } else { Some(combo_layers) },
+            positions,
+        });
+    }
+
+    let keymap = Keymap {
+        keymap: String::from("zmk"),
+        // no QMK keyboard, the keymap is drawn as an ortho layout
+        keyboard: String::new(),
+        layout: String::new(),
+        num_rows,
+        num_columns: num_keys / num_rows,
+        layers,
+        tap_dances,
+        combos,
+        macros: macro_nodes.iter().map(|n| context.macro_steps(&bindings(&property(n, "bindings")))).collect(),
+        custom_keycodes: HashMap::new(),
+        key_overrides: Vec::new(),
+        encoders: if encoders.iter().any(|e| !e.is_empty()) { encoders } else { Vec::new() },
+        extra_fields: serde_json::Map::new(),
+        info_json: None,
+        source_keys: Vec::new(),
+    };
+    Ok((keymap, layer_names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYMAP: &str = r#"
+#include <behaviors.dtsi>
+#include <dt-bindings/zmk/keys.h>
+
+#define BASE 0
+#define NAV 1
+
+/ {
+    behaviors {
+        hm: home_row_mods {
+            compatible = "zmk,behavior-hold-tap";
+            #binding-cells = <2>;
+            bindings = <&kp>, <&kp>;
+        };
+        td_esc: td_esc {
+            compatible = "zmk,behavior-tap-dance";
+            #binding-cells = <0>;
+            bindings = <&kp ESC>, <&kp CAPS>;
+        };
+    };
+
+    macros {
+        hello: hello {
+            compatible = "zmk,behavior-macro";
+            #binding-cells = <0>;
+            bindings = <&macro_press &kp LSHFT &macro_tap &kp H &macro_release &kp LSHFT &macro_wait_time 50>;
+        };
+    };
+
+    combos {
+        compatible = "zmk,combos";
+        combo_tab {
+            key-positions = <0 1>;
+            bindings = <&kp TAB>;
+            layers = <BASE>;
+        };
+    };
+
+    keymap {
+        compatible = "zmk,keymap";
+
+        base_layer {
+            display-name = "Base";
+            bindings = <
+                &kp Q  &hm LCTRL A  &lt NAV SPACE
+                &td_esc  &hello  &kp LS(N1)
+            >;
+            sensor-bindings = <&inc_dec_kp C_VOL_UP C_VOL_DN>;
+        };
+
+        nav_layer {
+            bindings = <
+                &trans  &mo NAV  &bt BT_CLR
+                &none  &bootloader  &sk LSHFT
+            >;
+        };
+    };
+};
+"#;
+
+    #[test]
+    fn keymaps_are_parsed() {
+        let (keymap, layer_names) = keymap_from_str(KEYMAP, 0).unwrap();
+        assert_eq!(layer_names.names(), &[String::from("Base"), String::from("nav")]);
+        assert_eq!((keymap.num_rows, keymap.num_columns), (2, 3));
+        let keycodes: Vec<Vec<String>> = keymap.layers.iter().map(|layer| layer.iter().map(parser::keycode).collect()).collect();
+        assert_eq!(keycodes[0], ["KC_Q", "LCTL_T(KC_A)", "LT(_NAV, KC_SPC)", "TD(td_esc)", "QK_MACRO_0", "LSFT(KC_1)"]);
+        assert_eq!(keycodes[1], ["KC_TRNS", "MO(_NAV)", "&bt BT_CLR", "KC_NO", "QK_BOOT", "OSM(MOD_LSFT)"]);
+        assert_eq!(parser::keycode(&keymap.encoders[0][0].cw), "KC_VOLU");
+        let tap_dance = &keymap.tap_dances["td_esc"];
+        assert_eq!(tap_dance.double_tap.as_ref().map(parser::keycode).as_deref(), Some("KC_CAPS"));
+        let steps = vec![
+            MacroStep::Down(vec![String::from("KC_LSFT")]),
+            MacroStep::Tap(vec![String::from("KC_H")]),
+            MacroStep::Up(vec![String::from("KC_LSFT")]),
+            MacroStep::Delay(50),
+        ];
+        assert_eq!(keymap.macros, vec![Macro { steps }]);
+        assert_eq!(keymap.combos.len(), 1);
+        assert_eq!(keymap.combos[0].positions, [0, 1]);
+        assert_eq!(keymap.combos[0].layers, Some(vec![String::from("Base")]));
+        assert_eq!(parser::keycode(&keymap.combos[0].result), "KC_TAB");
+    }
+
+    #[test]
+    fn bindings_are_split_with_their_parameters() {
+        let split = bindings("<&kp A &mt LSHIFT B>, <&trans &kp LS(LC(C))>");
+        assert_eq!(split, [vec!["&kp", "A"], vec!["&mt", "LSHIFT", "B"], vec!["&trans"], vec!["&kp", "LS(LC(C))"]]);
+    }
+}
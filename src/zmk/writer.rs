@@ -0,0 +1,413 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::keymap::layer::LayerNames;
+use crate::keymap::{CustomAction, Key, Keymap, Macro, MacroStep, Unsupported};
+use crate::qmk::keymap_extras::{Level, Locale};
+use crate::qmk::parser;
+use crate::zmk::keycodes;
+
+// Name of the `#define` of a layer, `NaVim` -> `NAVIM`
+fn layer_define(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect()
+}
+
+// Layer parameter of a layer key, its number when the QMK keycode has one, `MO(1)` -> `1`, else
+// the `#define` of the layer, `MO(_LOWER)` -> `LOWER`
+fn layer_param(layer: &str, index: Option<usize>) -> String {
+    index.map_or(layer_define(layer), |idx| idx.to_string())
+}
+
+fn node_name(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect()
+}
+
+// Node name with a prefix telling what it is, `TD_ESC_CAPS` -> `td_esc_caps`, `ESC` -> `td_esc`
+fn prefixed_name(prefix: &str, name: &str) -> String {
+    let name = node_name(name);
+    match name.starts_with(prefix) {
+        true => name,
+        false => format!("{prefix}_{name}"),
+    }
+}
+
+// ZMK key parameter of a QMK keycode, modifiers included, `LCTL(LALT(KC_DEL))` -> `LC(LA(DEL))`
+fn kp(keycode: &str) -> Option<String> {
+    lazy_static! {
+        static ref FUNCTION: Regex = Regex::new(r"^(\w+)\((.+)\)$").unwrap();
+    }
+    if let Some(f) = FUNCTION.captures(keycode) {
+        return Some(format!("{}({})", keycodes::mod_function(&f[1])?, kp(f[2].trim())?));
+    }
+    keycodes::to_zmk(keycode)
+}
+
+// Bindings of the characters of a string, for the macros
+fn text_bindings(text: &str, locale: &Locale) -> Option<Vec<String>> {
+    let mut bindings = Vec::new();
+    for c in text.chars() {
+        let output = c.to_uppercase().to_string();
+        let key = match c {
+            'a'..='z' => kp(&format!("KC_{output}"))?,
+            'A'..='Z' => format!("LS({})", kp(&format!("KC_{c}"))?),
+            ' ' => String::from("SPACE"),
+            '\n' => String::from("RET"),
+            '\t' => String::from("TAB"),
+            _ => {
+                let key = locale.keys.iter().find(|k| k.output == output)?;
+                match key.level {
+                    Level::Base => kp(&key.keycode)?,
+                    Level::Shift => format!("LS({})", kp(&key.keycode)?),
+                    _ => return None,
+                }
+            }
+        };
+        bindings.push(format!("&kp {key}"));
+    }
+    Some(bindings)
+}
+
+struct Writer<'a> {
+    keymap: &'a Keymap,
+    layer_names: &'a LayerNames,
+    unsupported: Vec<Unsupported>,
+}
+
+impl Writer<'_> {
+    fn mod_tap(&self, modifier: &str, key: &str) -> Option<String> {
+        Some(format!("&mt {} {}", keycodes::modifier_to_zmk(modifier)?, kp(key)?))
+    }
+
+    // ZMK binding of the key, or None if it has no equivalent
+    fn binding(&self, key: &Key) -> Option<String> {
+        lazy_static! {
+            static ref OSM: Regex = Regex::new(r"^OSM\(MOD_(\w+)\)$").unwrap();
+        }
+        let binding = match key {
+            Key::No => String::from("&none"),
+            Key::Trans => String::from("&trans"),
+            Key::BasicKey(key) => format!("&kp {}", kp(&format!("KC_{key}"))?),
+            Key::SpecialKey(key) => match key.as_str() {
+                "QK_BOOT" | "QK_BOOTLOADER" | "RESET" => String::from("&bootloader"),
+                "QK_RBT" | "QK_REBOOT" => String::from("&sys_reset"),
+                "CW_TOGG" | "QK_CAPS_WORD_TOGGLE" => String::from("&caps_word"),
+                "QK_REP" | "QK_REPEAT_KEY" => String::from("&key_repeat"),
+                "QK_GESC" | "QK_GRAVE_ESCAPE" => String::from("&gresc"),
+                _ => return None,
+            },
+            Key::LayerTap { layer, key, index } => format!("&lt {} {}", layer_param(layer, *index), kp(key)?),
+            Key::ModTap { modifier, key } => self.mod_tap(modifier, &format!("KC_{key}"))?,
+            Key::ModKey { modifier, key } => format!("&kp {}", kp(&format!("{modifier}(KC_{key})"))?),
+            Key::LayerChange { action, layer, index } => match action.as_str() {
+                "MO" => format!("&mo {}", layer_param(layer, *index)),
+                "TG" => format!("&tog {}", layer_param(layer, *index)),
+                // ZMK has no default layer, switching to the layer is the closest
+                "TO" | "DF" | "PDF" => format!("&to {}", layer_param(layer, *index)),
+                "OSL" => format!("&sl {}", layer_param(layer, *index)),
+                _ => return None,
+            },
+            Key::TapDance(name) => {
+                let td = self.keymap.tap_dances.get(name)?;
+                if td.function.is_some() || td.hold.is_some() || td.tap.is_none() {
+                    return None;
+                }
+                format!("&{}", prefixed_name("td", name))
+            }
+            Key::Macro(idx) => {
+                self.keymap.macros.get(*idx)?;
+                format!("&macro_{idx}")
+            }
+            // bindings read from a ZMK keymap
+            Key::Custom(name) if name.starts_with('&') => name.to_string(),
+            Key::Custom(name) => match self.keymap.custom_keycodes.get(name).and_then(|c| c.action.as_ref()) {
+                Some(CustomAction::Tap(key)) => self.binding(key)?,
+                Some(CustomAction::DefaultLayer(layer)) => format!("&to {}", layer_define(layer)),
+                _ => return None,
+            },
+            Key::Unknown(keycode) => match OSM.captures(keycode) {
+                Some(osm) => format!("&sk {}", keycodes::modifier_to_zmk(&osm[1])?),
+                None => format!("&kp {}", kp(keycode)?),
+            },
+            Key::Unicode(..) => return None,
+        };
+        Some(binding)
+    }
+
+    fn report(&mut self, layer: Option<usize>, index: Option<usize>, message: String) {
+        self.unsupported.push(Unsupported { layer, index, message });
+    }
+
+    fn key_binding(&mut self, layer: usize, index: usize, key: &Key) -> String {
+        if let Some(binding) = self.binding(key) {
+            if let Key::LayerChange { action, .. } = key {
+                if action == "DF" || action == "PDF" {
+                    self.report(Some(layer), Some(index), format!("{} written as &to", parser::keycode(key)));
+                }
+            }
+            return binding;
+        }
+        let message = match key {
+            Key::Unicode(..) => format!("unicode keycode {} is not supported by ZMK", parser::keycode(key)),
+            Key::TapDance(name) if self.keymap.tap_dances.contains_key(name) => {
+                format!("tap dance {name} has a hold action or a custom function")
+            }
+            _ => format!("{} has no ZMK equivalent", parser::keycode(key)),
+        };
+        self.report(Some(layer), Some(index), message);
+        String::from("&none")
+    }
+
+    fn macro_bindings(&mut self, idx: usize, m: &Macro, locale: &Locale) -> Vec<String> {
+        let mut bindings = Vec::new();
+        let mut mode = "tap";
+        for step in &m.steps {
+            let (step_mode, keycodes) = match step {
+                MacroStep::Text(text) => match text_bindings(text, locale) {
+                    Some(keys) => {
+                        if mode != "tap" {
+                            bindings.push(String::from("&macro_tap"));
+                            mode = "tap";
+                        }
+                        bindings.extend(keys);
+                        continue;
+                    }
+                    None => {
+                        self.report(None, None, format!("macro {idx}: text {text:?} can't be typed with US keycodes"));
+                        continue;
+                    }
+                },
+                MacroStep::Tap(keycodes) => ("tap", keycodes),
+                MacroStep::Down(keycodes) => ("press", keycodes),
+                MacroStep::Up(keycodes) => ("release", keycodes),
+                MacroStep::Delay(delay) => {
+                    bindings.push(format!("&macro_wait_time {delay}"));
+                    continue;
+                }
+            };
+            if step_mode != mode {
+                bindings.push(format!("&macro_{step_mode}"));
+                mode = step_mode;
+            }
+            for keycode in keycodes {
+                match kp(keycode) {
+                    Some(key) => bindings.push(format!("&kp {key}")),
+                    None => self.report(None, None, format!("macro {idx}: {keycode} has no ZMK equivalent")),
+                }
+            }
+        }
+        bindings
+    }
+
+    fn behaviors(&mut self) -> String {
+        let keymap = self.keymap;
+        let mut names: Vec<&String> = keymap.tap_dances.keys().collect();
+        names.sort();
+        let mut nodes = String::new();
+        for name in names {
+            let td = &keymap.tap_dances[name];
+            if td.function.is_some() || td.hold.is_some() || td.tap.is_none() {
+                continue;
+            }
+            let mut bindings = Vec::new();
+            for key in [&td.tap, &td.double_tap].into_iter().flatten() {
+                match self.binding(key) {
+                    Some(binding) => bindings.push(format!("<{binding}>")),
+                    None => {
+                        self.report(None, None, format!("tap dance {name}: {} has no ZMK equivalent", parser::keycode(key)));
+                        bindings.push(String::from("<&none>"));
+                    }
+                }
+            }
+            let label = prefixed_name("td", name);
+            nodes.push_str(&format!("        {label}: {label} {{\n"));
+            nodes.push_str("            compatible = \"zmk,behavior-tap-dance\";\n");
+            nodes.push_str("            #binding-cells = <0>;\n");
+            nodes.push_str(&format!("            bindings = {};\n", bindings.join(", ")));
+            nodes.push_str("        };\n");
+        }
+        if nodes.is_empty() {
+            return nodes;
+        }
+        format!("    behaviors {{\n{nodes}    }};\n\n")
+    }
+
+    fn macros(&mut self) -> String {
+        let keymap = self.keymap;
+        let locale = Locale::us();
+        let mut nodes = String::new();
+        for (idx, m) in keymap.macros.iter().enumerate() {
+            let bindings = self.macro_bindings(idx, m, &locale);
+            nodes.push_str(&format!("        macro_{idx}: macro_{idx} {{\n"));
+            nodes.push_str("            compatible = \"zmk,behavior-macro\";\n");
+            nodes.push_str("            #binding-cells = <0>;\n");
+            nodes.push_str(&format!("            bindings = <{}>;\n", bindings.join(" ")));
+            nodes.push_str("        };\n");
+        }
+        if nodes.is_empty() {
+            return nodes;
+        }
+        format!("    macros {{\n{nodes}    }};\n\n")
+    }
+
+    fn combos(&mut self) -> String {
+        let keymap = self.keymap;
+        let mut nodes = String::new();
+        for combo in &keymap.combos {
+            if combo.positions.len() != combo.keys.len() {
+                self.report(None, None, format!("combo {}: keys not found in the keymap", combo.name));
+                continue;
+            }
+            let Some(binding) = self.binding(&combo.result) else {
+                self.report(None, None, format!("combo {}: {} has no ZMK equivalent", combo.name, parser::keycode(&combo.result)));
+                continue;
+            };
+            let positions: Vec<String> = combo.positions.iter().map(|p| p.to_string()).collect();
+            nodes.push_str(&format!("        {} {{\n", prefixed_name("combo", &combo.name)));
+            nodes.push_str(&format!("            key-positions = <{}>;\n", positions.join(" ")));
+            nodes.push_str(&format!("            bindings = <{binding}>;\n"));
+            if let Some(layers) = &combo.layers {
+                let layers: Vec<String> = layers.iter().map(|l| layer_define(l)).collect();
+                nodes.push_str(&format!("            layers = <{}>;\n", layers.join(" ")));
+            }
+            nodes.push_str("        };\n");
+        }
+        if nodes.is_empty() {
+            return nodes;
+        }
+        format!("    combos {{\n        compatible = \"zmk,combos\";\n\n{nodes}    }};\n\n")
+    }
+
+    // Layer bindings, one row per line with the columns aligned
+    fn layer_bindings(&mut self, layer: usize) -> String {
+        let keymap = self.keymap;
+        let keys = &keymap.layers[layer];
+        let bindings: Vec<String> = keys.iter().enumerate().map(|(index, key)| self.key_binding(layer, index, key)).collect();
+        let columns = keymap.num_columns.max(1);
+        let mut widths = vec![0; columns];
+        for (i, binding) in bindings.iter().enumerate() {
+            widths[i % columns] = widths[i % columns].max(binding.chars().count());
+        }
+        let rows: Vec<String> = bindings
+            .chunks(columns)
+            .map(|row| {
+                let cells: Vec<String> = row.iter().enumerate().map(|(c, b)| format!("{b:<width$}", width = widths[c])).collect();
+                format!("                {}", cells.join("  ").trim_end())
+            })
+            .collect();
+        rows.join("\n")
+    }
+
+    fn sensor_bindings(&mut self, layer: usize) -> Option<String> {
+        let keymap = self.keymap;
+        let encoders = keymap.encoders.get(layer).filter(|e| !e.is_empty())?;
+        let mut bindings = Vec::new();
+        for (i, encoder) in encoders.iter().enumerate() {
+            match (kp_param(&self.binding(&encoder.cw)), kp_param(&self.binding(&encoder.ccw))) {
+                (Some(cw), Some(ccw)) => bindings.push(format!("&inc_dec_kp {cw} {ccw}")),
+                _ => {
+                    self.report(Some(layer), None, format!("encoder {i}: only keys can be sent by ZMK encoders"));
+                    bindings.push(String::from("&none &none"));
+                }
+            }
+        }
+        Some(bindings.join(" "))
+    }
+}
+
+fn kp_param(binding: &Option<String>) -> Option<&str> {
+    binding.as_deref()?.strip_prefix("&kp ")
+}
+
+// Writes the keymap as a ZMK .keymap file, with the keys, features and keycodes which ZMK doesn't
+// support returned separately
+pub fn keymap_to_zmk(keymap: &Keymap, layer_names: &LayerNames) -> (String, Vec<Unsupported>) {
+    let mut writer = Writer {
+        keymap,
+        layer_names,
+        unsupported: Vec::new(),
+    };
+    let mut zmk = String::new();
+    if !keymap.keyboard.is_empty() {
+        zmk.push_str(&format!("// Converted from the QMK keymap {}:{}\n\n", keymap.keyboard, keymap.keymap));
+    }
+    zmk.push_str("#include <behaviors.dtsi>\n");
+    zmk.push_str("#include <dt-bindings/zmk/keys.h>\n");
+    zmk.push_str("#include <dt-bindings/zmk/bt.h>\n\n");
+    for layer in 0..keymap.layers.len() {
+        zmk.push_str(&format!("#define {} {layer}\n", layer_define(&writer.layer_names.get(layer))));
+    }
+    zmk.push_str("\n/ {\n");
+    zmk.push_str(&writer.behaviors());
+    zmk.push_str(&writer.macros());
+    zmk.push_str(&writer.combos());
+    zmk.push_str("    keymap {\n");
+    zmk.push_str("        compatible = \"zmk,keymap\";\n");
+    for layer in 0..keymap.layers.len() {
+        let name = writer.layer_names.get(layer);
+        zmk.push_str(&format!("\n        {}_layer {{\n", node_name(&name)));
+        zmk.push_str(&format!("            display-name = \"{name}\";\n"));
+        zmk.push_str(&format!("            bindings = <\n{}\n            >;\n", writer.layer_bindings(layer)));
+        if let Some(sensor_bindings) = writer.sensor_bindings(layer) {
+            zmk.push_str(&format!("            sensor-bindings = <{sensor_bindings}>;\n"));
+        }
+        zmk.push_str("        };\n");
+    }
+    zmk.push_str("    };\n");
+    zmk.push_str("};\n");
+    if !keymap.key_overrides.is_empty() {
+        writer.report(None, None, String::from("key overrides are not supported by ZMK"));
+    }
+    (zmk, writer.unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zmk::parser::keymap_from_str;
+
+    fn layer_names() -> LayerNames {
+        LayerNames::new(["Base", "Lower"].iter().map(|name| name.to_string()).collect())
+    }
+
+    fn keycodes(keymap: &Keymap) -> Vec<Vec<String>> {
+        keymap.layers.iter().map(|layer| layer.iter().map(parser::keycode).collect()).collect()
+    }
+
+    #[test]
+    fn keymaps_round_trip() {
+        let json = r#"{
+            "keyboard": "", "keymap": "test", "layout": "",
+            "layers": [
+                ["KC_Q", "LCTL_T(KC_A)", "LT(_LOWER, KC_SPC)", "LSFT(KC_1)", "QK_MACRO_0", "MO(_LOWER)"],
+                ["KC_TRNS", "KC_NO", "QK_BOOT", "TG(_LOWER)", "CW_TOGG", "KC_VOLU"]
+            ],
+            "combos": [{"name": "tab", "keys": ["KC_Q", "LCTL_T(KC_A)"], "output": "KC_TAB"}],
+            "macros": [["hi", {"action": "tap", "keycodes": ["KC_ENT"]}]],
+            "encoders": [[{"ccw": "KC_VOLD", "cw": "KC_VOLU"}], [{"ccw": "KC_PGDN", "cw": "KC_PGUP"}]]
+        }"#;
+        let layer_names = layer_names();
+        let mut keymap = parser::keymap_from_str(json, 2, &layer_names).unwrap();
+        keymap.locate_combos(0);
+        let (zmk, unsupported) = keymap_to_zmk(&keymap, &layer_names);
+        assert!(unsupported.is_empty(), "{:?}", unsupported.iter().map(|u| &u.message).collect::<Vec<_>>());
+        assert!(zmk.contains("                &kp Q       &mt LCTRL A  &lt LOWER SPACE\n"), "{zmk}");
+
+        let (imported, imported_names) = keymap_from_str(&zmk, 0).unwrap();
+        assert_eq!(imported_names.names(), layer_names.names());
+        assert_eq!((imported.num_rows, imported.num_columns), (2, 3));
+        assert_eq!(keycodes(&imported), keycodes(&keymap));
+        assert_eq!(imported.combos[0].positions, [0, 1]);
+        assert_eq!(keymap_to_zmk(&imported, &imported_names).0, zmk);
+    }
+
+    #[test]
+    fn unsupported_keys_are_reported() {
+        let json = r#"{"keyboard": "", "keymap": "test", "layout": "", "layers": [["UM(0)", "KC_A"]]}"#;
+        let layer_names = layer_names();
+        let keymap = parser::keymap_from_str(json, 1, &layer_names).unwrap();
+        let (zmk, unsupported) = keymap_to_zmk(&keymap, &layer_names);
+        assert!(zmk.contains("&none  &kp A"), "{zmk}");
+        assert_eq!(unsupported.len(), 1);
+        assert_eq!((unsupported[0].layer, unsupported[0].index), (Some(0), Some(0)));
+        assert_eq!(unsupported[0].message, "unicode keycode UM(0) is not supported by ZMK");
+    }
+}
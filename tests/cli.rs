@@ -0,0 +1,50 @@
+use std::process::Command;
+
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_qzmk-drawer")).args(args).output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn lint_sarif_output_is_json() {
+    let output = run(&["lint", "--qmk-json", "data/planck-ergol.json", "--format", "sarif"]);
+    let sarif: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+    assert!(sarif["runs"][0]["results"].is_array());
+}
+
+#[test]
+fn lint_reads_the_keycodes_of_a_qmk_tree() {
+    let qmk_src_path = std::env::temp_dir().to_string_lossy().to_string();
+    let output = run(&["lint", "--qmk-json", "data/planck-ergol.json", "--qmk-src-path", &qmk_src_path, "--format", "json"]);
+    assert!(serde_json::from_str::<serde_json::Value>(&output).is_ok());
+}
+
+#[test]
+fn lint_json_output_is_json() {
+    let output = run(&["lint", "--qmk-json", "data/planck-ergol.json", "--format", "json"]);
+    assert!(serde_json::from_str::<serde_json::Value>(&output).is_ok());
+}
+
+#[test]
+fn encoded_keymap_is_json() {
+    let output = run(&["keycodes", "encode-keymap", "--qmk-json", "data/planck-ergol.json"]);
+    let keymap: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(keymap["layers"][0][0], 0x2B);
+}
+
+#[test]
+fn draw_writes_only_to_the_output_file() {
+    let yaml = std::env::temp_dir().join(format!("qzmk-drawer-draw-{}.yaml", std::process::id()));
+    let output = run(&["draw", "--qmk-json", "data/planck-ergol.json", "--output", yaml.to_str().unwrap()]);
+    assert!(std::fs::read_to_string(&yaml).unwrap().starts_with("layout:"));
+    assert!(!output.contains("layers:"));
+    std::fs::remove_file(&yaml).unwrap();
+}
+
+#[test]
+fn coverage_counts_the_characters_which_can_be_typed() {
+    let output = run(&["coverage", "--qmk-json", "data/planck-ergol.json", "--chars", "ae?€\u{1F600}"]);
+    assert!(output.contains("'?': 2 key(s), Qwerty with Shift\n"), "{output}");
+    assert!(output.ends_with("\n4/5 characters can be typed\nmissing: \u{1F600}\n"), "{output}");
+}